@@ -0,0 +1,59 @@
+//! Request and response bodies shared by the server and `actix_example_client`.
+
+use serde::{Deserialize, Serialize};
+
+/// `/url-dispatch/v2/path/{username}/{id}`
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PathInfo {
+    pub id: u32,
+    pub username: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Extractors {
+    pub id: u32,
+    pub username: String,
+}
+
+/// `/query?name=...`
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QueryStruct {
+    pub name: String,
+}
+
+/// Body of `POST /json`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JsonStruct {
+    pub name: String,
+}
+
+/// Body of `POST /form`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FormData {
+    pub username: String,
+}
+
+/// Returned by `/custom-type`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CustomType {
+    pub name: String,
+}
+
+#[cfg(feature = "responder")]
+impl actix_web::Responder for CustomType {
+    type Body = actix_web::body::BoxBody;
+
+    fn respond_to(self, _req: &actix_web::HttpRequest) -> actix_web::HttpResponse<Self::Body> {
+        let body = serde_json::to_string(&self).unwrap();
+
+        actix_web::HttpResponse::Ok()
+            .content_type(actix_web::http::header::ContentType::json())
+            .body(body)
+    }
+}
+
+/// Returned by `/testing/app-data`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AppState {
+    pub counter: i32,
+}
@@ -0,0 +1,39 @@
+//! Micro-benchmarks for extractor and responder hot paths: `cargo bench --bench handlers`.
+
+use actix_example::routes::extractors::Extractors;
+use actix_example::routes::handlers::CustomType;
+use actix_web::{body, http, rt, test, web, FromRequest, Responder};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+fn json_extractor(c: &mut Criterion) {
+    let runtime = rt::System::new();
+    let payload = web::Bytes::from_static(br#"{"id":42,"username":"ittokun"}"#);
+
+    c.bench_function("web::Json<Extractors>", |b| {
+        b.iter(|| {
+            let (req, mut pl) = test::TestRequest::default()
+                .insert_header(http::header::ContentType::json())
+                .set_payload(payload.clone())
+                .to_http_parts();
+            let info = runtime
+                .block_on(web::Json::<Extractors>::from_request(&req, &mut pl))
+                .unwrap();
+            black_box(info.id);
+        })
+    });
+}
+
+fn custom_type_responder(c: &mut Criterion) {
+    let runtime = rt::System::new();
+    let req = test::TestRequest::default().to_http_request();
+
+    c.bench_function("CustomType::respond_to", |b| {
+        b.iter(|| {
+            let res = black_box(CustomType { name: "ittokun".to_owned() }).respond_to(&req);
+            black_box(runtime.block_on(body::to_bytes(res.into_body())).unwrap());
+        })
+    });
+}
+
+criterion_group!(benches, json_extractor, custom_type_responder);
+criterion_main!(benches);
@@ -0,0 +1,305 @@
+//! Typed async client for the example server.
+//!
+//! ```no_run
+//! # async fn run() -> Result<(), actix_example_client::ClientError> {
+//! use actix_example_client::{api::PathInfo, Client};
+//!
+//! let client = Client::builder("https://127.0.0.1:8080").build()?;
+//! let info = PathInfo { id: 7, username: "alice".to_owned() };
+//! println!("{}", client.path_info(&info).await?);
+//! # Ok(())
+//! # }
+//! ```
+
+use reqwest::{header, Method, RequestBuilder, Response, StatusCode, Url};
+use serde::de::DeserializeOwned;
+
+use std::time::Duration;
+
+pub use actix_example_api as api;
+
+use api::{AppState, CustomType, FormData, JsonStruct, PathInfo, QueryStruct};
+
+const IDEMPOTENCY_KEY: &str = "idempotency-key";
+
+#[derive(Debug, derive_more::Display, derive_more::From)]
+pub enum ClientError {
+    #[display(fmt = "invalid base URL {}", _0)]
+    #[from(ignore)]
+    BaseUrl(String),
+    #[display(fmt = "request failed: {}", _0)]
+    Http(reqwest::Error),
+    /// The server answered with an error status.
+    #[display(fmt = "{}: {}", status, body)]
+    #[from(ignore)]
+    Status { status: StatusCode, body: String },
+}
+
+impl std::error::Error for ClientError {}
+
+/// How often and how soon failed requests are sent again.
+///
+/// Connection errors, timeouts, 429 and 502–504 are retried. POST requests carry an
+/// `Idempotency-Key`, so the server answers a retry of a request it already handled
+/// with the first response instead of handling it twice.
+#[derive(Clone, Debug)]
+pub struct Retry {
+    pub max_retries: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for Retry {
+    fn default() -> Self {
+        Retry {
+            max_retries: 2,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(2),
+        }
+    }
+}
+
+impl Retry {
+    pub fn none() -> Self {
+        Retry {
+            max_retries: 0,
+            ..Retry::default()
+        }
+    }
+
+    /// Delay before retry number `retry` (from 1), doubling each time. A `Retry-After`
+    /// from the server is honored up to `max_delay`.
+    fn delay(&self, retry: u32, retry_after: Option<Duration>) -> Duration {
+        let backoff = self.base_delay.saturating_mul(2u32.saturating_pow(retry.saturating_sub(1)));
+        backoff.max(retry_after.unwrap_or_default()).min(self.max_delay)
+    }
+}
+
+pub struct ClientBuilder {
+    base_url: String,
+    timeout: Duration,
+    connect_timeout: Duration,
+    retry: Retry,
+    root_certificates: Vec<reqwest::Certificate>,
+    identity: Option<reqwest::Identity>,
+    accept_invalid_certs: bool,
+}
+
+impl ClientBuilder {
+    /// Time allowed for a whole attempt, from connecting to reading the body.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    pub fn retry(mut self, retry: Retry) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Trusts server certificates issued by this PEM certificate, besides the system's.
+    pub fn root_certificate_pem(mut self, pem: &[u8]) -> Result<Self, ClientError> {
+        self.root_certificates.push(reqwest::Certificate::from_pem(pem)?);
+        Ok(self)
+    }
+
+    /// Presents a client certificate, for routes behind mutual TLS such as `/internal`.
+    pub fn identity_pem(mut self, cert: &[u8], pkcs8_key: &[u8]) -> Result<Self, ClientError> {
+        self.identity = Some(reqwest::Identity::from_pkcs8_pem(cert, pkcs8_key)?);
+        Ok(self)
+    }
+
+    /// Skips server certificate verification. Only for development servers.
+    pub fn danger_accept_invalid_certs(mut self, accept: bool) -> Self {
+        self.accept_invalid_certs = accept;
+        self
+    }
+
+    pub fn build(self) -> Result<Client, ClientError> {
+        let base = Url::parse(&self.base_url)
+            .ok()
+            .filter(|url| !url.cannot_be_a_base())
+            .ok_or_else(|| ClientError::BaseUrl(self.base_url.clone()))?;
+
+        let mut http = reqwest::Client::builder()
+            .timeout(self.timeout)
+            .connect_timeout(self.connect_timeout)
+            .danger_accept_invalid_certs(self.accept_invalid_certs);
+        for cert in self.root_certificates {
+            http = http.add_root_certificate(cert);
+        }
+        if let Some(identity) = self.identity {
+            http = http.identity(identity);
+        }
+
+        Ok(Client {
+            http: http.build()?,
+            base,
+            retry: self.retry,
+        })
+    }
+}
+
+/// One method per route, taking and returning the server's own types.
+#[derive(Clone, Debug)]
+pub struct Client {
+    http: reqwest::Client,
+    base: Url,
+    retry: Retry,
+}
+
+impl Client {
+    pub fn builder(base_url: impl Into<String>) -> ClientBuilder {
+        ClientBuilder {
+            base_url: base_url.into(),
+            timeout: Duration::from_secs(10),
+            connect_timeout: Duration::from_secs(5),
+            retry: Retry::default(),
+            root_certificates: vec![],
+            identity: None,
+            accept_invalid_certs: false,
+        }
+    }
+
+    /// The URL for `segments`, each one escaped, below the base URL.
+    fn url(&self, segments: &[&str]) -> Url {
+        let mut url = self.base.clone();
+        url.path_segments_mut()
+            .expect("base URL checked in build")
+            .pop_if_empty()
+            .extend(segments);
+        url
+    }
+
+    fn request(&self, method: Method, segments: &[&str]) -> RequestBuilder {
+        let mut req = self.http.request(method.clone(), self.url(segments));
+        if method == Method::POST {
+            let key = format!("{:032x}", rand::random::<u128>());
+            req = req.header(IDEMPOTENCY_KEY, key);
+        }
+        req
+    }
+
+    /// Sends `req`, and copies of it as the retry policy allows, until it succeeds.
+    async fn send(&self, req: RequestBuilder) -> Result<Response, ClientError> {
+        let mut retry = 0;
+        loop {
+            let attempt = req.try_clone().expect("request bodies are buffered");
+            let result = attempt.send().await;
+            let retryable = match &result {
+                Ok(res) => matches!(
+                    res.status(),
+                    StatusCode::TOO_MANY_REQUESTS
+                        | StatusCode::BAD_GATEWAY
+                        | StatusCode::SERVICE_UNAVAILABLE
+                        | StatusCode::GATEWAY_TIMEOUT
+                ),
+                Err(err) => err.is_connect() || err.is_timeout(),
+            };
+
+            if !retryable || retry >= self.retry.max_retries {
+                let res = result?;
+                let status = res.status();
+                if status.is_client_error() || status.is_server_error() {
+                    let body = res.text().await.unwrap_or_default();
+                    return Err(ClientError::Status { status, body });
+                }
+                return Ok(res);
+            }
+
+            retry += 1;
+            let retry_after = result
+                .ok()
+                .and_then(|res| res.headers().get(header::RETRY_AFTER)?.to_str().ok()?.parse().ok())
+                .map(Duration::from_secs);
+            tokio::time::sleep(self.retry.delay(retry, retry_after)).await;
+        }
+    }
+
+    async fn send_text(&self, req: RequestBuilder) -> Result<String, ClientError> {
+        Ok(self.send(req).await?.text().await?)
+    }
+
+    async fn send_json<T: DeserializeOwned>(&self, req: RequestBuilder) -> Result<T, ClientError> {
+        Ok(self.send(req).await?.json().await?)
+    }
+
+    /// `GET /hello`
+    pub async fn hello(&self) -> Result<String, ClientError> {
+        self.send_text(self.request(Method::GET, &["hello"])).await
+    }
+
+    /// `POST /echo`
+    pub async fn echo(&self, body: impl Into<String>) -> Result<String, ClientError> {
+        self.send_text(self.request(Method::POST, &["echo"]).body(body.into())).await
+    }
+
+    /// `GET /url-dispatch/v2/path/{username}/{id}`
+    pub async fn path_info(&self, info: &PathInfo) -> Result<String, ClientError> {
+        let id = info.id.to_string();
+        let req = self.request(Method::GET, &["url-dispatch", "v2", "path", &info.username, &id]);
+        self.send_text(req).await
+    }
+
+    /// `GET /query?name=...`
+    pub async fn query(&self, query: &QueryStruct) -> Result<String, ClientError> {
+        self.send_text(self.request(Method::GET, &["query"]).query(query)).await
+    }
+
+    /// `POST /json`
+    pub async fn json(&self, body: &JsonStruct) -> Result<String, ClientError> {
+        self.send_text(self.request(Method::POST, &["json"]).json(body)).await
+    }
+
+    /// `POST /form`
+    pub async fn form(&self, form: &FormData) -> Result<String, ClientError> {
+        self.send_text(self.request(Method::POST, &["form"]).form(form)).await
+    }
+
+    /// `GET /custom-type`
+    pub async fn custom_type(&self) -> Result<CustomType, ClientError> {
+        self.send_json(self.request(Method::GET, &["custom-type"])).await
+    }
+
+    /// `GET /testing/app-data`
+    pub async fn app_state(&self) -> Result<AppState, ClientError> {
+        self.send_json(self.request(Method::GET, &["testing", "app-data"])).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_url() {
+        let client = Client::builder("http://localhost:8080/base/").build().unwrap();
+        assert_eq!(
+            client.url(&["url-dispatch", "v2", "path", "a b/c", "7"]).as_str(),
+            "http://localhost:8080/base/url-dispatch/v2/path/a%20b%2Fc/7"
+        );
+        assert!(matches!(
+            Client::builder("mailto:ops@example.com").build(),
+            Err(ClientError::BaseUrl(_))
+        ));
+    }
+
+    #[test]
+    fn test_retry_delay() {
+        let retry = Retry {
+            max_retries: 5,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(1),
+        };
+        assert_eq!(retry.delay(1, None), Duration::from_millis(100));
+        assert_eq!(retry.delay(3, None), Duration::from_millis(400));
+        assert_eq!(retry.delay(10, None), Duration::from_secs(1));
+        assert_eq!(retry.delay(1, Some(Duration::from_millis(500))), Duration::from_millis(500));
+        assert_eq!(retry.delay(1, Some(Duration::from_secs(30))), Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,157 @@
+//! Runs the client against the real app, served in-process on a loopback port.
+
+use actix_example::tls::testing::self_signed;
+use actix_example::{create_app, AppData};
+use actix_example_client::api::{AppState, CustomType, FormData, JsonStruct, PathInfo, QueryStruct};
+use actix_example_client::{Client, ClientError, Retry};
+use actix_web::{rt, web, App, HttpRequest, HttpResponse, HttpServer};
+use openssl::ssl::{SslAcceptor, SslAcceptorBuilder, SslMethod};
+
+use std::net::{SocketAddr, TcpListener};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+async fn serve_app(tls: Option<SslAcceptorBuilder>) -> SocketAddr {
+    let data = AppData::ephemeral().await;
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = HttpServer::new(move || create_app(data.clone()))
+        .workers(1)
+        .disable_signals();
+    let server = match tls {
+        Some(tls) => server.listen_openssl(listener, tls).unwrap(),
+        None => server.listen(listener).unwrap(),
+    };
+    rt::spawn(server.run());
+    addr
+}
+
+fn fast_retry(max_retries: u32) -> Retry {
+    Retry {
+        max_retries,
+        base_delay: Duration::from_millis(10),
+        max_delay: Duration::from_millis(50),
+    }
+}
+
+#[actix_web::test]
+async fn test_typed_routes() {
+    let addr = serve_app(None).await;
+    let client = Client::builder(format!("http://{}", addr)).build().unwrap();
+
+    assert!(!client.hello().await.unwrap().is_empty());
+    assert_eq!(client.echo("ping").await.unwrap(), "ping");
+
+    let info = PathInfo {
+        id: 7,
+        username: "alice".to_owned(),
+    };
+    let welcome = client.path_info(&info).await.unwrap();
+    assert!(welcome.contains("alice") && welcome.contains('7'), "{}", welcome);
+
+    let name = "alice".to_owned();
+    assert!(client.query(&QueryStruct { name: name.clone() }).await.unwrap().contains("alice"));
+    assert!(client.json(&JsonStruct { name: name.clone() }).await.unwrap().contains("alice"));
+    assert!(client.form(&FormData { username: name }).await.unwrap().contains("alice"));
+
+    assert_eq!(
+        client.custom_type().await.unwrap(),
+        CustomType {
+            name: "ittokun".to_owned()
+        }
+    );
+    assert_eq!(client.app_state().await.unwrap(), AppState { counter: 4 });
+}
+
+#[actix_web::test]
+async fn test_tls() {
+    let server = self_signed("localhost");
+    let mut acceptor = SslAcceptor::mozilla_intermediate(SslMethod::tls()).unwrap();
+    acceptor.set_private_key(&server.key).unwrap();
+    acceptor.set_certificate(&server.cert).unwrap();
+    let addr = serve_app(Some(acceptor)).await;
+    let url = format!("https://localhost:{}", addr.port());
+
+    let client = Client::builder(&url)
+        .root_certificate_pem(&server.cert.to_pem().unwrap())
+        .unwrap()
+        .build()
+        .unwrap();
+    assert_eq!(client.echo("over tls").await.unwrap(), "over tls");
+
+    // The server's certificate is not trusted by default.
+    let untrusted = Client::builder(&url).retry(Retry::none()).build().unwrap();
+    assert!(matches!(untrusted.hello().await, Err(ClientError::Http(_))));
+}
+
+#[actix_web::test]
+async fn test_retries_and_timeouts() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let keys = Arc::new(Mutex::new(vec![]));
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = {
+        let (calls, keys) = (calls.clone(), keys.clone());
+        HttpServer::new(move || {
+            let (calls, keys) = (calls.clone(), keys.clone());
+            App::new()
+                // Unavailable for the first two calls of every three.
+                .route(
+                    "/echo",
+                    web::post().to(move |req: HttpRequest, body: String| {
+                        let (calls, keys) = (calls.clone(), keys.clone());
+                        async move {
+                            let key = req.headers().get("idempotency-key").cloned();
+                            keys.lock().unwrap().push(key.unwrap().to_str().unwrap().to_owned());
+                            if calls.fetch_add(1, Ordering::SeqCst) % 3 < 2 {
+                                HttpResponse::ServiceUnavailable().finish()
+                            } else {
+                                HttpResponse::Ok().body(body)
+                            }
+                        }
+                    }),
+                )
+                .route(
+                    "/hello",
+                    web::get().to(|| async {
+                        rt::time::sleep(Duration::from_millis(500)).await;
+                        "late"
+                    }),
+                )
+        })
+        .workers(1)
+        .disable_signals()
+        .listen(listener)
+        .unwrap()
+        .run()
+    };
+    rt::spawn(server);
+    let base = format!("http://{}", addr);
+
+    let client = Client::builder(&base).retry(fast_retry(2)).build().unwrap();
+    assert_eq!(client.echo("again").await.unwrap(), "again");
+    // Every attempt of one call carries the same key.
+    {
+        let keys = keys.lock().unwrap();
+        assert_eq!(keys.len(), 3);
+        assert!(keys.iter().all(|key| *key == keys[0]));
+    }
+
+    let client = Client::builder(&base).retry(fast_retry(1)).build().unwrap();
+    match client.echo("give up").await {
+        Err(ClientError::Status { status, .. }) => assert_eq!(status.as_u16(), 503),
+        other => panic!("expected 503, got {:?}", other),
+    }
+    assert_eq!(calls.load(Ordering::SeqCst), 5);
+
+    let client = Client::builder(&base)
+        .timeout(Duration::from_millis(100))
+        .retry(Retry::none())
+        .build()
+        .unwrap();
+    match client.hello().await {
+        Err(ClientError::Http(err)) => assert!(err.is_timeout()),
+        other => panic!("expected a timeout, got {:?}", other),
+    }
+}
@@ -0,0 +1,38 @@
+## templates
+site-name = Actix Web Example
+nav-users = Users
+
+## application
+hello-world = Hello world!
+hello-app = Hello { $app_name }, Request number: { $counter }
+hey-there = Hey there!
+
+## extractors
+welcome = Welcome { $name }
+welcome-post = Welcome { $name }, post_id: { $post_id }
+count = count: { $count }
+count-added = Count: { $count }
+
+## handlers
+responder-hello = Hello World!
+bad-data = Bad data
+register-hello = Hello!
+
+## errors
+error-custom = my error: { $name }
+error-internal = internal error
+error-bad-request = bad request
+error-timeout = timeout
+
+## url_dispatch
+url-dispatch-hello = Hello
+url-dispatch-show-users = Show users
+user-detail = User detail: { $id }
+match-values = Values { $v1 } { $v2 } { $v3 } { $v4 }
+welcome-id = Welcome { $username }! id: { $id }
+
+## server
+sleep-response = response
+
+## testing
+testing-hello = hello: { $path }
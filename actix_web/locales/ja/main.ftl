@@ -0,0 +1,38 @@
+## templates
+site-name = Actix Web サンプル
+nav-users = ユーザー
+
+## application
+hello-world = こんにちは、世界！
+hello-app = こんにちは { $app_name }、リクエスト番号: { $counter }
+hey-there = やあ！
+
+## extractors
+welcome = ようこそ { $name }
+welcome-post = ようこそ { $name }、post_id: { $post_id }
+count = カウント: { $count }
+count-added = カウント: { $count }
+
+## handlers
+responder-hello = こんにちは、世界！
+bad-data = 不正なデータです
+register-hello = こんにちは！
+
+## errors
+error-custom = エラー: { $name }
+error-internal = 内部エラー
+error-bad-request = 不正なリクエスト
+error-timeout = タイムアウト
+
+## url_dispatch
+url-dispatch-hello = こんにちは
+url-dispatch-show-users = ユーザー一覧
+user-detail = ユーザー詳細: { $id }
+match-values = 値 { $v1 } { $v2 } { $v3 } { $v4 }
+welcome-id = ようこそ { $username }！ id: { $id }
+
+## server
+sleep-response = レスポンス
+
+## testing
+testing-hello = こんにちは: { $path }
@@ -0,0 +1,565 @@
+use actix_web::body::MessageBody;
+use actix_web::dev::{Payload, ServiceRequest, ServiceResponse};
+use actix_web::error::PayloadError;
+use actix_web::middleware::Next;
+use actix_web::{http, web, Error, HttpMessage};
+use futures::stream::{self, StreamExt};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use crate::tls::ClientCert;
+
+const REDACTED: &str = "[redacted]";
+/// Records one [`AuditLog::query`] returns at most, whatever `limit` asks for.
+pub const MAX_QUERY_RECORDS: usize = 1000;
+
+/// Where audit records go and what they may contain.
+#[derive(Clone, Debug)]
+pub struct AuditConfig {
+    pub path: PathBuf,
+    /// Size at which the file is rotated to `<path>.1`.
+    pub max_bytes: u64,
+    /// Rotated files kept besides the current one.
+    pub max_files: usize,
+    /// Request body bytes kept; larger bodies are recorded as truncated, without content.
+    pub max_body: usize,
+    /// JSON keys and form fields whose name contains one of these (case-insensitively) are redacted.
+    pub redact: Vec<String>,
+}
+
+impl Default for AuditConfig {
+    fn default() -> Self {
+        AuditConfig {
+            path: "audit.jsonl".into(),
+            max_bytes: 10 * 1024 * 1024,
+            max_files: 5,
+            max_body: 16 * 1024,
+            redact: ["password", "secret", "token", "authorization", "api_key"]
+                .map(String::from)
+                .to_vec(),
+        }
+    }
+}
+
+/// One mutating request, as written to the audit log.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    /// Milliseconds since the Unix epoch.
+    pub at: i64,
+    /// Common name (or subject) of the client certificate.
+    pub identity: Option<String>,
+    pub method: String,
+    /// Pattern of the resource that served the request, if any did.
+    pub route: Option<String>,
+    pub path: String,
+    pub status: u16,
+    /// Time until the response head was ready; streamed bodies are not waited for.
+    pub duration_ms: f64,
+    /// JSON and form bodies, with sensitive fields redacted.
+    pub body: Option<Value>,
+    /// Other complete bodies, which cannot be redacted, are only summed up.
+    #[serde(default)]
+    pub digest: Option<BodyDigest>,
+    pub truncated: bool,
+}
+
+/// What is recorded of a body that is not shown.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BodyDigest {
+    pub bytes: usize,
+    pub sha256: String,
+}
+
+impl BodyDigest {
+    fn of(body: &[u8]) -> Self {
+        BodyDigest {
+            bytes: body.len(),
+            sha256: openssl::sha::sha256(body)
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect(),
+        }
+    }
+}
+
+/// Filters for [`AuditLog::query`]; `from` is inclusive, `to` exclusive.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct AuditQuery {
+    pub from: Option<i64>,
+    pub to: Option<i64>,
+    pub route: Option<String>,
+    /// Defaults to, and is capped at, [`MAX_QUERY_RECORDS`].
+    pub limit: Option<usize>,
+}
+
+impl AuditQuery {
+    fn matches(&self, record: &AuditRecord) -> bool {
+        self.from.is_none_or(|from| record.at >= from)
+            && self.to.is_none_or(|to| record.at < to)
+            && self.route.as_ref().is_none_or(|route| record.route.as_ref() == Some(route))
+    }
+}
+
+struct Writer {
+    file: File,
+    len: u64,
+}
+
+/// Append-only JSON Lines file, rotated by size and shared by all workers.
+#[derive(Clone)]
+pub struct AuditLog {
+    config: Arc<AuditConfig>,
+    writer: Arc<Mutex<Writer>>,
+}
+
+impl AuditLog {
+    pub fn open(config: AuditConfig) -> io::Result<Self> {
+        let writer = Writer::open(&config.path)?;
+        Ok(AuditLog {
+            config: Arc::new(config),
+            writer: Arc::new(Mutex::new(writer)),
+        })
+    }
+
+    pub fn config(&self) -> &AuditConfig {
+        &self.config
+    }
+
+    pub fn append(&self, record: &AuditRecord) -> io::Result<()> {
+        let mut line = serde_json::to_vec(record)?;
+        line.push(b'\n');
+
+        let mut writer = self.writer.lock().unwrap();
+        if writer.len > 0 && writer.len + line.len() as u64 > self.config.max_bytes {
+            self.rotate()?;
+            *writer = Writer::open(&self.config.path)?;
+        }
+        writer.file.write_all(&line)?;
+        writer.len += line.len() as u64;
+        Ok(())
+    }
+
+    /// `<path>.1` becomes `<path>.2` and so on, dropping the oldest; the current file becomes `<path>.1`.
+    fn rotate(&self) -> io::Result<()> {
+        let path = &self.config.path;
+        if self.config.max_files == 0 {
+            return fs::remove_file(path);
+        }
+        remove_if_exists(&rotated(path, self.config.max_files))?;
+        for n in (1..self.config.max_files).rev() {
+            rename_if_exists(&rotated(path, n), &rotated(path, n + 1))?;
+        }
+        fs::rename(path, rotated(path, 1))
+    }
+
+    /// The oldest matching records from the rotated files and the current one, oldest first.
+    /// Reading stops once `limit` records are found.
+    pub fn query(&self, query: &AuditQuery) -> io::Result<Vec<AuditRecord>> {
+        let limit = query.limit.unwrap_or(MAX_QUERY_RECORDS).min(MAX_QUERY_RECORDS);
+        let path = &self.config.path;
+        let files = (1..=self.config.max_files)
+            .rev()
+            .map(|n| rotated(path, n))
+            .chain([path.clone()]);
+
+        let mut records = vec![];
+        for file in files {
+            let file = match File::open(&file) {
+                Ok(file) => file,
+                Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+                Err(err) => return Err(err),
+            };
+            for line in BufReader::new(file).lines() {
+                // A line cut short by a crash is skipped rather than failing the whole query.
+                let Ok(record) = serde_json::from_str::<AuditRecord>(&line?) else {
+                    continue;
+                };
+                if records.len() == limit {
+                    return Ok(records);
+                }
+                if query.matches(&record) {
+                    records.push(record);
+                }
+            }
+        }
+        Ok(records)
+    }
+}
+
+impl Writer {
+    fn open(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let len = file.metadata()?.len();
+        Ok(Writer { file, len })
+    }
+}
+
+fn rotated(path: &Path, n: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{}", n));
+    name.into()
+}
+
+fn remove_if_exists(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+        _ => Ok(()),
+    }
+}
+
+fn rename_if_exists(from: &Path, to: &Path) -> io::Result<()> {
+    match fs::rename(from, to) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+        _ => Ok(()),
+    }
+}
+
+fn now_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as i64
+}
+
+fn is_sensitive(name: &str, redact: &[String]) -> bool {
+    let name = name.to_lowercase();
+    redact.iter().any(|word| name.contains(word.as_str()))
+}
+
+fn redact_json(value: &mut Value, redact: &[String]) {
+    match value {
+        Value::Object(fields) => {
+            for (name, value) in fields {
+                if is_sensitive(name, redact) {
+                    *value = Value::from(REDACTED);
+                } else {
+                    redact_json(value, redact);
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(|item| redact_json(item, redact)),
+        _ => {}
+    }
+}
+
+/// The body as it should appear in the log, or `None` when it cannot be shown safely:
+/// only JSON and forms have fields to redact, so text is not shown either.
+fn redacted_body(content_type: &str, body: &[u8], redact: &[String]) -> Option<Value> {
+    if body.is_empty() {
+        return None;
+    }
+    let mime = content_type.split(';').next().unwrap_or_default().trim();
+
+    if mime == "application/json" || mime.ends_with("+json") {
+        let mut value: Value = serde_json::from_slice(body).ok()?;
+        redact_json(&mut value, redact);
+        Some(value)
+    } else if mime == "application/x-www-form-urlencoded" {
+        let fields: Vec<(String, String)> = serde_urlencoded::from_bytes(body).ok()?;
+        let fields = fields
+            .into_iter()
+            .map(|(name, value)| {
+                let value = if is_sensitive(&name, redact) { REDACTED.to_owned() } else { value };
+                (name, Value::from(value))
+            })
+            .collect();
+        Some(Value::Object(fields))
+    } else {
+        None
+    }
+}
+
+/// Reads up to `max_body` bytes of the payload and puts them back in front of the rest,
+/// so handlers still see the whole body. Returns what was read and whether that is all of it.
+async fn capture_body(req: &mut ServiceRequest, max_body: usize) -> (web::Bytes, bool) {
+    let mut payload = req.take_payload();
+    let mut head = web::BytesMut::new();
+    let mut chunks: Vec<Result<web::Bytes, PayloadError>> = vec![];
+    let mut complete = false;
+
+    while head.len() <= max_body {
+        match payload.next().await {
+            Some(Ok(chunk)) => {
+                head.extend_from_slice(&chunk);
+                chunks.push(Ok(chunk));
+            }
+            Some(Err(err)) => {
+                chunks.push(Err(err));
+                break;
+            }
+            None => {
+                complete = true;
+                break;
+            }
+        }
+    }
+
+    let failed = chunks.last().is_some_and(Result::is_err);
+    let rest = if complete || failed { None } else { Some(payload) };
+    let replay = stream::iter(chunks).chain(stream::iter(rest).flatten());
+    req.set_payload(Payload::Stream { payload: Box::pin(replay) });
+
+    let complete = complete && head.len() <= max_body;
+    (head.freeze(), complete)
+}
+
+/// Records every request with an unsafe method (POST, PUT, PATCH, DELETE, ...) to the
+/// [`AuditLog`] in `web::Data`, after the response is ready. Without one, nothing is recorded.
+pub async fn record(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let log = match req.app_data::<web::Data<AuditLog>>() {
+        Some(log) if !req.method().is_safe() => log.clone(),
+        _ => return next.call(req).await,
+    };
+
+    let start = Instant::now();
+    let at = now_millis();
+    let identity = req
+        .conn_data::<ClientCert>()
+        .map(|cert| cert.common_name.clone().unwrap_or_else(|| cert.subject.clone()));
+    let content_type = req
+        .headers()
+        .get(http::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default()
+        .to_owned();
+    let (body, complete) = capture_body(&mut req, log.config().max_body).await;
+    let (body, digest) = match complete {
+        true => match redacted_body(&content_type, &body, &log.config().redact) {
+            Some(shown) => (Some(shown), None),
+            None => (None, (!body.is_empty()).then(|| BodyDigest::of(&body))),
+        },
+        false => (None, None),
+    };
+    let (method, path) = (req.method().to_string(), req.path().to_owned());
+
+    let res = next.call(req).await;
+    let (route, status) = match &res {
+        Ok(res) => (res.request().match_pattern(), res.status()),
+        Err(err) => (None, err.as_response_error().status_code()),
+    };
+    let record = AuditRecord {
+        at,
+        identity,
+        method,
+        route,
+        path,
+        status: status.as_u16(),
+        duration_ms: start.elapsed().as_secs_f64() * 1000.0,
+        body,
+        digest,
+        truncated: !complete,
+    };
+
+    // Written before the response goes out, so an acknowledged request is always on record.
+    let written = web::block(move || log.append(&record)).await;
+    if let Err(err) = written.map_err(Error::from).and_then(|res| res.map_err(Error::from)) {
+        warn!("audit record not written: {}", err);
+    }
+    res
+}
+
+/// Audit logs in a fresh temporary directory, for tests.
+#[cfg(test)]
+pub mod testing {
+    use super::*;
+
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    pub fn config() -> AuditConfig {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let dir = std::env::temp_dir().join(format!(
+            "actix-example-audit-{}-{}",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        AuditConfig {
+            path: dir.join("audit.jsonl"),
+            ..AuditConfig::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::middleware::from_fn;
+    use actix_web::{test, App, HttpResponse};
+
+    fn record_at(at: i64, route: &str) -> AuditRecord {
+        AuditRecord {
+            at,
+            identity: None,
+            method: "POST".to_owned(),
+            route: Some(route.to_owned()),
+            path: route.to_owned(),
+            status: 200,
+            duration_ms: 1.0,
+            body: None,
+            digest: None,
+            truncated: false,
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_redacted_body() {
+        let redact = AuditConfig::default().redact;
+
+        let body = redacted_body(
+            "application/json; charset=utf-8",
+            br#"{"name":"ferris","Password":"hunter2","nested":[{"api_key":"k","id":1}]}"#,
+            &redact,
+        );
+        assert_eq!(
+            body,
+            Some(serde_json::json!({
+                "name": "ferris",
+                "Password": REDACTED,
+                "nested": [{ "api_key": REDACTED, "id": 1 }],
+            }))
+        );
+
+        let body = redacted_body("application/x-www-form-urlencoded", b"username=ferris&access_token=t", &redact);
+        assert_eq!(body, Some(serde_json::json!({ "username": "ferris", "access_token": REDACTED })));
+
+        assert_eq!(redacted_body("text/plain", b"password=hunter2", &redact), None);
+        assert_eq!(redacted_body("application/json", b"{not json", &redact), None);
+        assert_eq!(redacted_body("application/octet-stream", b"\x00\x01", &redact), None);
+    }
+
+    #[actix_web::test]
+    async fn test_rotation_and_query() {
+        let config = AuditConfig {
+            max_bytes: 400,
+            max_files: 2,
+            ..testing::config()
+        };
+        let log = AuditLog::open(config.clone()).unwrap();
+        for at in 0..20 {
+            let route = if at % 2 == 0 { "/echo" } else { "/json" };
+            log.append(&record_at(at, route)).unwrap();
+        }
+
+        assert!(fs::metadata(&config.path).unwrap().len() <= config.max_bytes);
+        assert!(rotated(&config.path, 2).exists());
+        assert!(!rotated(&config.path, 3).exists());
+
+        // The oldest records were rotated out; what is left comes back in order.
+        let all = log.query(&AuditQuery::default()).unwrap();
+        let ats: Vec<_> = all.iter().map(|record| record.at).collect();
+        assert!(ats.len() < 20);
+        assert!(ats.windows(2).all(|pair| pair[0] + 1 == pair[1]));
+        assert_eq!(ats.last(), Some(&19));
+
+        let query = AuditQuery {
+            from: Some(16),
+            to: Some(19),
+            route: Some("/echo".to_owned()),
+            limit: None,
+        };
+        let ats: Vec<_> = log.query(&query).unwrap().iter().map(|record| record.at).collect();
+        assert_eq!(ats, [16, 18]);
+
+        let first = all[0].at;
+        let query = AuditQuery {
+            from: Some(first + 1),
+            limit: Some(3),
+            ..AuditQuery::default()
+        };
+        let ats: Vec<_> = log.query(&query).unwrap().iter().map(|record| record.at).collect();
+        assert_eq!(ats, [first + 1, first + 2, first + 3]);
+
+        // Reopening continues the current file.
+        let reopened = AuditLog::open(config).unwrap();
+        assert_eq!(reopened.query(&AuditQuery::default()).unwrap(), all);
+    }
+
+    #[actix_web::test]
+    async fn test_query_limit_is_capped() {
+        let log = AuditLog::open(testing::config()).unwrap();
+        for at in 0..=MAX_QUERY_RECORDS as i64 {
+            log.append(&record_at(at, "/echo")).unwrap();
+        }
+
+        let query = AuditQuery {
+            limit: Some(MAX_QUERY_RECORDS + 1),
+            ..AuditQuery::default()
+        };
+        let records = log.query(&query).unwrap();
+        assert_eq!(records.len(), MAX_QUERY_RECORDS);
+        assert_eq!(records.last().unwrap().at, MAX_QUERY_RECORDS as i64 - 1);
+    }
+
+    #[actix_web::test]
+    async fn test_record_middleware() {
+        let log = AuditLog::open(AuditConfig {
+            max_body: 64,
+            ..testing::config()
+        })
+        .unwrap();
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(log.clone()))
+                .wrap(from_fn(record))
+                .route("/users/{name}", web::get().to(HttpResponse::Ok))
+                .route("/users/{name}", web::put().to(HttpResponse::Created))
+                .route("/echo", web::post().to(|body: String| async move { body })),
+        )
+        .await;
+
+        let req = test::TestRequest::put()
+            .uri("/users/ferris")
+            .set_json(serde_json::json!({ "password": "hunter2", "email": "f@rust" }))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), http::StatusCode::CREATED);
+
+        let req = test::TestRequest::get().uri("/users/ferris").to_request();
+        test::call_service(&app, req).await;
+
+        // The handler still gets the whole body when only part of it is recorded.
+        let long = "x".repeat(100);
+        let req = test::TestRequest::post().uri("/echo").set_payload(long.clone()).to_request();
+        assert_eq!(test::call_and_read_body(&app, req).await, long.as_bytes());
+
+        // Text has no fields to redact, so only its size and hash are kept.
+        let secret = "token hunter2";
+        let req = test::TestRequest::post()
+            .uri("/echo")
+            .insert_header((http::header::CONTENT_TYPE, "text/plain"))
+            .set_payload(secret)
+            .to_request();
+        assert_eq!(test::call_and_read_body(&app, req).await, secret.as_bytes());
+
+        let records = log.query(&AuditQuery::default()).unwrap();
+        assert_eq!(records.len(), 3);
+        let lines = fs::read_to_string(&log.config().path).unwrap();
+        assert!(!lines.contains("hunter2"), "{}", lines);
+
+        let put = &records[0];
+        assert_eq!((put.method.as_str(), put.route.as_deref()), ("PUT", Some("/users/{name}")));
+        assert_eq!((put.path.as_str(), put.status, put.identity.as_ref()), ("/users/ferris", 201, None));
+        assert_eq!(put.body, Some(serde_json::json!({ "password": REDACTED, "email": "f@rust" })));
+        assert!(!put.truncated);
+
+        let echo = &records[1];
+        assert_eq!((echo.route.as_deref(), echo.body.as_ref(), echo.truncated), (Some("/echo"), None, true));
+        assert_eq!(echo.digest, None);
+
+        let text = &records[2];
+        assert_eq!((text.body.as_ref(), text.truncated), (None, false));
+        assert_eq!(text.digest, Some(BodyDigest::of(secret.as_bytes())));
+        assert_eq!(text.digest.as_ref().unwrap().bytes, secret.len());
+    }
+}
@@ -0,0 +1,350 @@
+//! Drives the app on a loopback port and reports throughput and latency percentiles.
+//!
+//! ```text
+//! cargo run --release --bin loadtest -- \
+//!     --concurrency 32 --duration 10 --route GET:/hello=3 --route GET:/custom-type --close
+//! ```
+
+use actix_example::{create_app, AppData};
+use actix_web::dev::ResourceDef;
+use actix_web::introspection::{IntrospectionNode, IntrospectionTree, ResourceType};
+use actix_web::{http, rt, test, web, HttpServer};
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::net::TcpListener;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+const USAGE: &str = "usage: loadtest [--concurrency N] [--duration SECS] [--workers N] \
+                     [--route METHOD:PATH[=WEIGHT]]... [--close]";
+
+/// Registered GET routes left out of the default mix: they block, stream, mutate state
+/// or need credentials, so they would measure something other than the router.
+const SKIPPED_ROUTES: &[&str] = &["/admin/", "/quit", "/sleep", "/count", "/err-logging", "/stream", "/testing/stream"];
+
+#[derive(Clone, Debug, PartialEq)]
+struct Route {
+    method: http::Method,
+    path: String,
+    weight: usize,
+}
+
+impl std::str::FromStr for Route {
+    type Err = String;
+
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let (method, rest) = spec
+            .split_once(':')
+            .ok_or_else(|| format!("route `{}` is not METHOD:PATH", spec))?;
+        let (path, weight) = match rest.split_once('=') {
+            Some((path, weight)) => (path, weight.parse().map_err(|_| format!("bad weight in `{}`", spec))?),
+            None => (rest, 1),
+        };
+        if weight == 0 {
+            return Err(format!("weight of `{}` must be positive", spec));
+        }
+
+        Ok(Route {
+            method: method.to_uppercase().parse().map_err(|_| format!("bad method in `{}`", spec))?,
+            path: path.to_owned(),
+            weight,
+        })
+    }
+}
+
+#[derive(Debug)]
+struct Options {
+    concurrency: usize,
+    duration: Duration,
+    workers: usize,
+    routes: Vec<Route>,
+    /// Open a new connection for every request instead of reusing it.
+    close: bool,
+}
+
+impl Options {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut options = Options {
+            concurrency: 16,
+            duration: Duration::from_secs(10),
+            workers: 2,
+            routes: vec![],
+            close: false,
+        };
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or_else(|| format!("{} needs a value", arg));
+            match arg.as_str() {
+                "--concurrency" => options.concurrency = value()?.parse().map_err(|_| "bad --concurrency")?,
+                "--duration" => options.duration = Duration::from_secs(value()?.parse().map_err(|_| "bad --duration")?),
+                "--workers" => options.workers = value()?.parse().map_err(|_| "bad --workers")?,
+                "--route" => options.routes.push(value()?.parse()?),
+                "--close" => options.close = true,
+                _ => return Err(USAGE.to_owned()),
+            }
+        }
+
+        Ok(options)
+    }
+}
+
+#[derive(Default)]
+struct Stats {
+    latencies: Vec<Duration>,
+    statuses: BTreeMap<u16, usize>,
+    errors: usize,
+}
+
+impl Stats {
+    fn merge(&mut self, other: &Stats) {
+        self.latencies.extend(&other.latencies);
+        for (status, count) in &other.statuses {
+            *self.statuses.entry(*status).or_default() += count;
+        }
+        self.errors += other.errors;
+    }
+
+    fn summary(&mut self) -> String {
+        self.latencies.sort();
+        let millis = |latency: Duration| latency.as_secs_f64() * 1000.0;
+
+        format!(
+            "p50 {:.2}ms  p90 {:.2}ms  p99 {:.2}ms  max {:.2}ms  statuses {:?}  errors {}",
+            millis(percentile(&self.latencies, 50.0)),
+            millis(percentile(&self.latencies, 90.0)),
+            millis(percentile(&self.latencies, 99.0)),
+            millis(self.latencies.last().copied().unwrap_or_default()),
+            self.statuses,
+            self.errors,
+        )
+    }
+}
+
+/// Nearest-rank percentile of already sorted samples.
+fn percentile(sorted: &[Duration], p: f64) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    let rank = (p / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+/// The routes the app registers, as served by `create_app(data)`.
+async fn introspect(data: AppData) -> web::Data<IntrospectionTree> {
+    let app = test::init_service(create_app(data)).await;
+    let res = test::call_service(&app, test::TestRequest::default().to_request()).await;
+    res.request().app_data::<web::Data<IntrospectionTree>>().unwrap().clone()
+}
+
+fn resources(node: &IntrospectionNode) -> Vec<&IntrospectionNode> {
+    let mut found: Vec<_> = node.children.iter().flat_map(resources).collect();
+    if matches!(node.kind, ResourceType::Resource) {
+        found.push(node);
+    }
+    found
+}
+
+/// Every registered GET route without path parameters or guards beyond the method that
+/// succeeds without further input, used when no `--route` is given.
+async fn default_routes(data: AppData, tree: &IntrospectionTree) -> Vec<Route> {
+    let mut candidates: Vec<_> = resources(&tree.root)
+        .into_iter()
+        .filter(|node| node.methods.contains(&http::Method::GET))
+        .filter(|node| node.guards.iter().all(|guard| node.methods.iter().any(|method| method.as_str() == guard)))
+        .filter(|node| !ResourceDef::new(node.full_path.as_str()).is_prefix() && !node.full_path.contains('{'))
+        .filter(|node| !SKIPPED_ROUTES.iter().any(|skipped| node.full_path.starts_with(skipped)))
+        .map(|node| Route { method: http::Method::GET, path: node.full_path.clone(), weight: 1 })
+        .collect();
+    candidates.sort_by(|a, b| a.path.cmp(&b.path));
+    candidates.dedup();
+
+    let app = test::init_service(create_app(data)).await;
+    let mut routes = vec![];
+    for route in candidates {
+        let res = test::call_service(&app, test::TestRequest::get().uri(&route.path).to_request()).await;
+        if res.status().is_success() {
+            routes.push(route);
+        }
+    }
+    routes
+}
+
+/// Maps every route of the mix to the pattern it is registered under,
+/// so typos fail fast instead of benchmarking the 404 handler.
+fn registered_patterns(routes: &[Route], tree: &IntrospectionTree) -> Result<Vec<String>, String> {
+    let resources = resources(&tree.root);
+    routes
+        .iter()
+        .map(|route| {
+            resources
+                .iter()
+                .filter(|node| node.methods.is_empty() || node.methods.contains(&route.method))
+                .find(|node| ResourceDef::new(node.full_path.as_str()).is_match(&route.path))
+                .map(|node| format!("{} {}", route.method, node.full_path))
+                .ok_or_else(|| format!("{} {} does not match any registered route", route.method, route.path))
+        })
+        .collect()
+}
+
+#[actix_web::main]
+async fn main() -> std::io::Result<()> {
+    let mut options = Options::parse(std::env::args().skip(1)).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        std::process::exit(2);
+    });
+
+    let data = AppData::ephemeral().await;
+    let tree = introspect(data.clone()).await;
+    if options.routes.is_empty() {
+        options.routes = default_routes(data.clone(), &tree).await;
+    }
+    let patterns = registered_patterns(&options.routes, &tree).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        std::process::exit(2);
+    });
+
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let addr = listener.local_addr()?;
+    let server = HttpServer::new(move || create_app(data.clone()))
+        .workers(options.workers)
+        .disable_signals()
+        .listen(listener)?
+        .run();
+    let handle = server.handle();
+    rt::spawn(server);
+
+    // Weighted round-robin: each route appears `weight` times in the schedule.
+    let schedule: Rc<Vec<usize>> = Rc::new(
+        options
+            .routes
+            .iter()
+            .enumerate()
+            .flat_map(|(i, route)| std::iter::repeat_n(i, route.weight))
+            .collect(),
+    );
+    let client = awc::Client::builder()
+        .connector(awc::Connector::new().limit(options.concurrency))
+        .timeout(Duration::from_secs(30))
+        .finish();
+    let stats: Rc<RefCell<Vec<Stats>>> =
+        Rc::new(RefCell::new(options.routes.iter().map(|_| Stats::default()).collect()));
+
+    println!(
+        "{} connections, {}s, {} against http://{}",
+        options.concurrency,
+        options.duration.as_secs(),
+        if options.close { "Connection: close" } else { "keep-alive" },
+        addr
+    );
+    let start = Instant::now();
+    let deadline = start + options.duration;
+    let tasks = (0..options.concurrency).map(|task| {
+        let (client, schedule, stats) = (client.clone(), schedule.clone(), stats.clone());
+        let (routes, close) = (options.routes.clone(), options.close);
+        rt::spawn(async move {
+            let mut next = task;
+            while Instant::now() < deadline {
+                let i = schedule[next % schedule.len()];
+                next += 1;
+
+                let route = &routes[i];
+                let mut req = client.request(route.method.clone(), format!("http://{}{}", addr, route.path));
+                if close {
+                    req = req.force_close();
+                }
+                let sent = Instant::now();
+                let result = match req.send().await {
+                    Ok(mut res) => res.body().limit(16 * 1024 * 1024).await.map(|_| res.status()).ok(),
+                    Err(_) => None,
+                };
+
+                let route_stats = &mut stats.borrow_mut()[i];
+                match result {
+                    Some(status) => {
+                        route_stats.latencies.push(sent.elapsed());
+                        *route_stats.statuses.entry(status.as_u16()).or_default() += 1;
+                    }
+                    None => route_stats.errors += 1,
+                }
+            }
+        })
+    });
+    futures::future::join_all(tasks).await;
+    let elapsed = start.elapsed();
+    handle.stop(true).await;
+
+    let mut total = Stats::default();
+    for (pattern, route_stats) in patterns.iter().zip(stats.borrow_mut().iter_mut()) {
+        total.merge(route_stats);
+        println!("{:<40} {:>8} req  {}", pattern, route_stats.latencies.len(), route_stats.summary());
+    }
+    println!(
+        "{:<40} {:>8} req  {}",
+        "total",
+        total.latencies.len(),
+        total.summary()
+    );
+    println!("throughput {:.1} req/s", total.latencies.len() as f64 / elapsed.as_secs_f64());
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{default_routes, introspect, percentile, registered_patterns, Route};
+    use actix_example::AppData;
+    use actix_web::http;
+
+    use std::time::Duration;
+
+    #[test]
+    fn test_parse_route() {
+        let route: Route = "post:/echo=3".parse().unwrap();
+        assert_eq!(route.method, http::Method::POST);
+        assert_eq!(route.path, "/echo");
+        assert_eq!(route.weight, 3);
+
+        assert_eq!("GET:/hello".parse::<Route>().unwrap().weight, 1);
+        assert!("/hello".parse::<Route>().is_err());
+        assert!("GET:/hello=0".parse::<Route>().is_err());
+    }
+
+    #[test]
+    fn test_percentile() {
+        let samples: Vec<_> = (1..=100).map(Duration::from_millis).collect();
+        assert_eq!(percentile(&samples, 50.0), Duration::from_millis(50));
+        assert_eq!(percentile(&samples, 99.0), Duration::from_millis(99));
+        assert_eq!(percentile(&samples, 100.0), Duration::from_millis(100));
+        assert_eq!(percentile(&[], 50.0), Duration::ZERO);
+    }
+
+    #[actix_web::test]
+    async fn test_registered_patterns() {
+        let routes: Vec<Route> = ["GET:/url-dispatch/show/7", "GET:/hello"]
+            .iter()
+            .map(|spec| spec.parse().unwrap())
+            .collect();
+        let tree = introspect(AppData::ephemeral().await).await;
+        let patterns = registered_patterns(&routes, &tree).unwrap();
+        assert_eq!(patterns, ["GET /url-dispatch/show/{id}", "GET /hello"]);
+
+        for spec in ["GET:/missing", "POST:/hello"] {
+            assert!(registered_patterns(&[spec.parse().unwrap()], &tree).is_err(), "{}", spec);
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_default_routes() {
+        let data = AppData::ephemeral().await;
+        let tree = introspect(data.clone()).await;
+        let routes = default_routes(data, &tree).await;
+        let paths: Vec<_> = routes.iter().map(|route| route.path.as_str()).collect();
+        for path in ["/hello", "/custom-type", "/healthz", "/url-dispatch/path-normalize"] {
+            assert!(paths.contains(&path), "{} missing from {:?}", path, paths);
+        }
+        for path in ["/quit", "/admin/audit", "/url-dispatch/path", "/url-dispatch/show/{id}", "/custom-error"] {
+            assert!(!paths.contains(&path), "{} in {:?}", path, paths);
+        }
+        assert!(registered_patterns(&routes, &tree).is_ok());
+    }
+}
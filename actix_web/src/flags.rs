@@ -0,0 +1,405 @@
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{self, ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::{web, Error, FromRequest, HttpMessage, HttpRequest, HttpResponse};
+use futures::future::{ready, Ready};
+use log::{debug, error, info};
+use serde::Deserialize;
+
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::routing::registered_pattern;
+use crate::tls::ClientCert;
+
+#[derive(Debug, derive_more::Display, derive_more::From)]
+pub enum FlagError {
+    #[display(fmt = "cannot read flags: {}", _0)]
+    Io(std::io::Error),
+    #[display(fmt = "invalid flags: {}", _0)]
+    Parse(toml::de::Error),
+    #[display(fmt = "flag {}: rollout {} is over 100%", flag, rollout)]
+    #[from(ignore)]
+    Rollout { flag: String, rollout: u8 },
+}
+
+impl std::error::Error for FlagError {}
+
+fn yes() -> bool {
+    true
+}
+
+fn everyone() -> u8 {
+    100
+}
+
+/// One flag from the flags file.
+///
+/// ```toml
+/// [flags.new-checkout]
+/// enabled = true
+/// rollout = 25                      # percent of rollout keys, see `Flags`
+/// scope = "/checkout"               # everything below this path
+/// route = "/cart/{id}"              # or one resource, as registered
+/// methods = ["POST"]                # optionally only some of its handlers
+/// ```
+///
+/// Requests covered by `scope` or `route` get 404 while the flag is off for them.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Flag {
+    #[serde(default = "yes")]
+    pub enabled: bool,
+    #[serde(default = "everyone")]
+    pub rollout: u8,
+    pub scope: Option<String>,
+    pub route: Option<String>,
+    #[serde(default)]
+    pub methods: Vec<String>,
+}
+
+impl Flag {
+    /// Whether the flag is on for `key`. Partial rollouts leave requests without a key out.
+    pub fn on_for(&self, name: &str, key: Option<&str>) -> bool {
+        self.enabled && (self.rollout >= 100 || key.is_some_and(|key| bucket(name, key) < self.rollout))
+    }
+
+    /// Whether the flag switches the handler for `method` on `path` (registered as `pattern`).
+    fn covers(&self, method: &str, path: &str, pattern: Option<&str>) -> bool {
+        let in_scope = self.scope.as_deref().is_some_and(|scope| {
+            let scope = scope.trim_end_matches('/');
+            path.strip_prefix(scope)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+        });
+        let on_route = self.route.is_some() && self.route.as_deref() == pattern;
+        let method = self.methods.is_empty() || self.methods.iter().any(|m| m.eq_ignore_ascii_case(method));
+
+        (in_scope || on_route) && method
+    }
+}
+
+/// FNV-1a of the flag name and key, so a key stays in or out of a rollout across
+/// restarts and builds, and different flags roll out to different keys.
+fn bucket(name: &str, key: &str) -> u8 {
+    let hash = [name.as_bytes(), b":", key.as_bytes()]
+        .concat()
+        .iter()
+        .fold(0xcbf2_9ce4_8422_2325u64, |hash, byte| {
+            (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+        });
+    (hash % 100) as u8
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct FlagSet {
+    #[serde(default)]
+    pub flags: BTreeMap<String, Flag>,
+}
+
+impl FlagSet {
+    pub fn parse(source: &str) -> Result<Self, FlagError> {
+        let set: FlagSet = toml::from_str(source)?;
+        if let Some((name, flag)) = set.flags.iter().find(|(_, flag)| flag.rollout > 100) {
+            return Err(FlagError::Rollout {
+                flag: name.clone(),
+                rollout: flag.rollout,
+            });
+        }
+        Ok(set)
+    }
+}
+
+struct Loaded {
+    set: Arc<FlagSet>,
+    /// Modification time and length of the file `set` was read from.
+    version: Option<(SystemTime, u64)>,
+    checked: Instant,
+}
+
+/// Flags read from a TOML file, read again when it changes.
+pub struct FlagStore {
+    path: PathBuf,
+    reload_every: Duration,
+    loaded: RwLock<Loaded>,
+}
+
+fn version(path: &PathBuf) -> Option<(SystemTime, u64)> {
+    let meta = std::fs::metadata(path).ok()?;
+    Some((meta.modified().ok()?, meta.len()))
+}
+
+fn read(path: &PathBuf) -> Result<FlagSet, FlagError> {
+    match std::fs::read_to_string(path) {
+        Ok(source) => FlagSet::parse(&source),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(FlagSet::default()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+impl FlagStore {
+    /// Loads the flags file, or no flags when it does not exist. The file is checked
+    /// for changes at most every `reload_every`.
+    pub fn open(path: impl Into<PathBuf>, reload_every: Duration) -> Result<Self, FlagError> {
+        let path = path.into();
+        let version = version(&path);
+        let set = read(&path)?;
+
+        Ok(FlagStore {
+            path,
+            reload_every,
+            loaded: RwLock::new(Loaded {
+                set: Arc::new(set),
+                version,
+                checked: Instant::now(),
+            }),
+        })
+    }
+
+    /// The current flags. A file that no longer parses keeps the previous ones in effect.
+    pub fn get(&self) -> Arc<FlagSet> {
+        {
+            let loaded = self.loaded.read().unwrap();
+            if loaded.checked.elapsed() < self.reload_every {
+                return loaded.set.clone();
+            }
+        }
+
+        let mut loaded = self.loaded.write().unwrap();
+        if loaded.checked.elapsed() >= self.reload_every {
+            loaded.checked = Instant::now();
+            let version = version(&self.path);
+            if version != loaded.version {
+                loaded.version = version;
+                match read(&self.path) {
+                    Ok(set) => {
+                        info!("reloaded {} flags from {}", set.flags.len(), self.path.display());
+                        loaded.set = Arc::new(set);
+                    }
+                    Err(err) => error!("{}: {}", self.path.display(), err),
+                }
+            }
+        }
+        loaded.set.clone()
+    }
+}
+
+/// The flags as they apply to one request.
+///
+/// Rollouts are keyed on `X-User-Id`, then the client certificate's identity, then
+/// `X-Request-Id`. Without a [`FlagStore`] in `web::Data` every flag is off.
+#[derive(Clone, Debug, Default)]
+pub struct Flags {
+    set: Arc<FlagSet>,
+    key: Option<String>,
+}
+
+impl Flags {
+    pub fn new(set: Arc<FlagSet>, key: Option<String>) -> Self {
+        Flags { set, key }
+    }
+
+    pub fn from_req(req: &HttpRequest) -> Self {
+        if let Some(flags) = req.extensions().get::<Flags>() {
+            return flags.clone();
+        }
+        let Some(store) = req.app_data::<web::Data<FlagStore>>() else {
+            return Flags::default();
+        };
+
+        let header = |name| {
+            req.headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_owned)
+        };
+        let key = header("x-user-id")
+            .or_else(|| {
+                req.conn_data::<ClientCert>()
+                    .map(|cert| cert.common_name.clone().unwrap_or_else(|| cert.subject.clone()))
+            })
+            .or_else(|| header("x-request-id"));
+
+        Flags::new(store.get(), key)
+    }
+
+    /// Unknown flags are off.
+    pub fn enabled(&self, name: &str) -> bool {
+        self.set
+            .flags
+            .get(name)
+            .is_some_and(|flag| flag.on_for(name, self.key.as_deref()))
+    }
+
+    /// The first flag that covers the request and is off for it.
+    fn blocking(&self, method: &str, path: &str, pattern: Option<&str>) -> Option<&str> {
+        self.set
+            .flags
+            .iter()
+            .find(|(name, flag)| flag.covers(method, path, pattern) && !flag.on_for(name, self.key.as_deref()))
+            .map(|(name, _)| name.as_str())
+    }
+}
+
+impl FromRequest for Flags {
+    type Error = Infallible;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut dev::Payload) -> Self::Future {
+        ready(Ok(Flags::from_req(req)))
+    }
+}
+
+/// Answers 404 for scopes and handlers whose flag is off for the request, and leaves
+/// the [`Flags`] in the request extensions so handlers see the same snapshot.
+pub async fn gate(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, Error> {
+    let flags = Flags::from_req(req.request());
+    // Routing has not run yet, so the pattern comes from the registered routes.
+    let pattern = registered_pattern(req.request(), req.method(), req.path());
+    if let Some(name) = flags.blocking(req.method().as_str(), req.path(), pattern.as_deref()) {
+        debug!("{} {} switched off by flag {}", req.method(), req.path(), name);
+        return Ok(req.into_response(HttpResponse::NotFound().finish()).map_into_right_body());
+    }
+
+    req.extensions_mut().insert(flags);
+    Ok(next.call(req).await?.map_into_left_body())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::middleware::from_fn;
+    use actix_web::{http, test, App};
+
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    const FLAGS: &str = r#"
+        [flags.beta]
+        enabled = true
+        rollout = 50
+
+        [flags.admin]
+        enabled = false
+        scope = "/admin/"
+
+        [flags.item-writes]
+        enabled = false
+        route = "/items/{id}"
+        methods = ["put"]
+    "#;
+
+    fn temp_path() -> PathBuf {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "actix-example-flags-{}-{}.toml",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    async fn beta(flags: Flags) -> &'static str {
+        if flags.enabled("beta") {
+            "beta"
+        } else {
+            "stable"
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_parse_and_rollout() {
+        let set = FlagSet::parse(FLAGS).unwrap();
+        assert_eq!(set.flags.len(), 3);
+        assert!(matches!(
+            FlagSet::parse("[flags.x]\nrollout = 101"),
+            Err(FlagError::Rollout { rollout: 101, .. })
+        ));
+        assert!(FlagSet::parse("[flags.x]\nenabled = \"yes\"").is_err());
+
+        let set = Arc::new(set);
+        let flags = |key: Option<&str>| Flags::new(set.clone(), key.map(str::to_owned));
+        // Without a key nobody is in a partial rollout; unknown flags are off.
+        assert!(!flags(None).enabled("beta"));
+        assert!(!flags(Some("user-1")).enabled("missing"));
+
+        let users: Vec<_> = (0..1000).map(|id| format!("user-{}", id)).collect();
+        let on = users.iter().filter(|user| flags(Some(user)).enabled("beta")).count();
+        assert!((400..600).contains(&on), "{} of 1000 users in a 50% rollout", on);
+        // Stable for a given key.
+        assert!(users
+            .iter()
+            .all(|user| flags(Some(user)).enabled("beta") == flags(Some(user)).enabled("beta")));
+    }
+
+    #[actix_web::test]
+    async fn test_gate() {
+        let path = temp_path();
+        std::fs::write(&path, FLAGS).unwrap();
+        let store = FlagStore::open(&path, Duration::ZERO).unwrap();
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(store))
+                .wrap(from_fn(gate))
+                .route("/beta", web::get().to(beta))
+                .route("/admin/users", web::get().to(HttpResponse::Ok))
+                .route("/administrators", web::get().to(HttpResponse::Ok))
+                .route("/items/{id}", web::get().to(HttpResponse::Ok))
+                .route("/items/{id}", web::put().to(HttpResponse::Ok)),
+        )
+        .await;
+        let status = |method: http::Method, uri: &'static str| {
+            let req = test::TestRequest::default().method(method).uri(uri).to_request();
+            let app = &app;
+            async move { test::call_service(app, req).await.status() }
+        };
+
+        assert_eq!(status(http::Method::GET, "/admin/users").await, http::StatusCode::NOT_FOUND);
+        assert_eq!(status(http::Method::GET, "/administrators").await, http::StatusCode::OK);
+        assert_eq!(status(http::Method::GET, "/items/1").await, http::StatusCode::OK);
+        assert_eq!(status(http::Method::PUT, "/items/1").await, http::StatusCode::NOT_FOUND);
+
+        // Handlers see the flag per user.
+        let users: Vec<_> = (0..20).map(|id| format!("user-{}", id)).collect();
+        let mut answers = vec![];
+        for user in &users {
+            let req = test::TestRequest::get()
+                .uri("/beta")
+                .insert_header(("x-user-id", user.as_str()))
+                .to_request();
+            answers.push(test::call_and_read_body(&app, req).await);
+        }
+        assert!(answers.contains(&web::Bytes::from_static(b"beta")));
+        assert!(answers.contains(&web::Bytes::from_static(b"stable")));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[actix_web::test]
+    async fn test_reload() {
+        let path = temp_path();
+        let store = FlagStore::open(&path, Duration::ZERO).unwrap();
+        assert!(store.get().flags.is_empty());
+
+        std::fs::write(&path, "[flags.beta]\nenabled = true\n").unwrap();
+        assert!(store.get().flags["beta"].enabled);
+
+        // A broken file keeps the last good flags.
+        std::fs::write(&path, "[flags.beta\n").unwrap();
+        assert!(store.get().flags["beta"].enabled);
+
+        std::fs::write(&path, "[flags.beta]\nenabled = false\n").unwrap();
+        assert!(!store.get().flags["beta"].enabled);
+
+        // Changes are not looked for more often than asked.
+        let slow = FlagStore::open(&path, Duration::from_secs(3600)).unwrap();
+        std::fs::write(&path, "[flags.beta]\nenabled = true\n").unwrap();
+        assert!(!slow.get().flags["beta"].enabled);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
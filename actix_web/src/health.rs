@@ -0,0 +1,223 @@
+use async_trait::async_trait;
+use futures::future::join_all;
+use openssl::asn1::Asn1Time;
+use openssl::x509::X509;
+use sea_orm::{ConnectionTrait, DatabaseConnection, Statement};
+use serde::Serialize;
+
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Instant;
+
+/// A dependency the server needs in order to serve traffic.
+#[async_trait(?Send)]
+pub trait Check: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// Returns a short detail on success, or the reason for failure.
+    async fn check(&self) -> Result<String, String>;
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    Ok,
+    Fail,
+    ShuttingDown,
+}
+
+#[derive(Serialize)]
+pub struct CheckReport {
+    pub name: String,
+    pub status: Status,
+    pub latency_ms: f64,
+    pub detail: String,
+}
+
+#[derive(Serialize)]
+pub struct Report {
+    pub status: Status,
+    pub checks: Vec<CheckReport>,
+}
+
+/// Registered checks plus the readiness flag flipped during shutdown.
+pub struct Health {
+    checks: Vec<Box<dyn Check>>,
+    ready: AtomicBool,
+}
+
+impl Default for Health {
+    fn default() -> Self {
+        Health {
+            checks: Vec::new(),
+            ready: AtomicBool::new(true),
+        }
+    }
+}
+
+impl Health {
+    pub fn with_check(mut self, check: impl Check + 'static) -> Self {
+        self.checks.push(Box::new(check));
+        self
+    }
+
+    pub fn set_ready(&self, ready: bool) {
+        self.ready.store(ready, Ordering::Relaxed);
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.ready.load(Ordering::Relaxed)
+    }
+
+    /// Runs every check concurrently.
+    pub async fn report(&self) -> Report {
+        let checks = join_all(self.checks.iter().map(|check| async move {
+            let start = Instant::now();
+            let result = check.check().await;
+            let latency_ms = start.elapsed().as_secs_f64() * 1000.0;
+
+            let (status, detail) = match result {
+                Ok(detail) => (Status::Ok, detail),
+                Err(detail) => (Status::Fail, detail),
+            };
+            CheckReport {
+                name: check.name().to_owned(),
+                status,
+                latency_ms,
+                detail,
+            }
+        }))
+        .await;
+
+        let status = if !self.is_ready() {
+            Status::ShuttingDown
+        } else if checks.iter().all(|check| check.status == Status::Ok) {
+            Status::Ok
+        } else {
+            Status::Fail
+        };
+        Report { status, checks }
+    }
+}
+
+pub struct DatabaseCheck {
+    pub db: DatabaseConnection,
+}
+
+#[async_trait(?Send)]
+impl Check for DatabaseCheck {
+    fn name(&self) -> &str {
+        "database"
+    }
+
+    async fn check(&self) -> Result<String, String> {
+        let backend = self.db.get_database_backend();
+        self.db
+            .execute(Statement::from_string(backend, "SELECT 1".to_owned()))
+            .await
+            .map(|_| format!("{:?} reachable", backend))
+            .map_err(|err| err.to_string())
+    }
+}
+
+/// Fails when the filesystem holding `path` has less than `min_free` bytes available.
+pub struct DiskSpaceCheck {
+    pub path: PathBuf,
+    pub min_free: u64,
+}
+
+#[async_trait(?Send)]
+impl Check for DiskSpaceCheck {
+    fn name(&self) -> &str {
+        "disk_space"
+    }
+
+    async fn check(&self) -> Result<String, String> {
+        let free = fs2::available_space(&self.path).map_err(|err| err.to_string())?;
+        let detail = format!("{} bytes free in {}", free, self.path.display());
+        if free < self.min_free {
+            return Err(detail);
+        }
+        Ok(detail)
+    }
+}
+
+/// Fails when the certificate at `path` expires within `min_days`.
+pub struct CertExpiryCheck {
+    pub path: PathBuf,
+    pub min_days: u32,
+}
+
+#[async_trait(?Send)]
+impl Check for CertExpiryCheck {
+    fn name(&self) -> &str {
+        "tls_cert"
+    }
+
+    async fn check(&self) -> Result<String, String> {
+        let pem = std::fs::read(&self.path).map_err(|err| err.to_string())?;
+        let cert = X509::from_pem(&pem).map_err(|err| err.to_string())?;
+        let now = Asn1Time::days_from_now(0).map_err(|err| err.to_string())?;
+        let left = now.diff(cert.not_after()).map_err(|err| err.to_string())?;
+
+        let detail = format!("expires {} ({} days left)", cert.not_after(), left.days);
+        if left.days < self.min_days as i32 {
+            return Err(detail);
+        }
+        Ok(detail)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Fixed(Result<&'static str, &'static str>);
+
+    #[async_trait(?Send)]
+    impl Check for Fixed {
+        fn name(&self) -> &str {
+            "fixed"
+        }
+
+        async fn check(&self) -> Result<String, String> {
+            self.0.map(str::to_owned).map_err(str::to_owned)
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_report_status() {
+        let health = Health::default().with_check(Fixed(Ok("fine")));
+        assert_eq!(health.report().await.status, Status::Ok);
+
+        let health = health.with_check(Fixed(Err("broken")));
+        let report = health.report().await;
+        assert_eq!(report.status, Status::Fail);
+        assert_eq!(report.checks[1].detail, "broken");
+
+        health.set_ready(false);
+        assert_eq!(health.report().await.status, Status::ShuttingDown);
+    }
+
+    #[actix_web::test]
+    async fn test_builtin_checks() {
+        let disk = DiskSpaceCheck {
+            path: PathBuf::from("static"),
+            min_free: 0,
+        };
+        assert!(disk.check().await.is_ok());
+
+        let disk = DiskSpaceCheck {
+            path: PathBuf::from("static"),
+            min_free: u64::MAX,
+        };
+        assert!(disk.check().await.is_err());
+
+        // cert.pem expired in 2024.
+        let cert = CertExpiryCheck {
+            path: PathBuf::from("cert.pem"),
+            min_days: 0,
+        };
+        assert!(cert.check().await.is_err());
+    }
+}
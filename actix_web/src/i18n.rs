@@ -0,0 +1,309 @@
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::{body, dev, error, http, web, Error, FromRequest, HttpMessage, HttpRequest, HttpResponse};
+use fluent::concurrent::FluentBundle;
+use fluent::{FluentArgs, FluentResource};
+use fluent_langneg::{accepted_languages, negotiate_languages, NegotiationStrategy};
+use futures::future::{ready, Ready};
+use serde::Deserialize;
+use unic_langid::{langid, LanguageIdentifier};
+
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::fmt;
+use std::sync::OnceLock;
+
+const DEFAULT_LANG: LanguageIdentifier = langid!("en");
+
+/// Catalogs shipped with the binary, one Fluent file per language.
+const CATALOGS: &[(LanguageIdentifier, &str)] = &[
+    (langid!("en"), include_str!("../locales/en/main.ftl")),
+    (langid!("ja"), include_str!("../locales/ja/main.ftl")),
+];
+
+pub struct Catalog {
+    bundles: HashMap<LanguageIdentifier, FluentBundle<FluentResource>>,
+    available: Vec<LanguageIdentifier>,
+}
+
+impl Catalog {
+    pub fn global() -> &'static Catalog {
+        static CATALOG: OnceLock<Catalog> = OnceLock::new();
+        CATALOG.get_or_init(Catalog::load)
+    }
+
+    fn load() -> Self {
+        let mut bundles = HashMap::new();
+        for (lang, source) in CATALOGS {
+            let resource = FluentResource::try_new(source.to_string())
+                .unwrap_or_else(|(_, errs)| panic!("invalid catalog for {}: {:?}", lang, errs));
+            let mut bundle = FluentBundle::new_concurrent(vec![lang.clone()]);
+            // Isolation marks around placeables would end up in plain-text responses.
+            bundle.set_use_isolating(false);
+            bundle.add_resource(resource).unwrap();
+            bundles.insert(lang.clone(), bundle);
+        }
+        let available = CATALOGS.iter().map(|(lang, _)| lang.clone()).collect();
+
+        Catalog { bundles, available }
+    }
+
+    /// Formats `key` in `lang`, falling back to English and then to the key itself.
+    pub fn format(&self, lang: &LanguageIdentifier, key: &str, args: Option<&FluentArgs>) -> String {
+        [lang, &DEFAULT_LANG]
+            .into_iter()
+            .filter_map(|lang| self.bundles.get(lang))
+            .find_map(|bundle| {
+                let pattern = bundle.get_message(key)?.value()?;
+                let mut errors = vec![];
+                Some(bundle.format_pattern(pattern, args, &mut errors).into_owned())
+            })
+            .unwrap_or_else(|| key.to_owned())
+    }
+
+    /// Picks the best supported language, in order of the client's preference.
+    pub fn negotiate(&self, requested: &[LanguageIdentifier]) -> LanguageIdentifier {
+        negotiate_languages(
+            requested,
+            &self.available,
+            Some(&DEFAULT_LANG),
+            NegotiationStrategy::Lookup,
+        )
+        .first()
+        .map(|lang| (*lang).clone())
+        .unwrap_or(DEFAULT_LANG)
+    }
+}
+
+#[derive(Deserialize)]
+struct LangQuery {
+    lang: Option<String>,
+}
+
+/// The response language of a request.
+///
+/// Taken from the `lang` query parameter when present, otherwise from `Accept-Language`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Locale {
+    lang: LanguageIdentifier,
+}
+
+impl Locale {
+    pub fn new(lang: LanguageIdentifier) -> Self {
+        Locale { lang }
+    }
+
+    pub fn from_req(req: &HttpRequest) -> Self {
+        let query = web::Query::<LangQuery>::from_query(req.query_string())
+            .ok()
+            .and_then(|query| query.into_inner().lang);
+        let by_header = query.is_none();
+        let requested = match query {
+            Some(lang) => accepted_languages::parse(&lang),
+            None => req
+                .headers()
+                .get(http::header::ACCEPT_LANGUAGE)
+                .and_then(|value| value.to_str().ok())
+                .map(accepted_languages::parse)
+                .unwrap_or_default(),
+        };
+
+        let lang = Catalog::global().negotiate(&requested);
+        req.extensions_mut().insert(Negotiated {
+            lang: lang.clone(),
+            by_header,
+        });
+        Locale::new(lang)
+    }
+
+    pub fn lang(&self) -> &LanguageIdentifier {
+        &self.lang
+    }
+
+    pub fn text(&self, key: &str) -> String {
+        Catalog::global().format(&self.lang, key, None)
+    }
+
+    pub fn format(&self, key: &str, args: &FluentArgs) -> String {
+        Catalog::global().format(&self.lang, key, Some(args))
+    }
+
+    /// Attaches this locale to an error so its response is rendered in the right language.
+    pub fn error<E: LocalizedError>(&self, error: E) -> Localized<E> {
+        Localized {
+            error,
+            locale: self.clone(),
+        }
+    }
+}
+
+impl Default for Locale {
+    fn default() -> Self {
+        Locale::new(DEFAULT_LANG)
+    }
+}
+
+impl FromRequest for Locale {
+    type Error = Infallible;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut dev::Payload) -> Self::Future {
+        ready(Ok(Locale::from_req(req)))
+    }
+}
+
+/// Left on a request once its [`Locale`] is known, for [`negotiated`].
+#[derive(Clone)]
+struct Negotiated {
+    lang: LanguageIdentifier,
+    /// Whether `Accept-Language` decided it, rather than the `lang` query parameter.
+    by_header: bool,
+}
+
+/// Labels the response to any request that negotiated a [`Locale`] with its
+/// `Content-Language`, and with `Vary: Accept-Language` when the header picked it, so
+/// shared caches keep one copy per language.
+pub async fn negotiated(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let mut res = next.call(req).await?;
+    let Some(negotiated) = res.request().extensions().get::<Negotiated>().cloned() else {
+        return Ok(res);
+    };
+
+    let headers = res.headers_mut();
+    if !headers.contains_key(http::header::CONTENT_LANGUAGE) {
+        let lang = http::header::HeaderValue::from_str(&negotiated.lang.to_string()).unwrap();
+        headers.insert(http::header::CONTENT_LANGUAGE, lang);
+    }
+    let varies = headers
+        .get_all(http::header::VARY)
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|name| name.trim().eq_ignore_ascii_case("accept-language"));
+    if negotiated.by_header && !varies {
+        headers.append(
+            http::header::VARY,
+            http::header::HeaderValue::from_static("accept-language"),
+        );
+    }
+    Ok(res)
+}
+
+/// An error whose message comes from the catalog.
+pub trait LocalizedError: error::ResponseError {
+    fn message(&self, locale: &Locale) -> String;
+}
+
+#[derive(Debug)]
+pub struct Localized<E> {
+    pub error: E,
+    pub locale: Locale,
+}
+
+impl<E: LocalizedError> fmt::Display for Localized<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.error.message(&self.locale))
+    }
+}
+
+impl<E: LocalizedError> error::ResponseError for Localized<E> {
+    fn status_code(&self) -> http::StatusCode {
+        self.error.status_code()
+    }
+
+    fn error_response(&self) -> HttpResponse<body::BoxBody> {
+        let mut res = self.error.error_response();
+        res.headers_mut().insert(
+            http::header::CONTENT_LANGUAGE,
+            http::header::HeaderValue::from_str(&self.locale.lang().to_string()).unwrap(),
+        );
+        res.set_body(body::BoxBody::new(self.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test;
+    use fluent::fluent_args;
+
+    #[actix_web::test]
+    async fn test_negotiate_accept_language() {
+        let req = test::TestRequest::default()
+            .insert_header((http::header::ACCEPT_LANGUAGE, "fr-FR, ja;q=0.8, en;q=0.5"))
+            .to_http_request();
+        let locale = Locale::from_req(&req);
+        assert_eq!(locale.lang(), &langid!("ja"));
+        assert_eq!(locale.text("hello-world"), "こんにちは、世界！");
+
+        let req = test::TestRequest::default()
+            .insert_header((http::header::ACCEPT_LANGUAGE, "de"))
+            .to_http_request();
+        assert_eq!(Locale::from_req(&req), Locale::default());
+    }
+
+    #[actix_web::test]
+    async fn test_negotiate_query() {
+        let req = test::TestRequest::with_uri("/?lang=en")
+            .insert_header((http::header::ACCEPT_LANGUAGE, "ja"))
+            .to_http_request();
+        let locale = Locale::from_req(&req);
+        assert_eq!(locale.lang(), &langid!("en"));
+        assert_eq!(locale.format("welcome", &fluent_args!["name" => "ittokun"]), "Welcome ittokun");
+    }
+
+    #[actix_web::test]
+    async fn test_negotiated_headers() {
+        async fn hello(locale: Locale) -> String {
+            locale.text("hello-world")
+        }
+
+        let app = test::init_service(
+            actix_web::App::new()
+                .wrap(actix_web::middleware::from_fn(negotiated))
+                .route("/hello", web::get().to(hello))
+                .route("/plain", web::get().to(|| async { "plain" })),
+        )
+        .await;
+
+        let req = test::TestRequest::with_uri("/hello")
+            .insert_header((http::header::ACCEPT_LANGUAGE, "ja"))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.headers().get(http::header::CONTENT_LANGUAGE).unwrap(), "ja");
+        assert_eq!(res.headers().get(http::header::VARY).unwrap(), "accept-language");
+
+        // The URL already tells the languages apart.
+        let req = test::TestRequest::with_uri("/hello?lang=ja").to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.headers().get(http::header::CONTENT_LANGUAGE).unwrap(), "ja");
+        assert!(res.headers().get(http::header::VARY).is_none());
+
+        let req = test::TestRequest::with_uri("/plain")
+            .insert_header((http::header::ACCEPT_LANGUAGE, "ja"))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert!(res.headers().get(http::header::CONTENT_LANGUAGE).is_none());
+        assert!(res.headers().get(http::header::VARY).is_none());
+    }
+
+    #[actix_web::test]
+    async fn test_catalogs_have_same_keys() {
+        let keys = |source: &str| {
+            let mut keys: Vec<_> = source
+                .lines()
+                .filter_map(|line| line.split_once(" = "))
+                .map(|(key, _)| key.to_owned())
+                .collect();
+            keys.sort();
+            keys
+        };
+        let (_, default) = CATALOGS.iter().find(|(lang, _)| lang == &DEFAULT_LANG).unwrap();
+        for (lang, source) in CATALOGS {
+            assert_eq!(keys(source), keys(default), "catalog {} is out of sync", lang);
+        }
+    }
+}
@@ -0,0 +1,19 @@
+use sea_orm::entity::prelude::*;
+
+/// A request seen with an `Idempotency-Key`; `status` stays empty until its response is stored.
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
+#[sea_orm(table_name = "idempotency_key")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub key: String,
+    pub fingerprint: String,
+    pub status: Option<i32>,
+    pub headers: Option<Json>,
+    pub body: Option<Vec<u8>>,
+    pub expires_at: i64,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,503 @@
+use actix_web::body::{self, BodySize, BoxBody, EitherBody, MessageBody};
+use actix_web::dev::{Payload, ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::{error, http, rt, web, Error, HttpMessage, HttpResponse};
+use async_trait::async_trait;
+use futures::future::ready;
+use futures::stream::{self, StreamExt};
+use log::warn;
+use sea_orm::DbErr;
+use serde::{Deserialize, Serialize};
+
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::routing::registered_pattern;
+
+pub mod entity;
+pub mod store;
+
+pub use store::{MemoryStore, SqliteStore};
+
+const IDEMPOTENCY_KEY: &str = "idempotency-key";
+/// Set on responses replayed from the store.
+pub const IDEMPOTENT_REPLAYED: &str = "idempotent-replayed";
+/// Response headers not sent again: `Date` would be stale, cookies may hold a session
+/// for whoever sent the key first, and hop-by-hop ones belong to the first connection.
+const UNSTORED_HEADERS: &[&str] = &[
+    "date",
+    "set-cookie",
+    "connection",
+    "keep-alive",
+    "proxy-authenticate",
+    "proxy-authorization",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+];
+
+#[derive(Debug, derive_more::Display, derive_more::From)]
+pub enum IdempotencyError {
+    #[display(fmt = "database error: {}", _0)]
+    Db(DbErr),
+    #[display(fmt = "invalid stored response: {}", _0)]
+    Stored(serde_json::Error),
+}
+
+impl std::error::Error for IdempotencyError {}
+
+impl error::ResponseError for IdempotencyError {}
+
+/// The parts of a response needed to send it again.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StoredResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl StoredResponse {
+    fn to_response(&self) -> HttpResponse {
+        let status = http::StatusCode::from_u16(self.status).unwrap_or(http::StatusCode::INTERNAL_SERVER_ERROR);
+        let mut res = HttpResponse::build(status);
+        for (name, value) in &self.headers {
+            res.append_header((name.as_str(), value.as_str()));
+        }
+        res.insert_header((IDEMPOTENT_REPLAYED, "true"))
+            .body(self.body.clone())
+    }
+}
+
+/// What a store knows about a key when a request claims it.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Claim {
+    /// The key was free and now belongs to this request.
+    Started,
+    /// An earlier request with the same body is still running.
+    InProgress,
+    /// An earlier request with the same body finished with this response.
+    Done(StoredResponse),
+    /// The key was used with a different body.
+    Mismatch,
+}
+
+/// Where first responses are kept until they expire.
+#[async_trait(?Send)]
+pub trait IdempotencyStore: Send + Sync {
+    /// Claims `key` for a request whose body hashes to `fingerprint`, until `expires_at`
+    /// (milliseconds since the Unix epoch), unless an unexpired claim already exists.
+    async fn claim(&self, key: &str, fingerprint: &str, expires_at: i64) -> Result<Claim, IdempotencyError>;
+
+    /// Stores the response of a claim, keeping it until `expires_at`.
+    async fn complete(&self, key: &str, response: &StoredResponse, expires_at: i64) -> Result<(), IdempotencyError>;
+
+    /// Forgets a claim whose request did not produce a response worth replaying.
+    async fn release(&self, key: &str) -> Result<(), IdempotencyError>;
+}
+
+/// Settings and store used by [`replay`].
+pub struct Idempotency {
+    store: Arc<dyn IdempotencyStore>,
+    ttl: Duration,
+    lease: Duration,
+    max_body: usize,
+}
+
+impl Idempotency {
+    /// Keeps responses for a day, claims of unfinished requests for a minute, and
+    /// accepts bodies of up to 1 MiB.
+    pub fn new(store: impl IdempotencyStore + 'static) -> Self {
+        Idempotency {
+            store: Arc::new(store),
+            ttl: Duration::from_secs(24 * 60 * 60),
+            lease: Duration::from_secs(60),
+            max_body: 1024 * 1024,
+        }
+    }
+
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// How long a request may run before its key can be claimed again, should the
+    /// process stop before releasing it.
+    pub fn lease(mut self, lease: Duration) -> Self {
+        self.lease = lease;
+        self
+    }
+
+    /// The largest request body read, and response body stored.
+    pub fn max_body(mut self, max_body: usize) -> Self {
+        self.max_body = max_body;
+        self
+    }
+}
+
+fn now_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as i64
+}
+
+fn fingerprint(path: &str, body: &[u8]) -> String {
+    let mut hasher = openssl::sha::Sha256::new();
+    hasher.update(path.as_bytes());
+    hasher.update(b"\n");
+    hasher.update(body);
+    hasher
+        .finish()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// A claim that is released unless its response gets stored. Dropping it, as happens
+/// when the client goes away while the handler runs, releases it in the background.
+struct Claimed {
+    store: Arc<dyn IdempotencyStore>,
+    key: Option<String>,
+}
+
+impl Claimed {
+    fn keep(mut self) {
+        self.key = None;
+    }
+
+    async fn release(mut self) {
+        if let Some(key) = self.key.take() {
+            if let Err(err) = self.store.release(&key).await {
+                warn!("idempotency key {} not released: {}", key, err);
+            }
+        }
+    }
+}
+
+impl Drop for Claimed {
+    fn drop(&mut self) {
+        let Some(key) = self.key.take() else {
+            return;
+        };
+        let store = self.store.clone();
+        rt::spawn(async move {
+            if let Err(err) = store.release(&key).await {
+                warn!("idempotency key {} not released: {}", key, err);
+            }
+        });
+    }
+}
+
+async fn read_body(req: &mut ServiceRequest, max_body: usize) -> Result<web::Bytes, Error> {
+    let mut payload = req.take_payload();
+    let mut body = web::BytesMut::new();
+    while let Some(chunk) = payload.next().await {
+        let chunk = chunk?;
+        if body.len() + chunk.len() > max_body {
+            return Err(error::ErrorPayloadTooLarge("request body too large for an Idempotency-Key"));
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(body.freeze())
+}
+
+/// Honors `Idempotency-Key` on POST and PATCH requests, with the [`Idempotency`] in `web::Data`.
+///
+/// The first response for a key, route and body is stored and sent again for retries;
+/// reusing the key on the route with another path or body, or while the first request
+/// runs, gets 409. Requests no route serves, server errors, and streamed or oversized
+/// responses are not stored, so those can be retried for real.
+pub async fn replay(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, Error> {
+    let idempotency = match req.app_data::<web::Data<Idempotency>>() {
+        Some(idempotency) if matches!(*req.method(), http::Method::POST | http::Method::PATCH) => {
+            idempotency.clone()
+        }
+        _ => return Ok(next.call(req).await?.map_into_left_body()),
+    };
+    let Some(key) = req.headers().get(IDEMPOTENCY_KEY) else {
+        return Ok(next.call(req).await?.map_into_left_body());
+    };
+    let key = match key.to_str() {
+        Ok(key) if !key.is_empty() && key.len() <= 255 => key.to_owned(),
+        _ => {
+            let err = error::ErrorBadRequest("Idempotency-Key must be 1 to 255 visible ASCII characters");
+            return Ok(req.error_response(err).map_into_right_body());
+        }
+    };
+
+    // Routing has not run yet, so the pattern comes from the registered routes.
+    let Some(route) = registered_pattern(req.request(), req.method(), req.path()) else {
+        return Ok(next.call(req).await?.map_into_left_body());
+    };
+
+    let body = match read_body(&mut req, idempotency.max_body).await {
+        Ok(body) => body,
+        Err(err) => return Ok(req.error_response(err).map_into_right_body()),
+    };
+    let key = format!("{} {} {}", req.method(), route, key);
+    let lease_ends_at = now_millis() + idempotency.lease.as_millis() as i64;
+
+    match idempotency.store.claim(&key, &fingerprint(req.path(), &body), lease_ends_at).await? {
+        Claim::Started => {}
+        Claim::Done(stored) => return Ok(req.into_response(stored.to_response()).map_into_right_body()),
+        Claim::InProgress => {
+            let err = error::ErrorConflict("a request with this Idempotency-Key is still in progress");
+            return Ok(req.error_response(err).map_into_right_body());
+        }
+        Claim::Mismatch => {
+            let err = error::ErrorConflict("Idempotency-Key was already used with a different request");
+            return Ok(req.error_response(err).map_into_right_body());
+        }
+    }
+    let claimed = Claimed {
+        store: idempotency.store.clone(),
+        key: Some(key.clone()),
+    };
+
+    req.set_payload(Payload::Stream {
+        payload: Box::pin(stream::once(ready(Ok(body)))),
+    });
+    let res = match next.call(req).await {
+        Ok(res) if res.status().is_server_error() => {
+            claimed.release().await;
+            return Ok(res.map_into_left_body());
+        }
+        Ok(res) => res,
+        Err(err) => {
+            claimed.release().await;
+            return Err(err);
+        }
+    };
+
+    let (req, res) = res.into_parts();
+    let (res, body) = res.into_parts();
+    let stored = match body.size() {
+        BodySize::None => true,
+        BodySize::Sized(size) => size <= idempotency.max_body as u64,
+        BodySize::Stream => false,
+    };
+    if !stored {
+        claimed.release().await;
+        return Ok(ServiceResponse::new(req, res.set_body(body)).map_into_left_body());
+    }
+    let body = body::to_bytes(body).await.map_err(|err| {
+        let err: Box<dyn std::error::Error> = err.into();
+        error::ErrorInternalServerError(err.to_string())
+    })?;
+    let stored = StoredResponse {
+        status: res.status().as_u16(),
+        headers: res
+            .headers()
+            .iter()
+            .filter(|(name, _)| !UNSTORED_HEADERS.contains(&name.as_str()))
+            .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_owned())))
+            .collect(),
+        body: body.to_vec(),
+    };
+    let expires_at = now_millis() + idempotency.ttl.as_millis() as i64;
+    match idempotency.store.complete(&key, &stored, expires_at).await {
+        Ok(()) => claimed.keep(),
+        Err(err) => warn!("idempotency key {} not stored: {}", key, err),
+    }
+
+    let res = res.set_body(BoxBody::new(body));
+    Ok(ServiceResponse::new(req, res).map_into_right_body())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::middleware::from_fn;
+    use actix_web::{test, App};
+
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[derive(Default)]
+    struct Calls(AtomicUsize);
+
+    async fn create(calls: web::Data<Calls>, body: String) -> HttpResponse {
+        let id = calls.0.fetch_add(1, Ordering::SeqCst) + 1;
+        HttpResponse::Created()
+            .insert_header(("location", format!("/items/{}", id)))
+            .body(format!("{} {}", id, body))
+    }
+
+    async fn login(calls: web::Data<Calls>) -> HttpResponse {
+        let id = calls.0.fetch_add(1, Ordering::SeqCst) + 1;
+        HttpResponse::Ok()
+            .insert_header(("set-cookie", format!("session={}", id)))
+            .body(format!("{}", id))
+    }
+
+    async fn streamed(calls: web::Data<Calls>) -> HttpResponse {
+        let id = calls.0.fetch_add(1, Ordering::SeqCst) + 1;
+        let chunk: Result<_, Error> = Ok(web::Bytes::from(format!("{}", id)));
+        HttpResponse::Ok().streaming(stream::once(ready(chunk)))
+    }
+
+    async fn broken(calls: web::Data<Calls>) -> HttpResponse {
+        calls.0.fetch_add(1, Ordering::SeqCst);
+        HttpResponse::ServiceUnavailable().finish()
+    }
+
+    async fn call(idempotency: Idempotency, requests: &[(&'static str, &'static str, &'static str)]) -> Vec<String> {
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(idempotency))
+                .app_data(web::Data::new(Calls::default()))
+                .wrap(from_fn(replay))
+                .route("/items", web::post().to(create))
+                .route("/other", web::post().to(create))
+                .route("/login", web::post().to(login))
+                .route("/streamed", web::post().to(streamed))
+                .route("/broken", web::post().to(broken)),
+        )
+        .await;
+
+        let mut answers = vec![];
+        for &(uri, key, body) in requests {
+            let mut req = test::TestRequest::post().uri(uri).set_payload(body);
+            if !key.is_empty() {
+                req = req.insert_header((IDEMPOTENCY_KEY, key));
+            }
+            let res = test::call_service(&app, req.to_request()).await;
+            let replayed = res.headers().contains_key(IDEMPOTENT_REPLAYED);
+            let location = ["location", "set-cookie"]
+                .iter()
+                .find_map(|name| res.headers().get(*name))
+                .map(|value| value.to_str().unwrap().to_owned());
+            let status = res.status().as_u16();
+            let body = String::from_utf8(test::read_body(res).await.to_vec()).unwrap();
+            answers.push(format!(
+                "{} {}{}{}",
+                status,
+                body,
+                location.map(|location| format!(" @{}", location)).unwrap_or_default(),
+                if replayed { " (replayed)" } else { "" }
+            ));
+        }
+        answers
+    }
+
+    async fn check_store(idempotency: Idempotency) {
+        let answers = call(
+            idempotency,
+            &[
+                ("/items", "a", "one"),
+                ("/items", "a", "one"),
+                ("/items", "a", "two"),
+                ("/other", "a", "one"),
+                ("/items", "", "one"),
+                ("/broken", "b", "one"),
+                ("/broken", "b", "one"),
+                ("/missing", "c", "one"),
+                ("/missing", "c", "one"),
+            ],
+        )
+        .await;
+        assert_eq!(answers[0], "201 1 one @/items/1");
+        assert_eq!(answers[1], "201 1 one @/items/1 (replayed)");
+        assert!(answers[2].starts_with("409 "), "{}", answers[2]);
+        // Keys are scoped to the route; requests without one are not tracked.
+        assert_eq!(answers[3], "201 2 one @/items/2");
+        assert_eq!(answers[4], "201 3 one @/items/3");
+        // Server errors and requests no route serves are not stored.
+        assert_eq!(answers[5..7], ["503 ", "503 "]);
+        assert_eq!(answers[7..], ["404 ", "404 "]);
+    }
+
+    #[actix_web::test]
+    async fn test_memory_store() {
+        check_store(Idempotency::new(MemoryStore::default())).await;
+    }
+
+    #[actix_web::test]
+    async fn test_sqlite_store() {
+        check_store(Idempotency::new(SqliteStore::connect("sqlite::memory:").await.unwrap())).await;
+    }
+
+    #[actix_web::test]
+    async fn test_claims() {
+        let store = SqliteStore::connect("sqlite::memory:").await.unwrap();
+        let later = now_millis() + 60_000;
+        let response = StoredResponse {
+            status: 201,
+            headers: vec![("content-type".to_owned(), "text/plain".to_owned())],
+            body: b"done".to_vec(),
+        };
+
+        assert_eq!(store.claim("k", "f1", later).await.unwrap(), Claim::Started);
+        assert_eq!(store.claim("k", "f1", later).await.unwrap(), Claim::InProgress);
+        store.complete("k", &response, later).await.unwrap();
+        assert_eq!(store.claim("k", "f1", later).await.unwrap(), Claim::Done(response));
+        assert_eq!(store.claim("k", "f2", later).await.unwrap(), Claim::Mismatch);
+
+        store.release("k").await.unwrap();
+        assert_eq!(store.claim("k", "f2", later).await.unwrap(), Claim::Started);
+
+        // Expired claims make room for new ones.
+        assert_eq!(store.claim("old", "f1", now_millis()).await.unwrap(), Claim::Started);
+        assert_eq!(store.claim("old", "f2", later).await.unwrap(), Claim::Started);
+
+        // Oversized bodies are refused before the handler runs, and oversized responses
+        // are not stored.
+        let answers = call(
+            Idempotency::new(MemoryStore::default()).max_body(3),
+            &[("/items", "a", "four"), ("/items", "b", "one"), ("/items", "b", "one")],
+        )
+        .await;
+        assert!(answers[0].starts_with("413 "), "{}", answers[0]);
+        assert_eq!(answers[1..], ["201 1 one @/items/1", "201 2 one @/items/2"]);
+    }
+
+    async fn check_leases(store: impl IdempotencyStore) {
+        let response = StoredResponse {
+            status: 201,
+            headers: vec![],
+            body: b"done".to_vec(),
+        };
+        let soon = now_millis() + 50;
+        let later = now_millis() + 60_000;
+
+        // A request that never finished, as when the process stops, holds its key only
+        // for the lease.
+        assert_eq!(store.claim("k", "f1", soon).await.unwrap(), Claim::Started);
+        assert_eq!(store.claim("k", "f2", soon).await.unwrap(), Claim::Mismatch);
+        // A finished one keeps its response past the lease.
+        assert_eq!(store.claim("done", "f1", soon).await.unwrap(), Claim::Started);
+        store.complete("done", &response, later).await.unwrap();
+
+        rt::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(store.claim("k", "f2", later).await.unwrap(), Claim::Started);
+        assert_eq!(store.claim("k", "f2", later).await.unwrap(), Claim::InProgress);
+        assert_eq!(store.claim("done", "f1", later).await.unwrap(), Claim::Done(response));
+    }
+
+    #[actix_web::test]
+    async fn test_leases() {
+        check_leases(MemoryStore::default()).await;
+        check_leases(SqliteStore::connect("sqlite::memory:").await.unwrap()).await;
+    }
+
+    #[actix_web::test]
+    async fn test_unstored_responses() {
+        let answers = call(
+            Idempotency::new(MemoryStore::default()),
+            &[
+                ("/login", "a", ""),
+                ("/login", "a", ""),
+                ("/streamed", "b", ""),
+                ("/streamed", "b", ""),
+            ],
+        )
+        .await;
+        // Cookies belong to whoever first sent the key.
+        assert_eq!(answers[..2], ["200 1 @session=1", "200 1 (replayed)"]);
+        // Streams are passed on as they come, so there is nothing to replay.
+        assert_eq!(answers[2..], ["200 2", "200 3"]);
+    }
+}
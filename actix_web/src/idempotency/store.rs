@@ -0,0 +1,181 @@
+use async_trait::async_trait;
+use sea_orm::sea_query::Expr;
+use sea_orm::*;
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use super::entity::{ActiveModel, Column, Entity};
+use super::{now_millis, Claim, IdempotencyError, IdempotencyStore, StoredResponse};
+
+/// Expired keys are swept once every this many claims; until then they are replaced when
+/// claimed again.
+const SWEEP_EVERY: usize = 100;
+
+/// Counts claims to tell when to sweep.
+#[derive(Default)]
+struct Sweeps(AtomicUsize);
+
+impl Sweeps {
+    fn due(&self) -> bool {
+        self.0.fetch_add(1, Ordering::Relaxed).is_multiple_of(SWEEP_EVERY)
+    }
+}
+
+fn claim_for(fingerprint: &str, stored: &str, response: Option<StoredResponse>) -> Claim {
+    match response {
+        _ if fingerprint != stored => Claim::Mismatch,
+        Some(response) => Claim::Done(response),
+        None => Claim::InProgress,
+    }
+}
+
+struct Entry {
+    fingerprint: String,
+    response: Option<StoredResponse>,
+    expires_at: i64,
+}
+
+/// Keys kept in process memory, lost on restart and not shared between instances.
+#[derive(Default)]
+pub struct MemoryStore {
+    entries: Mutex<HashMap<String, Entry>>,
+    sweeps: Sweeps,
+}
+
+#[async_trait(?Send)]
+impl IdempotencyStore for MemoryStore {
+    async fn claim(&self, key: &str, fingerprint: &str, expires_at: i64) -> Result<Claim, IdempotencyError> {
+        let now = now_millis();
+        let mut entries = self.entries.lock().unwrap();
+        if self.sweeps.due() {
+            entries.retain(|_, entry| entry.expires_at > now);
+        }
+
+        if let Some(entry) = entries.get(key).filter(|entry| entry.expires_at > now) {
+            return Ok(claim_for(fingerprint, &entry.fingerprint, entry.response.clone()));
+        }
+        entries.insert(
+            key.to_owned(),
+            Entry {
+                fingerprint: fingerprint.to_owned(),
+                response: None,
+                expires_at,
+            },
+        );
+        Ok(Claim::Started)
+    }
+
+    async fn complete(&self, key: &str, response: &StoredResponse, expires_at: i64) -> Result<(), IdempotencyError> {
+        if let Some(entry) = self.entries.lock().unwrap().get_mut(key) {
+            entry.response = Some(response.clone());
+            entry.expires_at = expires_at;
+        }
+        Ok(())
+    }
+
+    async fn release(&self, key: &str) -> Result<(), IdempotencyError> {
+        self.entries.lock().unwrap().remove(key);
+        Ok(())
+    }
+}
+
+/// Keys in an `idempotency_key` table, shared by every instance using the database.
+pub struct SqliteStore {
+    db: DatabaseConnection,
+    sweeps: Sweeps,
+}
+
+impl SqliteStore {
+    /// Uses `db`, creating the table when it does not exist yet.
+    pub async fn new(db: DatabaseConnection) -> Result<Self, DbErr> {
+        let backend = db.get_database_backend();
+        let mut stmt = Schema::new(backend).create_table_from_entity(Entity);
+        db.execute(backend.build(stmt.if_not_exists())).await?;
+        Ok(SqliteStore {
+            db,
+            sweeps: Sweeps::default(),
+        })
+    }
+
+    pub async fn connect(url: &str) -> Result<Self, DbErr> {
+        SqliteStore::new(Database::connect(url).await?).await
+    }
+}
+
+#[async_trait(?Send)]
+impl IdempotencyStore for SqliteStore {
+    async fn claim(&self, key: &str, fingerprint: &str, expires_at: i64) -> Result<Claim, IdempotencyError> {
+        let now = now_millis();
+        if self.sweeps.due() {
+            Entity::delete_many()
+                .filter(Column::ExpiresAt.lte(now))
+                .exec(&self.db)
+                .await?;
+        }
+
+        let entry = ActiveModel {
+            key: Set(key.to_owned()),
+            fingerprint: Set(fingerprint.to_owned()),
+            status: Set(None),
+            headers: Set(None),
+            body: Set(None),
+            expires_at: Set(expires_at),
+        };
+        // The primary key makes concurrent claims for one key race safely: the loser reads the winner's row.
+        let err = match Entity::insert(entry.clone()).exec(&self.db).await {
+            Ok(_) => return Ok(Claim::Started),
+            Err(err) => err,
+        };
+        let Some(found) = Entity::find_by_id(key.to_owned()).one(&self.db).await? else {
+            return Err(err.into());
+        };
+        // An expired row not swept yet is taken over, unless another claim got there first.
+        let entry = match found.expires_at > now {
+            true => found,
+            false => {
+                let taken = Entity::update_many()
+                    .set(entry)
+                    .filter(Column::Key.eq(key))
+                    .filter(Column::ExpiresAt.lte(now))
+                    .exec(&self.db)
+                    .await?;
+                if taken.rows_affected == 1 {
+                    return Ok(Claim::Started);
+                }
+                Entity::find_by_id(key.to_owned())
+                    .one(&self.db)
+                    .await?
+                    .ok_or(err)?
+            }
+        };
+
+        let response = match (entry.status, entry.headers) {
+            (Some(status), Some(headers)) => Some(StoredResponse {
+                status: status as u16,
+                headers: serde_json::from_value(headers)?,
+                body: entry.body.unwrap_or_default(),
+            }),
+            _ => None,
+        };
+        Ok(claim_for(fingerprint, &entry.fingerprint, response))
+    }
+
+    async fn complete(&self, key: &str, response: &StoredResponse, expires_at: i64) -> Result<(), IdempotencyError> {
+        Entity::update_many()
+            .col_expr(Column::ExpiresAt, Expr::value(expires_at))
+            .col_expr(Column::Status, Expr::value(i32::from(response.status)))
+            .col_expr(Column::Headers, Expr::value(serde_json::to_value(&response.headers)?))
+            .col_expr(Column::Body, Expr::value(response.body.clone()))
+            .filter(Column::Key.eq(key))
+            .exec(&self.db)
+            .await?;
+        Ok(())
+    }
+
+    async fn release(&self, key: &str) -> Result<(), IdempotencyError> {
+        Entity::delete_by_id(key.to_owned()).exec(&self.db).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,410 @@
+use actix_web::error;
+use async_trait::async_trait;
+use futures::future::LocalBoxFuture;
+use futures::stream::{self, Stream, TryStreamExt};
+use sea_orm::sea_query::Expr;
+use sea_orm::*;
+use serde::{de::DeserializeOwned, Serialize};
+
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub mod entity;
+pub mod worker;
+
+pub use entity::{Model as JobModel, Status};
+pub use worker::{WorkerConfig, WorkerPool};
+
+use entity::{ActiveModel, Column, Entity};
+
+/// A unit of work that can be persisted to the queue and performed later by a worker.
+#[async_trait(?Send)]
+pub trait Job: Serialize + DeserializeOwned + 'static {
+    /// Name stored alongside the payload, used to find the handler again.
+    const KIND: &'static str;
+    /// Attempts made before the job is dead-lettered.
+    const MAX_ATTEMPTS: i32 = 5;
+
+    async fn perform(self) -> Result<(), String>;
+}
+
+#[derive(Debug, derive_more::Display, derive_more::From)]
+pub enum JobError {
+    #[display(fmt = "database error: {}", _0)]
+    Db(DbErr),
+    #[display(fmt = "invalid payload: {}", _0)]
+    Payload(serde_json::Error),
+    #[display(fmt = "unknown job kind: {}", _0)]
+    #[from(ignore)]
+    UnknownKind(String),
+    #[display(fmt = "{}", _0)]
+    #[from(ignore)]
+    Failed(String),
+}
+
+impl JobError {
+    /// Whether trying the job again cannot help, as when there is no handler for it or
+    /// its payload does not fit the handler.
+    pub fn is_permanent(&self) -> bool {
+        matches!(self, JobError::Payload(_) | JobError::UnknownKind(_))
+    }
+}
+
+impl std::error::Error for JobError {}
+
+impl error::ResponseError for JobError {}
+
+type Handler = Box<dyn Fn(serde_json::Value) -> LocalBoxFuture<'static, Result<(), JobError>>>;
+
+/// Maps job kinds to the code that performs them.
+#[derive(Default)]
+pub struct Registry {
+    handlers: HashMap<&'static str, Handler>,
+}
+
+impl Registry {
+    pub fn register<J: Job>(&mut self) -> &mut Self {
+        let handler: Handler = Box::new(|payload| {
+            Box::pin(async move {
+                let job: J = serde_json::from_value(payload)?;
+                job.perform().await.map_err(JobError::Failed)
+            })
+        });
+        self.handlers.insert(J::KIND, handler);
+        self
+    }
+
+    pub async fn perform(&self, kind: &str, payload: serde_json::Value) -> Result<(), JobError> {
+        match self.handlers.get(kind) {
+            Some(handler) => handler(payload).await,
+            None => Err(JobError::UnknownKind(kind.to_owned())),
+        }
+    }
+}
+
+/// Job storage backed by SQLite, shared by request handlers and workers.
+#[derive(Clone)]
+pub struct JobQueue {
+    db: DatabaseConnection,
+}
+
+impl JobQueue {
+    pub async fn connect(url: &str) -> Result<Self, DbErr> {
+        let queue = JobQueue {
+            db: Database::connect(url).await?,
+        };
+        queue.setup().await?;
+        Ok(queue)
+    }
+
+    pub fn connection(&self) -> &DatabaseConnection {
+        &self.db
+    }
+
+    async fn setup(&self) -> Result<(), DbErr> {
+        let backend = self.db.get_database_backend();
+        let mut stmt = Schema::new(backend).create_table_from_entity(Entity);
+        self.db.execute(backend.build(stmt.if_not_exists())).await?;
+
+        // Jobs that were running when the process stopped are picked up again.
+        Entity::update_many()
+            .col_expr(Column::Status, Expr::value(Status::Pending))
+            .filter(Column::Status.eq(Status::Running))
+            .exec(&self.db)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn enqueue<J: Job>(&self, job: &J) -> Result<i32, JobError> {
+        let now = now_millis();
+        let job = ActiveModel {
+            kind: Set(J::KIND.to_owned()),
+            payload: Set(serde_json::to_value(job)?),
+            status: Set(Status::Pending),
+            attempts: Set(0),
+            max_attempts: Set(J::MAX_ATTEMPTS),
+            run_at: Set(now),
+            last_error: Set(None),
+            created_at: Set(now),
+            updated_at: Set(now),
+            ..Default::default()
+        };
+        let res = Entity::insert(job).exec(&self.db).await?;
+        Ok(res.last_insert_id)
+    }
+
+    /// Marks the next due job as running and returns it.
+    pub async fn claim(&self) -> Result<Option<JobModel>, DbErr> {
+        let now = now_millis();
+        let Some(job) = Entity::find()
+            .filter(Column::Status.eq(Status::Pending))
+            .filter(Column::RunAt.lte(now))
+            .order_by_asc(Column::RunAt)
+            .order_by_asc(Column::Id)
+            .one(&self.db)
+            .await?
+        else {
+            return Ok(None);
+        };
+
+        let res = Entity::update_many()
+            .col_expr(Column::Status, Expr::value(Status::Running))
+            .col_expr(Column::Attempts, Expr::col(Column::Attempts).add(1))
+            .col_expr(Column::UpdatedAt, Expr::value(now))
+            .filter(Column::Id.eq(job.id))
+            .filter(Column::Status.eq(Status::Pending))
+            .exec(&self.db)
+            .await?;
+        if res.rows_affected == 0 {
+            return Ok(None);
+        }
+
+        Entity::find_by_id(job.id).one(&self.db).await
+    }
+
+    pub async fn complete(&self, job: JobModel) -> Result<JobModel, DbErr> {
+        let mut job = job.into_active_model();
+        job.status = Set(Status::Done);
+        job.last_error = Set(None);
+        job.updated_at = Set(now_millis());
+        job.update(&self.db).await
+    }
+
+    /// Schedules the job again after `retry_in`, or dead-letters it once it is out of attempts.
+    pub async fn fail(&self, job: JobModel, error: &str, retry_in: Duration) -> Result<JobModel, DbErr> {
+        let now = now_millis();
+        let dead = job.attempts >= job.max_attempts;
+
+        let mut job = job.into_active_model();
+        if dead {
+            job.status = Set(Status::Dead);
+        } else {
+            job.status = Set(Status::Pending);
+            job.run_at = Set(now + retry_in.as_millis() as i64);
+        }
+        job.last_error = Set(Some(error.to_owned()));
+        job.updated_at = Set(now);
+        job.update(&self.db).await
+    }
+
+    /// Dead-letters the job at once, whatever attempts it has left.
+    pub async fn bury(&self, job: JobModel, error: &str) -> Result<JobModel, DbErr> {
+        let mut job = job.into_active_model();
+        job.status = Set(Status::Dead);
+        job.last_error = Set(Some(error.to_owned()));
+        job.updated_at = Set(now_millis());
+        job.update(&self.db).await
+    }
+
+    pub async fn list(&self, status: Option<Status>) -> Result<Vec<JobModel>, DbErr> {
+        let mut select = Entity::find().order_by_asc(Column::Id);
+        if let Some(status) = status {
+            select = select.filter(Column::Status.eq(status));
+        }
+        select.all(&self.db).await
+    }
+
+    /// Like [`JobQueue::list`], but fetches `page_size` rows at a time as the stream is read.
+    pub fn stream(&self, status: Option<Status>, page_size: u64) -> impl Stream<Item = Result<JobModel, DbErr>> {
+        let db = self.db.clone();
+        stream::try_unfold(Some(0), move |after| {
+            let db = db.clone();
+            async move {
+                let Some(after) = after else {
+                    return Ok(None);
+                };
+                let mut select = Entity::find()
+                    .filter(Column::Id.gt(after))
+                    .order_by_asc(Column::Id)
+                    .limit(page_size);
+                if let Some(status) = status {
+                    select = select.filter(Column::Status.eq(status));
+                }
+                let page = select.all(&db).await?;
+
+                let next = match page.last() {
+                    Some(job) if page.len() as u64 == page_size => Some(job.id),
+                    _ => None,
+                };
+                Ok::<_, DbErr>(Some((stream::iter(page.into_iter().map(Ok)), next)))
+            }
+        })
+        .try_flatten()
+    }
+
+    /// Puts a dead job back into the queue with a fresh set of attempts.
+    pub async fn retry(&self, id: i32) -> Result<Option<JobModel>, DbErr> {
+        let Some(job) = Entity::find_by_id(id)
+            .filter(Column::Status.eq(Status::Dead))
+            .one(&self.db)
+            .await?
+        else {
+            return Ok(None);
+        };
+
+        let now = now_millis();
+        let mut job = job.into_active_model();
+        job.status = Set(Status::Pending);
+        job.attempts = Set(0);
+        job.run_at = Set(now);
+        job.updated_at = Set(now);
+        Ok(Some(job.update(&self.db).await?))
+    }
+}
+
+fn now_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+    use worker::Backoff;
+
+    #[derive(Serialize, Deserialize)]
+    struct Greet {
+        name: String,
+    }
+
+    #[async_trait(?Send)]
+    impl Job for Greet {
+        const KIND: &'static str = "greet";
+        const MAX_ATTEMPTS: i32 = 2;
+
+        async fn perform(self) -> Result<(), String> {
+            if self.name.is_empty() {
+                return Err("name is empty".to_owned());
+            }
+            Ok(())
+        }
+    }
+
+    fn registry() -> Registry {
+        let mut registry = Registry::default();
+        registry.register::<Greet>();
+        registry
+    }
+
+    fn backoff() -> Backoff {
+        Backoff {
+            base: Duration::ZERO,
+            max: Duration::ZERO,
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_job_done() {
+        let queue = JobQueue::connect("sqlite::memory:").await.unwrap();
+        let id = queue.enqueue(&Greet { name: "ittokun".to_owned() }).await.unwrap();
+
+        assert!(worker::run_once(&queue, &registry(), &backoff()).await.unwrap());
+        assert!(!worker::run_once(&queue, &registry(), &backoff()).await.unwrap());
+
+        let jobs = queue.list(Some(Status::Done)).await.unwrap();
+        assert_eq!(jobs.len(), 1);
+        assert_eq!(jobs[0].id, id);
+        assert_eq!(jobs[0].attempts, 1);
+    }
+
+    #[actix_web::test]
+    async fn test_job_dead_letter_and_retry() {
+        let queue = JobQueue::connect("sqlite::memory:").await.unwrap();
+        let id = queue.enqueue(&Greet { name: String::new() }).await.unwrap();
+
+        for _ in 0..Greet::MAX_ATTEMPTS {
+            assert!(worker::run_once(&queue, &registry(), &backoff()).await.unwrap());
+        }
+        let dead = queue.list(Some(Status::Dead)).await.unwrap();
+        assert_eq!(dead.len(), 1);
+        assert_eq!(dead[0].last_error.as_deref(), Some("name is empty"));
+
+        let job = queue.retry(id).await.unwrap().unwrap();
+        assert_eq!(job.status, Status::Pending);
+        assert_eq!(job.attempts, 0);
+        assert!(queue.retry(id).await.unwrap().is_none());
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct Count {
+        count: i32,
+    }
+
+    #[async_trait(?Send)]
+    impl Job for Count {
+        const KIND: &'static str = "count";
+
+        async fn perform(self) -> Result<(), String> {
+            Ok(())
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_job_permanent_errors() {
+        let queue = JobQueue::connect("sqlite::memory:").await.unwrap();
+        queue.enqueue(&Count { count: 1 }).await.unwrap();
+        assert!(worker::run_once(&queue, &registry(), &backoff()).await.unwrap());
+
+        // A payload the handler cannot read.
+        let now = now_millis();
+        let bad = ActiveModel {
+            kind: Set(Greet::KIND.to_owned()),
+            payload: Set(serde_json::json!({ "count": 1 })),
+            status: Set(Status::Pending),
+            attempts: Set(0),
+            max_attempts: Set(Greet::MAX_ATTEMPTS),
+            run_at: Set(now),
+            last_error: Set(None),
+            created_at: Set(now),
+            updated_at: Set(now),
+            ..Default::default()
+        };
+        Entity::insert(bad).exec(queue.connection()).await.unwrap();
+        assert!(worker::run_once(&queue, &registry(), &backoff()).await.unwrap());
+
+        // Neither is tried again, attempts left or not.
+        let dead = queue.list(Some(Status::Dead)).await.unwrap();
+        assert_eq!(dead.len(), 2);
+        assert!(dead.iter().all(|job| job.attempts == 1));
+        assert_eq!(dead[0].last_error.as_deref(), Some("unknown job kind: count"));
+        assert!(dead[1].last_error.as_deref().unwrap().starts_with("invalid payload"));
+    }
+
+    #[actix_web::test]
+    async fn test_job_backoff() {
+        let queue = JobQueue::connect("sqlite::memory:").await.unwrap();
+        queue.enqueue(&Greet { name: String::new() }).await.unwrap();
+        let backoff = Backoff {
+            base: Duration::from_secs(60),
+            max: Duration::from_secs(600),
+        };
+
+        assert!(worker::run_once(&queue, &registry(), &backoff).await.unwrap());
+        // The failed job is not due again until the backoff has passed.
+        assert!(queue.claim().await.unwrap().is_none());
+        assert_eq!(backoff.delay(3), Duration::from_secs(240));
+        assert_eq!(backoff.delay(10), Duration::from_secs(600));
+    }
+
+    #[actix_web::test]
+    async fn test_job_stream_pages() {
+        let queue = JobQueue::connect("sqlite::memory:").await.unwrap();
+        for name in ["a", "b", "c", "d", "e"] {
+            queue.enqueue(&Greet { name: name.to_owned() }).await.unwrap();
+        }
+
+        let ids: Vec<i32> = queue
+            .stream(None, 2)
+            .map_ok(|job| job.id)
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(ids, [1, 2, 3, 4, 5]);
+
+        let done: Vec<JobModel> = queue.stream(Some(Status::Done), 2).try_collect().await.unwrap();
+        assert!(done.is_empty());
+    }
+}
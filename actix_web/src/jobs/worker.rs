@@ -0,0 +1,121 @@
+use actix_web::rt;
+use log::{error, warn};
+
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::{JobError, JobQueue, Registry};
+
+/// Exponential backoff between attempts of a failing job.
+#[derive(Clone, Debug)]
+pub struct Backoff {
+    pub base: Duration,
+    pub max: Duration,
+}
+
+impl Backoff {
+    /// Delay before the next try, after `attempt` attempts have failed.
+    pub fn delay(&self, attempt: i32) -> Duration {
+        let exp = attempt.saturating_sub(1).clamp(0, 31) as u32;
+        self.base.saturating_mul(2u32.saturating_pow(exp)).min(self.max)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct WorkerConfig {
+    pub workers: usize,
+    pub poll_interval: Duration,
+    pub backoff: Backoff,
+}
+
+impl Default for WorkerConfig {
+    fn default() -> Self {
+        WorkerConfig {
+            workers: 2,
+            poll_interval: Duration::from_secs(1),
+            backoff: Backoff {
+                base: Duration::from_secs(1),
+                max: Duration::from_secs(300),
+            },
+        }
+    }
+}
+
+/// Worker tasks running on the current actix system.
+pub struct WorkerPool {
+    running: Arc<AtomicBool>,
+    handles: Vec<rt::task::JoinHandle<()>>,
+}
+
+impl WorkerPool {
+    pub fn start(queue: JobQueue, registry: Registry, config: WorkerConfig) -> Self {
+        let running = Arc::new(AtomicBool::new(true));
+        let registry = Rc::new(registry);
+
+        let handles = (0..config.workers)
+            .map(|id| {
+                rt::spawn(work(
+                    id,
+                    queue.clone(),
+                    registry.clone(),
+                    config.clone(),
+                    running.clone(),
+                ))
+            })
+            .collect();
+
+        WorkerPool { running, handles }
+    }
+
+    /// Lets every worker finish its current job, then waits for them to exit.
+    pub async fn stop(self) {
+        self.running.store(false, Ordering::Relaxed);
+        for handle in self.handles {
+            let _ = handle.await;
+        }
+    }
+}
+
+async fn work(
+    id: usize,
+    queue: JobQueue,
+    registry: Rc<Registry>,
+    config: WorkerConfig,
+    running: Arc<AtomicBool>,
+) {
+    while running.load(Ordering::Relaxed) {
+        match run_once(&queue, &registry, &config.backoff).await {
+            Ok(true) => continue,
+            Ok(false) => rt::time::sleep(config.poll_interval).await,
+            Err(err) => {
+                error!("job worker {}: {}", id, err);
+                rt::time::sleep(config.poll_interval).await;
+            }
+        }
+    }
+}
+
+/// Performs the next due job, if any. Returns whether a job was claimed.
+pub async fn run_once(queue: &JobQueue, registry: &Registry, backoff: &Backoff) -> Result<bool, JobError> {
+    let Some(job) = queue.claim().await? else {
+        return Ok(false);
+    };
+
+    match registry.perform(&job.kind, job.payload.clone()).await {
+        Ok(()) => {
+            queue.complete(job).await?;
+        }
+        Err(err) if err.is_permanent() => {
+            error!("job {} ({}) cannot be performed: {}", job.id, job.kind, err);
+            queue.bury(job, &err.to_string()).await?;
+        }
+        Err(err) => {
+            warn!("job {} ({}) attempt {} failed: {}", job.id, job.kind, job.attempts, err);
+            let retry_in = backoff.delay(job.attempts);
+            queue.fail(job, &err.to_string(), retry_in).await?;
+        }
+    }
+    Ok(true)
+}
@@ -0,0 +1,140 @@
+use actix_files::Files;
+use actix_http::Request;
+use actix_web::body::MessageBody;
+use actix_service::IntoServiceFactory;
+use actix_web::dev::{AppConfig, ServiceFactory, ServiceRequest, ServiceResponse};
+use actix_web::middleware::{self, Logger};
+use actix_web::{http, web, App, Error};
+
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+pub mod audit;
+pub mod flags;
+pub mod health;
+pub mod idempotency;
+pub mod i18n;
+pub mod jobs;
+pub mod protocol;
+pub mod routes;
+pub mod routing;
+pub mod streaming;
+pub mod templates;
+pub mod tls;
+pub mod vhost;
+
+/// Shared state created once at startup and handed to every worker's `App`.
+#[derive(Clone)]
+pub struct AppData {
+    pub queue: web::Data<jobs::JobQueue>,
+    pub health: web::Data<health::Health>,
+    pub templates: web::Data<templates::Templates>,
+    pub limits: web::Data<protocol::Limits>,
+    pub sites: web::Data<vhost::Sites>,
+    pub audit: web::Data<audit::AuditLog>,
+    pub flags: web::Data<flags::FlagStore>,
+    pub idempotency: web::Data<idempotency::Idempotency>,
+}
+
+impl AppData {
+    /// State for tests and load tests: an in-memory database, a throwaway audit log and
+    /// the sites and flags files of this crate, whatever the working directory.
+    pub async fn ephemeral() -> AppData {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let audit = audit::AuditConfig {
+            path: std::env::temp_dir().join(format!(
+                "actix-example-audit-{}-{}.jsonl",
+                std::process::id(),
+                NEXT.fetch_add(1, Ordering::Relaxed)
+            )),
+            ..audit::AuditConfig::default()
+        };
+        let _ = std::fs::remove_file(&audit.path);
+        let path = |name: &str| format!("{}/{}", env!("CARGO_MANIFEST_DIR"), name);
+
+        AppData {
+            queue: web::Data::new(jobs::JobQueue::connect("sqlite::memory:").await.unwrap()),
+            health: web::Data::new(health::Health::default()),
+            templates: web::Data::new(templates::Templates::new(&path("templates"), false).unwrap()),
+            limits: web::Data::new(protocol::ProtocolConfig::default().limits()),
+            sites: web::Data::new(vhost::Sites::load(&path("sites.toml")).unwrap()),
+            audit: web::Data::new(audit::AuditLog::open(audit).unwrap()),
+            flags: web::Data::new(flags::FlagStore::open(path("flags.toml"), Duration::from_secs(1)).unwrap()),
+            idempotency: web::Data::new(idempotency::Idempotency::new(idempotency::MemoryStore::default())),
+        }
+    }
+}
+
+/// Trailing slashes are trimmed, except under `/url-dispatch` where they are redirected away.
+pub fn routing_policy() -> routing::RoutingPolicy {
+    routing::RoutingPolicy::new(routing::SlashPolicy::Trim)
+        .scope("/url-dispatch", routing::SlashPolicy::Redirect(http::StatusCode::PERMANENT_REDIRECT))
+}
+
+/// The application served by `main`: every route module for most hosts, and an app of its
+/// own for each site in `data.sites`.
+pub fn create_app(data: AppData) -> impl ServiceFactory<
+    Request,
+    Config = AppConfig,
+    Response = ServiceResponse<impl MessageBody>,
+    Error = Error,
+    InitError = (),
+> {
+    let modules = routes::MODULES.iter().map(|(_, init_routes)| *init_routes).collect();
+    let mut hosts = vhost::VirtualHosts::new(app(data.clone(), modules, None).into_factory());
+    for site in &data.sites.sites {
+        let modules = site
+            .routes
+            .iter()
+            .filter_map(|module| routes::MODULES.iter().find(|(name, _)| name == module))
+            .map(|(_, init_routes)| *init_routes)
+            .collect();
+        hosts = hosts.site(site, app(data.clone(), modules, site.static_root.clone()).into_factory());
+    }
+    hosts
+}
+
+/// The middleware every host gets, around `modules`. What no route matches is served from
+/// `static_root`, or answered by [`routing::not_found`].
+#[rustfmt::skip]
+fn app(
+    data: AppData,
+    modules: Vec<routes::InitRoutes>,
+    static_root: Option<PathBuf>,
+) -> App<
+    impl ServiceFactory<
+        ServiceRequest,
+        Config = (),
+        Response = ServiceResponse<impl MessageBody>,
+        Error = Error,
+        InitError = (),
+    >,
+> {
+    let app = App::new()
+        .app_data(data.queue)
+        .app_data(data.health)
+        .app_data(data.templates)
+        .app_data(data.limits)
+        .app_data(data.sites)
+        .app_data(data.audit)
+        .app_data(data.flags)
+        .app_data(data.idempotency)
+        .app_data(web::Data::new(routing_policy()))
+        .wrap(middleware::from_fn(tls::expose_client_cert))
+        .wrap(middleware::from_fn(protocol::enforce_limits))
+        .wrap(templates::error_pages())
+        .wrap(middleware::from_fn(i18n::negotiated))
+        .wrap(middleware::from_fn(idempotency::replay))
+        .wrap(middleware::from_fn(flags::gate))
+        .wrap(Logger::default())
+        .wrap(middleware::from_fn(audit::record))
+        .wrap(middleware::from_fn(routing::normalize_path));  // url-dispatch/path-normalization
+    let app = match static_root {
+        Some(root) => app.default_service(
+            Files::new("/", root).index_file("index.html").default_handler(web::to(routing::not_found)),
+        ),
+        None => app.default_service(web::to(routing::not_found)),
+    };
+    modules.into_iter().fold(app, |app, init_routes| app.configure(init_routes))
+}
@@ -1,10 +1,19 @@
-use actix_web::{http, middleware, web, App, HttpServer};
-use actix_web::middleware::Logger;
-use openssl::ssl::{SslAcceptor, SslFiletype, SslMethod};
+use actix_web::{http, rt, web, HttpServer};
+use actix_example::{audit, create_app, flags, health, idempotency, jobs, protocol, routes, templates, tls, vhost, AppData};
+use openssl::ssl::SslAcceptorBuilder;
 
+use std::str::FromStr;
 use std::time::Duration;
 
-mod routes;
+const CERT_FILE: &str = "cert.pem";
+const KEY_FILE: &str = "key.pem";
+const STATIC_DIR: &str = "static";
+const TEMPLATE_DIR: &str = "templates";
+const SITES_FILE: &str = "sites.toml";
+const AUDIT_FILE: &str = "audit.jsonl";
+const FLAGS_FILE: &str = "flags.toml";
+/// How long `/readyz` reports not-ready before the server stops, for load balancers to notice.
+const DRAIN_PERIOD: Duration = Duration::from_secs(10);
 
 #[rustfmt::skip]
 #[actix_web::main]
@@ -13,33 +22,123 @@ async fn main() -> std::io::Result<()> {
     std::env::set_var("RUST_BACKTRACE", "1");
     env_logger::init();
 
-    let mut builder = SslAcceptor::mozilla_intermediate(SslMethod::tls()).unwrap();
-    builder
-        .set_private_key_file("key.pem", SslFiletype::PEM)
-        .unwrap();
-    builder.set_certificate_chain_file("cert.pem").unwrap();
-
-    let app = move || {
-        App::new()
-            .wrap(Logger::default())
-            .wrap(middleware::NormalizePath::trim())  // url-dispatch/path-normalization
-            .default_service(web::route().method(http::Method::GET))  // url-dispatch/path-normalization
-            .configure(routes::application_routes)
-            .configure(routes::server_routes)
-            .configure(routes::extractor_routes)
-            .configure(routes::handler_routes)
-            .configure(routes::error_routes)
-            .configure(routes::url_dispatch_routes)
-            .configure(routes::testing_routes)
+    let protocol = protocol::ProtocolConfig::default();
+    // Optional mTLS: trust client certificates issued by the CA bundle in CLIENT_CA_FILE.
+    let client_auth = match std::env::var("CLIENT_CA_FILE") {
+        Ok(ca_file) => Some((tls::load_ca_bundle(&ca_file)?, env_parse("CLIENT_AUTH")?.unwrap_or(tls::ClientAuth::Optional))),
+        Err(_) => None,
     };
+    let client_auth = |builder: &mut SslAcceptorBuilder| match &client_auth {
+        Some((cas, mode)) => tls::client_auth(builder, cas, *mode),
+        None => Ok(()),
+    };
+    let mut builder = tls::acceptor(CERT_FILE, KEY_FILE).unwrap();
+    client_auth(&mut builder).unwrap();
+    let sites_file = std::env::var("SITES_FILE").unwrap_or_else(|_| SITES_FILE.to_owned());
+    let sites = web::Data::new(vhost::Sites::load(&sites_file).unwrap());
+    vhost::sni(&mut builder, &sites, |site| {
+        client_auth(site)?;
+        protocol.select_alpn(site);
+        Ok(())
+    }).unwrap();
+
+    let jobs_url = std::env::var("JOBS_DATABASE_URL")
+        .unwrap_or_else(|_| "sqlite://jobs.db?mode=rwc".to_owned());
+    let queue = jobs::JobQueue::connect(&jobs_url).await.unwrap();
+    let mut registry = jobs::Registry::default();
+    registry.register::<routes::jobs::EchoJob>();
+    // Idempotency keys live next to the jobs, so every instance sharing the database sees them.
+    let idempotency_keys = idempotency::SqliteStore::new(queue.connection().clone()).await.unwrap();
+    let workers = jobs::WorkerPool::start(queue.clone(), registry, jobs::WorkerConfig::default());
 
-    let _one   = HttpServer::new(app).keep_alive(Duration::from_secs(75));
-    let _two   = HttpServer::new(app).keep_alive(http::KeepAlive::Os);
-    let _three = HttpServer::new(app).keep_alive(None);
+    let mut health = health::Health::default()
+        .with_check(health::DatabaseCheck { db: queue.connection().clone() })
+        .with_check(health::DiskSpaceCheck { path: STATIC_DIR.into(), min_free: 64 * 1024 * 1024 });
+    // Off unless asked for: the sample certificate has long expired.
+    if let Some(min_days) = env_parse("CERT_MIN_DAYS")? {
+        health = health.with_check(health::CertExpiryCheck { path: CERT_FILE.into(), min_days });
+    }
+    let health = web::Data::new(health);
+    let drain = env_parse("SHUTDOWN_DRAIN_SECS")?.map_or(DRAIN_PERIOD, Duration::from_secs);
+    let audit_file = std::env::var("AUDIT_LOG_FILE").unwrap_or_else(|_| AUDIT_FILE.to_owned());
+    let audit = audit::AuditLog::open(audit::AuditConfig { path: audit_file.into(), ..Default::default() })?;
+    let flags_file = std::env::var("FLAGS_FILE").unwrap_or_else(|_| FLAGS_FILE.to_owned());
+    let flags = flags::FlagStore::open(flags_file, Duration::from_secs(2)).unwrap();
+
+    let data = AppData {
+        queue: web::Data::new(queue),
+        health: health.clone(),
+        // Pick up template edits without a restart in debug builds.
+        templates: web::Data::new(templates::Templates::new(TEMPLATE_DIR, cfg!(debug_assertions)).unwrap()),
+        limits: web::Data::new(protocol.limits()),
+        sites: sites.clone(),
+        audit: web::Data::new(audit),
+        flags: web::Data::new(flags),
+        idempotency: web::Data::new(idempotency::Idempotency::new(idempotency_keys)),
+    };
+    let app = move || create_app(data.clone());
 
-    HttpServer::new(app)
+    let _one   = HttpServer::new(app.clone()).keep_alive(Duration::from_secs(75));
+    let _two   = HttpServer::new(app.clone()).keep_alive(http::KeepAlive::Os);
+    let _three = HttpServer::new(app.clone()).keep_alive(None);
+
+    let mut server = HttpServer::new(app)
         .workers(1)
-        .bind_openssl(("127.0.0.1", 8080), builder)?
-        .run()
-        .await
+        .disable_signals()
+        .on_connect(|conn, ext| {
+            protocol::tag_connection(conn, ext);
+            tls::peer_certificate(conn, ext);
+        });
+    if let Some(size) = protocol.h2_initial_window_size {
+        server = server.h2_initial_window_size(size);
+    }
+    if let Some(size) = protocol.h2_initial_connection_window_size {
+        server = server.h2_initial_connection_window_size(size);
+    }
+    // h2 and http/1.1 over TLS via ALPN; h2c with prior knowledge on the plain listener.
+    server = server.bind_openssl(("127.0.0.1", 8080), builder)?;
+    server = if protocol.h2c {
+        server.bind_auto_h2c(("127.0.0.1", 8081))?
+    } else {
+        server.bind(("127.0.0.1", 8081))?
+    };
+    let server = server.run();
+
+    // Report not-ready first so load balancers drain us, then stop gracefully.
+    let handle = server.handle();
+    rt::spawn(async move {
+        shutdown_signal().await;
+        health.set_ready(false);
+        rt::time::sleep(drain).await;
+        handle.stop(true).await;
+    });
+
+    server.await?;
+    workers.stop().await;
+    Ok(())
+}
+
+/// The value of the environment variable `name`, if set.
+fn env_parse<T: FromStr>(name: &str) -> std::io::Result<Option<T>>
+where
+    T::Err: std::fmt::Display,
+{
+    let Ok(value) = std::env::var(name) else {
+        return Ok(None);
+    };
+    value.parse().map(Some).map_err(|err| {
+        std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("invalid {}={:?}: {}", name, value, err))
+    })
+}
+
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use rt::signal::unix::{signal, SignalKind};
+
+        let mut term = signal(SignalKind::terminate()).unwrap();
+        futures::future::select(Box::pin(rt::signal::ctrl_c()), Box::pin(term.recv())).await;
+    }
+    #[cfg(not(unix))]
+    let _ = rt::signal::ctrl_c().await;
 }
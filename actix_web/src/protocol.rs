@@ -0,0 +1,393 @@
+use actix_web::body::{BodySize, EitherBody, MessageBody};
+use actix_web::dev::{Extensions, ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::{http, web, Error, HttpResponse};
+use openssl::ssl::{select_next_proto, AlpnError, SslAcceptorBuilder};
+
+use std::any::Any;
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+/// HTTP protocol settings for the server's listeners.
+///
+/// TLS listeners negotiate `h2` and `http/1.1` through ALPN (set up by `bind_openssl`).
+/// Plain-text listeners speak HTTP/1.1 and, when `h2c` is set, HTTP/2 with prior knowledge.
+///
+/// The `max_*` fields are not HTTP/2 SETTINGS: the server advertises the h2 defaults and
+/// parses every request in full, then [`enforce_limits`] turns away the ones over them.
+#[derive(Clone, Debug)]
+pub struct ProtocolConfig {
+    pub h2c: bool,
+    pub h2_initial_window_size: Option<u32>,
+    pub h2_initial_connection_window_size: Option<u32>,
+    /// Requests the app handles at the same time for one connection; more get a 503.
+    pub max_requests_per_connection: usize,
+    /// Request headers the app accepts; more get a 431.
+    pub max_header_count: usize,
+    /// Total size of request header names and values the app accepts; more get a 431.
+    pub max_header_bytes: usize,
+}
+
+impl Default for ProtocolConfig {
+    fn default() -> Self {
+        ProtocolConfig {
+            h2c: true,
+            h2_initial_window_size: None,
+            h2_initial_connection_window_size: None,
+            max_requests_per_connection: 100,
+            max_header_count: 64,
+            max_header_bytes: 16 * 1024,
+        }
+    }
+}
+
+/// Protocols TLS listeners offer through ALPN, most preferred first, in wire format.
+const ALPN_PROTOCOLS: &[u8] = b"\x02h2\x08http/1.1";
+
+impl ProtocolConfig {
+    /// Answers ALPN on `builder` as `bind_openssl` does for the listener's own context.
+    /// Contexts picked later, such as a site's through SNI, answer it themselves.
+    pub fn select_alpn(&self, builder: &mut SslAcceptorBuilder) {
+        builder.set_alpn_select_callback(|_, client| {
+            select_next_proto(ALPN_PROTOCOLS, client).ok_or(AlpnError::NOACK)
+        });
+    }
+
+    pub fn limits(&self) -> Limits {
+        Limits {
+            max_requests_per_connection: self.max_requests_per_connection,
+            max_header_count: self.max_header_count,
+            max_header_bytes: self.max_header_bytes,
+            in_flight: Arc::default(),
+        }
+    }
+}
+
+/// Identifies the connection a request arrived on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ConnectionId(pub u64);
+
+/// `HttpServer::on_connect` callback giving every connection a [`ConnectionId`].
+pub fn tag_connection(_: &dyn Any, ext: &mut Extensions) {
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+    ext.insert(ConnectionId(NEXT.fetch_add(1, Ordering::Relaxed)));
+}
+
+/// Limits enforced by [`enforce_limits`], shared by all workers.
+#[derive(Clone)]
+pub struct Limits {
+    max_requests_per_connection: usize,
+    max_header_count: usize,
+    max_header_bytes: usize,
+    in_flight: Arc<Mutex<HashMap<ConnectionId, usize>>>,
+}
+
+impl Limits {
+    fn acquire(&self, conn: ConnectionId) -> Option<InFlightGuard> {
+        let mut in_flight = self.in_flight.lock().unwrap();
+        let count = in_flight.entry(conn).or_insert(0);
+        if *count >= self.max_requests_per_connection {
+            return None;
+        }
+        *count += 1;
+
+        Some(InFlightGuard {
+            conn,
+            in_flight: self.in_flight.clone(),
+        })
+    }
+}
+
+struct InFlightGuard {
+    conn: ConnectionId,
+    in_flight: Arc<Mutex<HashMap<ConnectionId, usize>>>,
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        let mut in_flight = self.in_flight.lock().unwrap();
+        if let Some(count) = in_flight.get_mut(&self.conn) {
+            *count -= 1;
+            if *count == 0 {
+                in_flight.remove(&self.conn);
+            }
+        }
+    }
+}
+
+/// A response body that keeps its request counted against the connection until the
+/// body is sent or dropped.
+struct Guarded<B> {
+    body: Pin<Box<B>>,
+    _guard: Option<InFlightGuard>,
+}
+
+impl<B: MessageBody> MessageBody for Guarded<B> {
+    type Error = B::Error;
+
+    fn size(&self) -> BodySize {
+        self.body.size()
+    }
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Result<web::Bytes, Self::Error>>> {
+        self.get_mut().body.as_mut().poll_next(cx)
+    }
+}
+
+/// Rejects requests over the header limits with 431 and requests beyond the
+/// per-connection limit with 503, once the protocol layer has accepted them. A request
+/// counts until its whole body is sent, so long streams hold their place.
+/// Needs `web::Data<Limits>` in the app.
+pub async fn enforce_limits(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, Error> {
+    let Some(limits) = req.app_data::<web::Data<Limits>>().cloned() else {
+        let res = next.call(req).await?;
+        return Ok(res.map_body(|_, body| Guarded { body: Box::pin(body), _guard: None }).map_into_left_body());
+    };
+
+    let headers = req.headers();
+    let header_bytes: usize = headers
+        .iter()
+        .map(|(name, value)| name.as_str().len() + value.len())
+        .sum();
+    if headers.len() > limits.max_header_count || header_bytes > limits.max_header_bytes {
+        let res = HttpResponse::build(http::StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE).finish();
+        return Ok(req.into_response(res).map_into_right_body());
+    }
+
+    let conn = req.conn_data::<ConnectionId>().copied();
+    let guard = match conn {
+        Some(conn) => match limits.acquire(conn) {
+            Some(guard) => Some(guard),
+            None => {
+                let res = HttpResponse::ServiceUnavailable()
+                    .insert_header((http::header::RETRY_AFTER, "1"))
+                    .finish();
+                return Ok(req.into_response(res).map_into_right_body());
+            }
+        },
+        None => None,
+    };
+
+    let res = next.call(req).await?;
+    Ok(res.map_body(|_, body| Guarded { body: Box::pin(body), _guard: guard }).map_into_left_body())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::middleware::from_fn;
+    use actix_web::{rt, App, HttpRequest, HttpServer};
+    use futures::stream::{self, StreamExt};
+    use openssl::ssl::{SslAcceptor, SslConnector, SslMethod, SslVerifyMode};
+
+    use std::net::{SocketAddr, TcpListener, TcpStream};
+    use std::time::Duration;
+
+    use crate::tls::testing::{self_signed, CertifiedKey};
+
+    async fn version(req: HttpRequest) -> String {
+        format!("{:?}", req.version())
+    }
+
+    async fn slow() -> &'static str {
+        rt::time::sleep(Duration::from_millis(300)).await;
+        "done"
+    }
+
+    /// Sends its head at once and the rest of its body later.
+    async fn streamed() -> HttpResponse {
+        let chunks = stream::iter(0..2).then(|i| async move {
+            if i > 0 {
+                rt::time::sleep(Duration::from_millis(300)).await;
+            }
+            Ok::<_, Error>(web::Bytes::from("chunk\n"))
+        });
+        HttpResponse::Ok().streaming(chunks)
+    }
+
+    fn app(limits: Limits) -> App<
+        impl actix_web::dev::ServiceFactory<
+            ServiceRequest,
+            Config = (),
+            Response = ServiceResponse<impl MessageBody>,
+            Error = Error,
+            InitError = (),
+        >,
+    > {
+        App::new()
+            .app_data(web::Data::new(limits))
+            .wrap(from_fn(enforce_limits))
+            .route("/version", web::get().to(version))
+            .route("/slow", web::get().to(slow))
+            .route("/streamed", web::get().to(streamed))
+    }
+
+    fn serve(config: &ProtocolConfig, tls: Option<&CertifiedKey>) -> SocketAddr {
+        let limits = config.limits();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = HttpServer::new(move || app(limits.clone()))
+            .workers(1)
+            .disable_signals()
+            .on_connect(tag_connection);
+        let server = match tls {
+            Some(tls) => {
+                let mut builder = SslAcceptor::mozilla_intermediate(SslMethod::tls()).unwrap();
+                builder.set_private_key(&tls.key).unwrap();
+                builder.set_certificate(&tls.cert).unwrap();
+                server.listen_openssl(listener, builder).unwrap()
+            }
+            None if config.h2c => server.listen_auto_h2c(listener).unwrap(),
+            None => server.listen(listener).unwrap(),
+        };
+        rt::spawn(server.run());
+        addr
+    }
+
+    fn client(tls: &CertifiedKey, addr: SocketAddr) -> reqwest::ClientBuilder {
+        reqwest::Client::builder()
+            .add_root_certificate(reqwest::Certificate::from_pem(&tls.cert.to_pem().unwrap()).unwrap())
+            .resolve("localhost", addr)
+    }
+
+    #[actix_web::test]
+    async fn test_tls_alpn() {
+        let tls = self_signed("localhost");
+        let addr = serve(&ProtocolConfig::default(), Some(&tls));
+
+        for (offered, selected) in [
+            (&b"\x02h2\x08http/1.1"[..], &b"h2"[..]),
+            (&b"\x08http/1.1"[..], &b"http/1.1"[..]),
+        ] {
+            let offered = offered.to_vec();
+            let selected_alpn = web::block(move || {
+                let mut connector = SslConnector::builder(SslMethod::tls()).unwrap();
+                connector.set_verify(SslVerifyMode::NONE);
+                connector.set_alpn_protos(&offered).unwrap();
+                let stream = TcpStream::connect(addr).unwrap();
+                let ssl = connector.build().connect("localhost", stream).unwrap();
+                ssl.ssl().selected_alpn_protocol().map(<[u8]>::to_vec)
+            })
+            .await
+            .unwrap();
+            assert_eq!(selected_alpn.as_deref(), Some(selected));
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_tls_versions() {
+        let tls = self_signed("localhost");
+        let addr = serve(&ProtocolConfig::default(), Some(&tls));
+        let url = format!("https://localhost:{}/version", addr.port());
+
+        let res = client(&tls, addr).build().unwrap().get(&url).send().await.unwrap();
+        assert_eq!(res.version(), http::Version::HTTP_2);
+        assert_eq!(res.text().await.unwrap(), "HTTP/2.0");
+
+        let res = client(&tls, addr).http1_only().build().unwrap().get(&url).send().await.unwrap();
+        assert_eq!(res.version(), http::Version::HTTP_11);
+        assert_eq!(res.text().await.unwrap(), "HTTP/1.1");
+    }
+
+    #[actix_web::test]
+    async fn test_plain_h2c() {
+        let addr = serve(&ProtocolConfig::default(), None);
+        let url = format!("http://{}/version", addr);
+
+        let res = reqwest::Client::builder()
+            .http2_prior_knowledge()
+            .build()
+            .unwrap()
+            .get(&url)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.version(), http::Version::HTTP_2);
+
+        let res = reqwest::get(&url).await.unwrap();
+        assert_eq!(res.version(), http::Version::HTTP_11);
+
+        // Without h2c the listener only understands HTTP/1.
+        let config = ProtocolConfig {
+            h2c: false,
+            ..Default::default()
+        };
+        let addr = serve(&config, None);
+        let res = reqwest::Client::builder()
+            .http2_prior_knowledge()
+            .build()
+            .unwrap()
+            .get(format!("http://{}/version", addr))
+            .send()
+            .await;
+        assert!(res.is_err());
+    }
+
+    #[actix_web::test]
+    async fn test_header_limits() {
+        let config = ProtocolConfig {
+            max_header_count: 8,
+            max_header_bytes: 1024,
+            ..Default::default()
+        };
+        let addr = serve(&config, None);
+        let url = format!("http://{}/version", addr);
+        let client = reqwest::Client::new();
+
+        let res = client.get(&url).send().await.unwrap();
+        assert_eq!(res.status(), http::StatusCode::OK);
+
+        let mut req = client.get(&url);
+        for i in 0..10 {
+            req = req.header(format!("x-header-{}", i), "value");
+        }
+        let res = req.send().await.unwrap();
+        assert_eq!(res.status(), http::StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE);
+
+        let res = client.get(&url).header("x-large", "a".repeat(2048)).send().await.unwrap();
+        assert_eq!(res.status(), http::StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE);
+    }
+
+    #[actix_web::test]
+    async fn test_max_requests_per_connection() {
+        let config = ProtocolConfig {
+            max_requests_per_connection: 1,
+            ..Default::default()
+        };
+        let tls = self_signed("localhost");
+        let addr = serve(&config, Some(&tls));
+        let url = format!("https://localhost:{}/slow", addr.port());
+
+        // Both requests are multiplexed over a single HTTP/2 connection.
+        let client = client(&tls, addr).build().unwrap();
+        let (first, second) = futures::join!(client.get(&url).send(), async {
+            rt::time::sleep(Duration::from_millis(100)).await;
+            client.get(&url).send().await
+        });
+        assert_eq!(first.unwrap().status(), http::StatusCode::OK);
+        assert_eq!(second.unwrap().status(), http::StatusCode::SERVICE_UNAVAILABLE);
+
+        let res = client.get(&url).send().await.unwrap();
+        assert_eq!(res.status(), http::StatusCode::OK);
+
+        // A response still streaming its body holds the connection's only place.
+        let url = format!("https://localhost:{}/streamed", addr.port());
+        let first = client.get(&url).send().await.unwrap();
+        assert_eq!(first.status(), http::StatusCode::OK);
+        let second = client.get(&url).send().await.unwrap();
+        assert_eq!(second.status(), http::StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(first.text().await.unwrap(), "chunk\nchunk\n");
+
+        // The place is given back once the body is dropped, right after its last chunk.
+        rt::time::sleep(Duration::from_millis(50)).await;
+        let res = client.get(&url).send().await.unwrap();
+        assert_eq!(res.status(), http::StatusCode::OK);
+    }
+}
@@ -1,30 +1,47 @@
-use actix_web::{get, web, guard, Responder, HttpResponse};
+use actix_web::{get, web, Responder, HttpResponse};
 use actix_web::post;
+use fluent::fluent_args;
+use serde::Serialize;
+use tera::Context;
 
 use std::sync::Mutex;
 
+use crate::i18n::Locale;
+use crate::templates::{TemplateError, Templates};
+
 pub struct AppStateWithCounter {
     pub app_name: String,
     pub counter: Mutex<i32>,
 }
 
+#[derive(Serialize)]
+struct User {
+    name: &'static str,
+}
+
+const USERS: [&str; 5] = ["Alice", "Bob", "Chris", "Dan", "Eve"];
+
 #[get("/")]
-async fn index(data: web::Data<AppStateWithCounter>) -> String {
-    let app_name = &data.app_name;
+async fn index(data: web::Data<AppStateWithCounter>, locale: Locale) -> String {
+    let app_name = data.app_name.as_str();
     let mut counter = data.counter.lock().unwrap();
     *counter += 1;
 
-    format!("Hello {app_name}, Request number: {counter}")
+    locale.format("hello-app", &fluent_args!["app_name" => app_name, "counter" => *counter])
 }
 
 #[get("/hello")]
-async fn hello() -> impl Responder {
-    HttpResponse::Ok().body("Hello world!")
+async fn hello(locale: Locale) -> impl Responder {
+    HttpResponse::Ok().body(locale.text("hello-world"))
 }
 
 #[get("/show")]
-async fn show_users() -> impl Responder {
-    HttpResponse::Ok().body("Alice, Bob, Chris, Dan, Eve")
+async fn show_users(templates: web::Data<Templates>, locale: Locale) -> Result<HttpResponse, TemplateError> {
+    let users: Vec<User> = USERS.iter().map(|&name| User { name }).collect();
+    let mut ctx = Context::new();
+    ctx.insert("users", &users);
+
+    templates.page("users/show.html", &locale, ctx)
 }
 
 #[post("/echo")]
@@ -32,12 +49,19 @@ async fn echo(req_body: String) -> impl Responder {
     HttpResponse::Ok().body(req_body)
 }
 
-async fn manual_hello() -> impl Responder {
-    HttpResponse::Ok().body("Hey there!")
+async fn manual_hello(locale: Locale) -> impl Responder {
+    HttpResponse::Ok().body(locale.text("hey-there"))
 }
 
-async fn app() -> impl Responder {
-    "Hello world!"
+async fn app(
+    templates: web::Data<Templates>,
+    data: web::Data<AppStateWithCounter>,
+    locale: Locale,
+) -> Result<HttpResponse, TemplateError> {
+    let mut ctx = Context::new();
+    ctx.insert("app_name", &data.app_name);
+
+    templates.page("app/index.html", &locale, ctx)
 }
 
 
@@ -51,16 +75,7 @@ pub fn init_routes(config: &mut web::ServiceConfig) {
     let app_scope = web::scope("/app")
         .route("/index.html", web::get().to(app));
 
-    let www_guard = web::scope("/")
-        .guard(guard::Header("Host", "www.rust-lang.org"))
-        .route("", web::to(|| async { HttpResponse::Ok().body("www") }));
-    let user_guard = web::scope("/")
-        .guard(guard::Header("Host", "users.rust-lang.org"))
-        .route("", web::to(|| async { HttpResponse::Ok().body("user") }));
-
     config.app_data(counter);
-    config.service(www_guard);
-    config.service(user_guard);
     config.service(index);
     config.service(hello);
     config.service(echo);
@@ -0,0 +1,17 @@
+use actix_web::{get, web, Error, HttpResponse};
+
+use super::internal::is_ops;
+use crate::audit::{AuditLog, AuditQuery};
+
+/// `?from=&to=` in milliseconds since the Unix epoch, `?route=` as registered, e.g. `/url-dispatch/user/{name}`.
+/// At most `?limit=` records, oldest first; page by passing the last `at` as the next `from`.
+#[get("/admin/audit", guard = "is_ops")]
+async fn query_audit(log: web::Data<AuditLog>, query: web::Query<AuditQuery>) -> Result<HttpResponse, Error> {
+    let log = log.into_inner();
+    let records = web::block(move || log.query(&query)).await??;
+    Ok(HttpResponse::Ok().json(records))
+}
+
+pub fn init_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(query_audit);
+}
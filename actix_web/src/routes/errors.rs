@@ -1,7 +1,10 @@
 use actix_web::{get, web, http, body, error, Result, HttpResponse};
 use actix_files::NamedFile;
+use fluent::fluent_args;
 use log::info;
 
+use crate::i18n::{Locale, Localized, LocalizedError};
+
 #[derive(Debug, derive_more::Display, derive_more::Error)]
 #[display(fmt = "my error: {}", name)]
 struct CustomError {
@@ -10,6 +13,12 @@ struct CustomError {
 
 impl error::ResponseError for CustomError {}
 
+impl LocalizedError for CustomError {
+    fn message(&self, locale: &Locale) -> String {
+        locale.format("error-custom", &fluent_args!["name" => self.name])
+    }
+}
+
 #[derive(Debug, derive_more::Display)]
 enum CustomErrorEnum {
     #[display(fmt = "internal error")]
@@ -36,36 +45,46 @@ impl error::ResponseError for CustomErrorEnum {
     }
 }
 
+impl LocalizedError for CustomErrorEnum {
+    fn message(&self, locale: &Locale) -> String {
+        match *self {
+            CustomErrorEnum::InternalError => locale.text("error-internal"),
+            CustomErrorEnum::BadClientData => locale.text("error-bad-request"),
+            CustomErrorEnum::Timeout => locale.text("error-timeout"),
+        }
+    }
+}
+
 #[get("/static-index")]
 async fn static_index() -> std::io::Result<NamedFile> {
     Ok(NamedFile::open("static/index.html")?)
 }
 
 #[get("/custom-error")]
-async fn custom_error() -> Result<&'static str, CustomError> {
-    Err(CustomError { name: "test" })
+async fn custom_error(locale: Locale) -> Result<&'static str, Localized<CustomError>> {
+    Err(locale.error(CustomError { name: "test" }))
 }
 
 #[get("/custom-error-enum")]
-async fn custom_error_enum() -> Result<&'static str, CustomErrorEnum> {
-    let internal_error = Err(CustomErrorEnum::InternalError)?;
-    let _bad_client_data = Err(CustomErrorEnum::BadClientData)?;
-    let _timeout = Err(CustomErrorEnum::Timeout)?;
+async fn custom_error_enum(locale: Locale) -> Result<&'static str, Localized<CustomErrorEnum>> {
+    let internal_error = Err(locale.error(CustomErrorEnum::InternalError))?;
+    let _bad_client_data = Err(locale.error(CustomErrorEnum::BadClientData))?;
+    let _timeout = Err(locale.error(CustomErrorEnum::Timeout))?;
 
     internal_error
 }
 
 #[get("/map-err")]
-async fn map_err() -> Result<&'static str> {
+async fn map_err(locale: Locale) -> Result<&'static str> {
     let result: Result<&'static str, CustomError> = Err(CustomError { name: "test error" });
-    Ok(result.map_err(|e| error::ErrorBadRequest(e.name))?)
+    Ok(result.map_err(|e| error::ErrorBadRequest(e.message(&locale)))?)
 }
 
 #[get("/err-logging")]
-async fn err_logging() -> Result<&'static str, CustomError> {
+async fn err_logging(locale: Locale) -> Result<&'static str, Localized<CustomError>> {
     let err = CustomError { name: "Error Logging" };
     info!("{}", err);
-    Err(err)
+    Err(locale.error(err))
 }
 
 pub fn init_routes(config: &mut web::ServiceConfig) {
@@ -1,15 +1,14 @@
 use actix_web::{get, post, web, error, Result, Responder, HttpRequest, HttpResponse};
+use fluent::fluent_args;
 use serde::Deserialize;
 
 use std::sync::Arc;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::cell::Cell;
 
-#[derive(Deserialize)]
-pub struct Extractors {
-    pub id: u32,
-    pub username: String,
-}
+use crate::i18n::Locale;
+
+pub use actix_example_api::{Extractors, FormData, JsonStruct, QueryStruct};
 
 #[derive(Deserialize)]
 pub struct PostInfo {
@@ -17,21 +16,6 @@ pub struct PostInfo {
     pub friend: String,
 }
 
-#[derive(Deserialize)]
-struct QueryStruct {
-    name: String,
-}
-
-#[derive(Deserialize)]
-struct JsonStruct {
-    name: String,
-}
-
-#[derive(Deserialize)]
-struct FormData {
-    username: String,
-}
-
 #[derive(Clone)]
 pub struct StateStruct {
     pub local_count: Cell<usize>,
@@ -45,41 +29,41 @@ async fn extractors(path: web::Path<(String, String)>, info: web::Json<Extractor
 }
 
 #[get("/posts/{post_id}/{friend}")]
-async fn post_friend(req: HttpRequest) -> Result<String> {
+async fn post_friend(req: HttpRequest, locale: Locale) -> Result<String> {
     let name: String = req.match_info().get("friend").unwrap().parse().unwrap();
     let postid: i32 = req.match_info().query("post_id").parse().unwrap();
 
-    Ok(format!("Welcome {}, post_id: {}", name, postid))
+    Ok(locale.format("welcome-post", &fluent_args!["name" => name, "post_id" => postid]))
 }
 
 #[get("/query")]
-async fn query(info: web::Query<QueryStruct>) -> String {
-    format!("Welcome {}", info.name)
+async fn query(info: web::Query<QueryStruct>, locale: Locale) -> String {
+    locale.format("welcome", &fluent_args!["name" => info.name.as_str()])
 }
 
 #[post("/json")]
-async fn json(info: web::Json<JsonStruct>) -> Result<String> {
-    Ok(format!("Welcome {}", info.name))
+async fn json(info: web::Json<JsonStruct>, locale: Locale) -> Result<String> {
+    Ok(locale.format("welcome", &fluent_args!["name" => info.name.as_str()]))
 }
 
 #[post("/form")]
-async fn form(form: web::Form<FormData>) -> Result<String> {
-    Ok(format!("Welcome {}", form.username))
+async fn form(form: web::Form<FormData>, locale: Locale) -> Result<String> {
+    Ok(locale.format("welcome", &fluent_args!["name" => form.username.as_str()]))
 }
 
 #[get("/count")]
-async fn show_count(data: web::Data<StateStruct>) -> impl Responder {
-    format!("count: {}", data.local_count.get())
+async fn show_count(data: web::Data<StateStruct>, locale: Locale) -> impl Responder {
+    locale.format("count", &fluent_args!["count" => data.local_count.get()])
 }
 
 #[get("/add-one")]
-async fn add_one(data: web::Data<StateStruct>) -> impl Responder {
+async fn add_one(data: web::Data<StateStruct>, locale: Locale) -> impl Responder {
     data.global_count.fetch_add(1, Ordering::Relaxed);
 
     let count = data.local_count.get();
     data.local_count.set(count + 1);
 
-    format!("Count: {}", data.local_count.get())
+    locale.format("count-added", &fluent_args!["count" => data.local_count.get()])
 }
 
 fn json_config() -> web::JsonConfig {
@@ -1,29 +1,17 @@
-use actix_web::{get, web, http, body, Result, Error, Either, Responder, HttpRequest, HttpResponse};
-use serde::Serialize;
+use actix_web::{get, web, Result, Error, Either, Responder, HttpRequest, HttpResponse};
 use futures::{future::ok, stream::once};
 
-#[derive(Serialize)]
-struct CustomType {
-    name: &'static str,
-}
-
-impl Responder for CustomType {
-    type Body = body::BoxBody;
-
-    fn respond_to(self, _req: &HttpRequest) -> HttpResponse<Self::Body> {
-        let body = serde_json::to_string(&self).unwrap();
+use crate::flags::Flags;
+use crate::i18n::Locale;
+use crate::streaming::{Format, JsonStream};
 
-        HttpResponse::Ok()
-        .content_type(http::header::ContentType::json())
-            .body(body)
-    }
-}
+pub use actix_example_api::CustomType;
 
-type RegisterResult = Either<HttpResponse, Result<&'static str, Error>>;
+type RegisterResult = Either<HttpResponse, Result<String, Error>>;
 
 #[get("/responder")]
-async fn responder(_req: HttpRequest) -> String {
-    "Hello World!".to_owned()
+async fn responder(_req: HttpRequest, locale: Locale) -> String {
+    locale.text("responder-hello")
 }
 
 #[get("/responder2")]
@@ -33,24 +21,22 @@ async fn responder_2(_req: HttpRequest) -> impl Responder {
 
 #[get("custom-type")]
 async fn custom_type() -> impl Responder {
-    CustomType { name: "ittokun" }
+    CustomType { name: "ittokun".to_owned() }
 }
 
 #[get("/stream")]
-async fn stream() -> HttpResponse {
-    let body = once(ok::<_, Error>(web::Bytes::from_static(b"test")));
+async fn stream(req: HttpRequest) -> impl Responder {
+    let items = once(ok::<_, Error>("test"));
 
-    HttpResponse::Ok()
-        .content_type("application/json")
-        .streaming(body)
+    JsonStream::new(Format::from_req(&req), items)
 }
 
 #[get("either")]
-async fn either() -> RegisterResult {
-    if true {
-        Either::Left(HttpResponse::BadRequest().body("Bad data"))
+async fn either(locale: Locale, flags: Flags) -> RegisterResult {
+    if flags.enabled("either-register") {
+        Either::Right(Ok(locale.text("register-hello")))
     } else {
-        Either::Right(Ok("Hello!"))
+        Either::Left(HttpResponse::BadRequest().body(locale.text("bad-data")))
     }
 }
 
@@ -0,0 +1,23 @@
+use actix_web::{get, web, HttpResponse};
+
+use crate::health::{Health, Status};
+
+#[get("/healthz")]
+async fn healthz() -> HttpResponse {
+    HttpResponse::Ok().json(serde_json::json!({ "status": Status::Ok }))
+}
+
+#[get("/readyz")]
+async fn readyz(health: web::Data<Health>) -> HttpResponse {
+    let report = health.report().await;
+
+    match report.status {
+        Status::Ok => HttpResponse::Ok().json(report),
+        _ => HttpResponse::ServiceUnavailable().json(report),
+    }
+}
+
+pub fn init_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(healthz);
+    cfg.service(readyz);
+}
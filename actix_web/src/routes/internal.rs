@@ -0,0 +1,28 @@
+use actix_web::guard::GuardContext;
+use actix_web::{get, web, HttpResponse};
+
+use crate::tls::{ClientCert, ClientIdentity};
+
+/// Client certificate identities let into `/internal` and the `/admin` routes.
+pub const OPS_IDENTITIES: &[&str] = &["ops.internal"];
+
+/// [`ClientIdentity`] for [`OPS_IDENTITIES`], as a function for `#[get(.., guard = "is_ops")]`.
+pub fn is_ops(ctx: &GuardContext<'_>) -> bool {
+    ctx.req_data()
+        .get::<ClientCert>()
+        .is_some_and(|cert| OPS_IDENTITIES.iter().any(|identity| cert.is(identity)))
+}
+
+#[get("/whoami")]
+async fn whoami(cert: ClientCert) -> HttpResponse {
+    HttpResponse::Ok().json(cert)
+}
+
+pub fn init_routes(cfg: &mut web::ServiceConfig) {
+    // Only reachable with a client certificate issued to one of the ops identities.
+    cfg.service(
+        web::scope("/internal")
+            .guard(ClientIdentity::new(OPS_IDENTITIES.iter().copied()))
+            .service(whoami),
+    );
+}
@@ -0,0 +1,74 @@
+use actix_web::{get, post, web, Error, HttpRequest, HttpResponse, Responder};
+use async_trait::async_trait;
+use futures::{StreamExt, TryStreamExt};
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use super::internal::is_ops;
+use crate::jobs::{Job, JobError, JobQueue, Status};
+use crate::streaming::{Format, JsonStream, Ndjson};
+
+#[derive(Serialize, Deserialize)]
+pub struct EchoJob {
+    pub body: String,
+}
+
+#[async_trait(?Send)]
+impl Job for EchoJob {
+    const KIND: &'static str = "echo";
+
+    async fn perform(self) -> Result<(), String> {
+        info!("echo job: {}", self.body);
+        Ok(())
+    }
+}
+
+#[derive(Deserialize)]
+struct ListQuery {
+    status: Option<Status>,
+}
+
+#[post("/jobs/echo")]
+async fn enqueue_echo(queue: web::Data<JobQueue>, req_body: String) -> Result<HttpResponse, JobError> {
+    let id = queue.enqueue(&EchoJob { body: req_body }).await?;
+    Ok(HttpResponse::Accepted().json(serde_json::json!({ "id": id })))
+}
+
+/// One echo job per NDJSON line: `{"body":"..."}`. Lines before an invalid one stay enqueued.
+#[post("/jobs/echo/bulk")]
+async fn enqueue_echo_bulk(queue: web::Data<JobQueue>, mut jobs: Ndjson<EchoJob>) -> Result<HttpResponse, Error> {
+    let mut ids = vec![];
+    while let Some(job) = jobs.next().await {
+        ids.push(queue.enqueue(&job?).await?);
+    }
+    Ok(HttpResponse::Accepted().json(serde_json::json!({ "ids": ids })))
+}
+
+#[get("/admin/jobs", guard = "is_ops")]
+async fn list_jobs(queue: web::Data<JobQueue>, query: web::Query<ListQuery>) -> Result<HttpResponse, JobError> {
+    let jobs = queue.list(query.status).await?;
+    Ok(HttpResponse::Ok().json(jobs))
+}
+
+/// All jobs as a JSON array, or NDJSON with `Accept: application/x-ndjson`, without loading them at once.
+#[get("/admin/jobs/export", guard = "is_ops")]
+async fn export_jobs(req: HttpRequest, queue: web::Data<JobQueue>, query: web::Query<ListQuery>) -> impl Responder {
+    let jobs = queue.stream(query.status, 500).map_err(JobError::Db);
+    JsonStream::new(Format::from_req(&req), jobs)
+}
+
+#[post("/admin/jobs/{id}/retry", guard = "is_ops")]
+async fn retry_job(queue: web::Data<JobQueue>, path: web::Path<(i32,)>) -> Result<HttpResponse, JobError> {
+    match queue.retry(path.into_inner().0).await? {
+        Some(job) => Ok(HttpResponse::Ok().json(job)),
+        None => Ok(HttpResponse::NotFound().finish()),
+    }
+}
+
+pub fn init_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(enqueue_echo);
+    cfg.service(enqueue_echo_bulk);
+    cfg.service(list_jobs);
+    cfg.service(export_jobs);
+    cfg.service(retry_job);
+}
@@ -2,9 +2,16 @@ pub mod application;
 pub mod server;
 pub mod extractors;
 pub mod handlers;
+// The tutorial examples show each way of writing things, needless or not.
+#[allow(clippy::needless_question_mark, clippy::let_unit_value)]
 pub mod errors;
+#[allow(unused_imports)]
 pub mod url_dispatch;
 pub mod testing;
+pub mod jobs;
+pub mod health;
+pub mod internal;
+pub mod audit;
 
 pub use application::init_routes as application_routes;
 pub use server::init_routes as server_routes;
@@ -13,3 +20,24 @@ pub use handlers::init_routes as handler_routes;
 pub use errors::init_routes as error_routes;
 pub use url_dispatch::init_routes as url_dispatch_routes;
 pub use testing::init_routes as testing_routes;
+pub use jobs::init_routes as job_routes;
+pub use health::init_routes as health_routes;
+pub use internal::init_routes as internal_routes;
+pub use audit::init_routes as audit_routes;
+
+pub type InitRoutes = fn(&mut actix_web::web::ServiceConfig);
+
+/// Route modules by name, for sites that mount a subset of them.
+pub const MODULES: &[(&str, InitRoutes)] = &[
+    ("application", application_routes),
+    ("server", server_routes),
+    ("extractors", extractor_routes),
+    ("handlers", handler_routes),
+    ("errors", error_routes),
+    ("url_dispatch", url_dispatch_routes),
+    ("testing", testing_routes),
+    ("jobs", job_routes),
+    ("health", health_routes),
+    ("internal", internal_routes),
+    ("audit", audit_routes),
+];
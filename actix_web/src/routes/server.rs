@@ -2,10 +2,12 @@ use actix_web::{get, http, web, Responder, HttpResponse};
 
 use std::time::Duration;
 
+use crate::i18n::Locale;
+
 #[get("/sleep")]
-async fn sleep() -> impl Responder {
+async fn sleep(locale: Locale) -> impl Responder {
     tokio::time::sleep(Duration::from_secs(5)).await;
-    "response"
+    locale.text("sleep-response")
 }
 
 #[get("/quit")]
@@ -1,16 +1,16 @@
 use actix_web::{get, web, http, Error, HttpRequest, HttpResponse};
-use serde::{Serialize, Deserialize};
+use fluent::fluent_args;
 use futures::stream;
 
 use std::task::Poll;
 
-#[derive(Serialize, Deserialize)]
-pub struct AppState {
-    pub counter: i32,
-}
+use crate::i18n::Locale;
+
+pub use actix_example_api::AppState;
 
 async fn index(req: HttpRequest) -> HttpResponse {
-    HttpResponse::Ok().body(format!("hello: {}", req.path()))
+    let locale = Locale::from_req(&req);
+    HttpResponse::Ok().body(locale.format("testing-hello", &fluent_args!["path" => req.path()]))
 }
 
 #[get("testing/app-data")]
@@ -1,43 +1,47 @@
 use actix_web::{get, guard, http, web::{self, service}, HttpRequest, HttpResponse};
-use serde::Deserialize;
+use fluent::fluent_args;
 
-#[derive(Deserialize)]
-struct PathInfo {
-    id: u32,
-    username: String,
-}
+use crate::i18n::Locale;
+
+pub use actix_example_api::PathInfo;
 
-async fn index() -> HttpResponse {
-    HttpResponse::Ok().body("Hello")
+async fn index(locale: Locale) -> HttpResponse {
+    HttpResponse::Ok().body(locale.text("url-dispatch-hello"))
 }
 
 #[get("/show")]
-async fn show_users() -> HttpResponse {
-    HttpResponse::Ok().body("Show users")
+async fn show_users(locale: Locale) -> HttpResponse {
+    HttpResponse::Ok().body(locale.text("url-dispatch-show-users"))
 }
 
 #[get("/show/{id}")]
-async fn user_detail(path: web::Path<(u32,)>) -> HttpResponse {
-    HttpResponse::Ok().body(format!("User detail: {}", path.into_inner().0))
+async fn user_detail(path: web::Path<(u32,)>, locale: Locale) -> HttpResponse {
+    HttpResponse::Ok().body(locale.format("user-detail", &fluent_args!["id" => path.into_inner().0]))
 }
 
 #[get("/match/{v1}/{v2}")]
-async fn match_info(req: HttpRequest) -> HttpResponse {
+async fn match_info(req: HttpRequest, locale: Locale) -> HttpResponse {
     let v1: u8 = req.match_info().get("v1").unwrap().parse().unwrap();
     let v2: u8 = req.match_info().query("v2").parse().unwrap();
     let (v3, v4): (u8, u8) = req.match_info().load().unwrap();
-    HttpResponse::Ok().body(format!("Values {} {} {} {}", v1, v2, v3, v4))
+    HttpResponse::Ok().body(locale.format(
+        "match-values",
+        &fluent_args!["v1" => v1, "v2" => v2, "v3" => v3, "v4" => v4],
+    ))
 }
 
 #[get("/path/{username}/{id}")]
-async fn path_info(info: web::Path<(String, u32)>) -> HttpResponse {
+async fn path_info(info: web::Path<(String, u32)>, locale: Locale) -> HttpResponse {
     let info = info.into_inner();
-    HttpResponse::Ok().body(format!("Welcome {}! id: {}", info.0, info.1))
+    HttpResponse::Ok().body(locale.format("welcome-id", &fluent_args!["username" => info.0, "id" => info.1]))
 }
 
 #[get("/v2/path/{username}/{id}")]
-async fn path_info_v2(info: web::Path<PathInfo>) -> HttpResponse {
-    HttpResponse::Ok().body(format!("Welcome {}! id: {}", info.username, info.id))
+async fn path_info_v2(info: web::Path<PathInfo>, locale: Locale) -> HttpResponse {
+    HttpResponse::Ok().body(locale.format(
+        "welcome-id",
+        &fluent_args!["username" => info.username.as_str(), "id" => info.id],
+    ))
 }
 
 #[get("/generate-resource-url")]
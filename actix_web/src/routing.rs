@@ -0,0 +1,275 @@
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{ResourceDef, ServiceRequest, ServiceResponse};
+use actix_web::introspection::{IntrospectionNode, IntrospectionTree, ResourceType};
+use actix_web::middleware::Next;
+use actix_web::{http, web, Error, HttpRequest, HttpResponse};
+
+/// What to do with a trailing slash (and repeated slashes) in the request path.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SlashPolicy {
+    /// Serve `/a/` as `/a`.
+    Trim,
+    /// Serve `/a` as `/a/`.
+    Append,
+    /// Answer `/a/` with a redirect to `/a` when `/a` is registered for the method.
+    /// Use 301 for browsers, 308 when the method and body must be kept.
+    Redirect(http::StatusCode),
+    /// Only exact paths match.
+    Strict,
+}
+
+/// Slash handling for the whole app, overridden by the longest matching scope prefix.
+#[derive(Clone, Debug)]
+pub struct RoutingPolicy {
+    default: SlashPolicy,
+    scopes: Vec<(String, SlashPolicy)>,
+}
+
+impl RoutingPolicy {
+    pub fn new(default: SlashPolicy) -> Self {
+        RoutingPolicy {
+            default,
+            scopes: vec![],
+        }
+    }
+
+    pub fn scope(mut self, prefix: &str, policy: SlashPolicy) -> Self {
+        self.scopes.push((prefix.trim_end_matches('/').to_owned(), policy));
+        self
+    }
+
+    pub fn policy_for(&self, path: &str) -> SlashPolicy {
+        self.scopes
+            .iter()
+            .filter(|(prefix, _)| {
+                path.strip_prefix(prefix.as_str())
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+            })
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map_or(self.default, |(_, policy)| *policy)
+    }
+}
+
+impl Default for RoutingPolicy {
+    fn default() -> Self {
+        RoutingPolicy::new(SlashPolicy::Trim)
+    }
+}
+
+/// `/a//b/` with repeated slashes merged and the trailing one removed.
+fn canonical(path: &str) -> String {
+    let segments: Vec<_> = path.split('/').filter(|segment| !segment.is_empty()).collect();
+    format!("/{}", segments.join("/"))
+}
+
+fn with_query(path: &str, query: &str) -> String {
+    if query.is_empty() {
+        path.to_owned()
+    } else {
+        format!("{}?{}", path, query)
+    }
+}
+
+/// Pattern of a resource registered for `method` that matches `path`, guards aside.
+/// Unlike [`HttpRequest::match_pattern`], this does not need routing to have run.
+pub fn registered_pattern(req: &HttpRequest, method: &http::Method, path: &str) -> Option<String> {
+    fn find(node: &IntrospectionNode, method: &http::Method, path: &str) -> Option<String> {
+        let matches = matches!(node.kind, ResourceType::Resource)
+            && (node.methods.is_empty() || node.methods.contains(method))
+            && ResourceDef::new(node.full_path.as_str()).is_match(path);
+        if matches {
+            return Some(node.full_path.clone());
+        }
+        node.children.iter().find_map(|child| find(child, method, path))
+    }
+
+    find(&req.app_data::<web::Data<IntrospectionTree>>()?.root, method, path)
+}
+
+/// Applies the [`RoutingPolicy`] in `web::Data` before routing. Without one, paths are left alone.
+pub async fn normalize_path(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, Error> {
+    let Some(policy) = req.app_data::<web::Data<RoutingPolicy>>().cloned() else {
+        return Ok(next.call(req).await?.map_into_left_body());
+    };
+
+    let path = req.path();
+    let canonical = canonical(path);
+    let target = match policy.policy_for(&canonical) {
+        SlashPolicy::Strict => None,
+        SlashPolicy::Trim => Some(canonical),
+        SlashPolicy::Append if canonical == "/" => Some(canonical),
+        SlashPolicy::Append => Some(format!("{}/", canonical)),
+        SlashPolicy::Redirect(status) => {
+            if canonical != path && registered_pattern(req.request(), req.method(), &canonical).is_some() {
+                let location = with_query(&canonical, req.query_string());
+                let res = HttpResponse::build(status)
+                    .insert_header((http::header::LOCATION, location))
+                    .finish();
+                return Ok(req.into_response(res).map_into_right_body());
+            }
+            None
+        }
+    };
+
+    if let Some(target) = target.filter(|target| target != path) {
+        let uri: http::Uri = with_query(&target, req.query_string()).parse().unwrap();
+        req.match_info_mut().get_mut().update(&uri);
+        req.head_mut().uri = uri;
+    }
+    Ok(next.call(req).await?.map_into_left_body())
+}
+
+/// Methods registered for the route pattern `req` matched, however its guards turned out.
+pub fn allowed_methods(req: &HttpRequest) -> Vec<http::Method> {
+    fn collect(node: &IntrospectionNode, pattern: &str, methods: &mut Vec<http::Method>) {
+        if matches!(node.kind, ResourceType::Resource) && node.full_path == pattern {
+            for method in &node.methods {
+                if !methods.contains(method) {
+                    methods.push(method.clone());
+                }
+            }
+        }
+        for child in &node.children {
+            collect(child, pattern, methods);
+        }
+    }
+
+    let mut methods = vec![];
+    if let (Some(pattern), Some(tree)) = (req.match_pattern(), req.app_data::<web::Data<IntrospectionTree>>()) {
+        collect(&tree.root, &pattern, &mut methods);
+    }
+    methods
+}
+
+/// Default service: 405 with `Allow` when the path exists for other methods, 404 otherwise.
+pub async fn not_found(req: HttpRequest) -> HttpResponse {
+    let allowed = allowed_methods(&req);
+
+    if allowed.is_empty() || allowed.contains(req.method()) {
+        HttpResponse::NotFound().finish()
+    } else {
+        HttpResponse::MethodNotAllowed()
+            .insert_header(http::header::Allow(allowed))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::middleware::from_fn;
+    use actix_web::{get, guard, post, test, App};
+
+    #[get("/items")]
+    async fn list_items() -> &'static str {
+        "items"
+    }
+
+    #[post("/items")]
+    async fn create_item() -> &'static str {
+        "created"
+    }
+
+    async fn show(req: HttpRequest) -> String {
+        req.path().to_owned()
+    }
+
+    fn policy() -> RoutingPolicy {
+        RoutingPolicy::new(SlashPolicy::Trim)
+            .scope("/strict", SlashPolicy::Strict)
+            .scope("/moved", SlashPolicy::Redirect(http::StatusCode::MOVED_PERMANENTLY))
+            .scope("/api/", SlashPolicy::Redirect(http::StatusCode::PERMANENT_REDIRECT))
+            .scope("/api/dirs", SlashPolicy::Append)
+    }
+
+    async fn call(method: http::Method, uri: &str) -> (http::StatusCode, Option<String>, String) {
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(policy()))
+                .wrap(from_fn(normalize_path))
+                .default_service(web::to(not_found))
+                .service(list_items)
+                .service(create_item)
+                .route("/strict/a", web::get().to(show))
+                .route("/moved/a", web::get().to(show))
+                .route("/api/a", web::to(show))
+                .route("/api/dirs/a/", web::get().to(show))
+                .service(web::resource("/guarded").guard(guard::Header("x-token", "1")).to(show)),
+        )
+        .await;
+        let req = test::TestRequest::default().method(method).uri(uri).to_request();
+        let res = test::call_service(&app, req).await;
+
+        let status = res.status();
+        // Redirects carry `Location`, 405s carry `Allow`; never both.
+        let location = [http::header::LOCATION, http::header::ALLOW]
+            .iter()
+            .find_map(|name| res.headers().get(name))
+            .map(|value| value.to_str().unwrap().to_owned());
+        let body = String::from_utf8(test::read_body(res).await.to_vec()).unwrap();
+        (status, location, body)
+    }
+
+    #[actix_web::test]
+    async fn test_policy_for() {
+        let policy = policy();
+        assert_eq!(policy.policy_for("/items"), SlashPolicy::Trim);
+        assert_eq!(policy.policy_for("/strict"), SlashPolicy::Strict);
+        assert_eq!(policy.policy_for("/strictly"), SlashPolicy::Trim);
+        assert_eq!(policy.policy_for("/api/dirs/a"), SlashPolicy::Append);
+        assert_eq!(
+            policy.policy_for("/api/a"),
+            SlashPolicy::Redirect(http::StatusCode::PERMANENT_REDIRECT)
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_slash_policies() {
+        use http::{Method, StatusCode};
+
+        assert_eq!(call(Method::GET, "/items/").await, (StatusCode::OK, None, "items".to_owned()));
+        assert_eq!(call(Method::GET, "//items").await.0, StatusCode::OK);
+
+        assert_eq!(call(Method::GET, "/strict/a").await.0, StatusCode::OK);
+        assert_eq!(call(Method::GET, "/strict/a/").await.0, StatusCode::NOT_FOUND);
+
+        assert_eq!(
+            call(Method::GET, "/moved/a/?x=1").await,
+            (StatusCode::MOVED_PERMANENTLY, Some("/moved/a?x=1".to_owned()), String::new())
+        );
+        assert_eq!(
+            call(Method::POST, "/api//a/").await,
+            (StatusCode::PERMANENT_REDIRECT, Some("/api/a".to_owned()), String::new())
+        );
+        // Nothing to redirect to, or nothing serving the method there.
+        assert_eq!(call(Method::GET, "/moved/b/").await.0, StatusCode::NOT_FOUND);
+        assert_eq!(call(Method::DELETE, "/moved/a/").await.0, StatusCode::NOT_FOUND);
+        assert_eq!(call(Method::GET, "/moved/").await.0, StatusCode::NOT_FOUND);
+
+        assert_eq!(
+            call(Method::GET, "/api/dirs/a").await,
+            (StatusCode::OK, None, "/api/dirs/a/".to_owned())
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_not_found_and_method_not_allowed() {
+        use http::{Method, StatusCode};
+
+        assert_eq!(call(Method::GET, "/missing").await.0, StatusCode::NOT_FOUND);
+        assert_eq!(call(Method::DELETE, "/missing").await.0, StatusCode::NOT_FOUND);
+        assert_eq!(
+            call(Method::DELETE, "/items").await,
+            (StatusCode::METHOD_NOT_ALLOWED, Some("GET, POST".to_owned()), String::new())
+        );
+        assert_eq!(
+            call(Method::POST, "/strict/a").await,
+            (StatusCode::METHOD_NOT_ALLOWED, Some("GET".to_owned()), String::new())
+        );
+        // The path exists but a non-method guard rejected the request.
+        assert_eq!(call(Method::GET, "/guarded").await.0, StatusCode::NOT_FOUND);
+    }
+}
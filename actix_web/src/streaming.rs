@@ -0,0 +1,373 @@
+use actix_web::{body, dev, error, http, web, Error, FromRequest, HttpRequest, HttpResponse, Responder};
+use futures::future::{ready, Ready};
+use futures::stream::{self, Stream, StreamExt};
+use serde::{de::DeserializeOwned, Serialize};
+
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+const NDJSON: &str = "application/x-ndjson";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// A single JSON array, `[{..},{..}]`.
+    Array,
+    /// One JSON document per line.
+    Ndjson,
+}
+
+impl Format {
+    /// NDJSON when the client accepts it, a JSON array otherwise.
+    pub fn from_req(req: &HttpRequest) -> Self {
+        let accepts_ndjson = req
+            .headers()
+            .get(http::header::ACCEPT)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.contains(NDJSON));
+
+        if accepts_ndjson {
+            Format::Ndjson
+        } else {
+            Format::Array
+        }
+    }
+
+    fn content_type(self) -> &'static str {
+        match self {
+            Format::Array => "application/json",
+            Format::Ndjson => NDJSON,
+        }
+    }
+}
+
+/// Serializes items as they are pulled from `items`.
+///
+/// The body is polled only when the connection can take more data, so a slow
+/// client holds the producer back instead of items piling up in memory.
+/// An error item aborts the response, since the status line is already sent.
+pub struct JsonStream<S> {
+    format: Format,
+    items: S,
+}
+
+impl<S> JsonStream<S> {
+    pub fn new(format: Format, items: S) -> Self {
+        JsonStream { format, items }
+    }
+
+    pub fn array(items: S) -> Self {
+        JsonStream::new(Format::Array, items)
+    }
+
+    pub fn ndjson(items: S) -> Self {
+        JsonStream::new(Format::Ndjson, items)
+    }
+}
+
+impl<S, T, E> JsonStream<S>
+where
+    S: Stream<Item = Result<T, E>> + 'static,
+    T: Serialize,
+    E: Into<Error>,
+{
+    fn into_body(self) -> impl Stream<Item = Result<web::Bytes, Error>> + 'static {
+        let format = self.format;
+        let items = self.items.enumerate().map(move |(i, item)| {
+            let mut buf = vec![];
+            if format == Format::Array && i > 0 {
+                buf.push(b',');
+            }
+            serde_json::to_writer(&mut buf, &item.map_err(Into::into)?)
+                .map_err(error::ErrorInternalServerError)?;
+            if format == Format::Ndjson {
+                buf.push(b'\n');
+            }
+            Ok(web::Bytes::from(buf))
+        });
+
+        let (open, close): (&[u8], &[u8]) = match format {
+            Format::Array => (b"[", b"]"),
+            Format::Ndjson => (b"", b""),
+        };
+        stream::iter([Ok(web::Bytes::from_static(open))])
+            .chain(items)
+            .chain(stream::iter([Ok(web::Bytes::from_static(close))]))
+            .filter(|chunk| ready(!matches!(chunk, Ok(bytes) if bytes.is_empty())))
+    }
+}
+
+impl<S, T, E> Responder for JsonStream<S>
+where
+    S: Stream<Item = Result<T, E>> + 'static,
+    T: Serialize,
+    E: Into<Error>,
+{
+    type Body = body::BoxBody;
+
+    fn respond_to(self, _req: &HttpRequest) -> HttpResponse<Self::Body> {
+        HttpResponse::Ok()
+            .content_type(self.format.content_type())
+            .streaming(self.into_body())
+    }
+}
+
+#[derive(Debug, derive_more::Display)]
+pub enum NdjsonError {
+    #[display(fmt = "expected content type {}", NDJSON)]
+    ContentType,
+    #[display(fmt = "line {} is longer than {} bytes", line, limit)]
+    LineTooLong { line: usize, limit: usize },
+    #[display(fmt = "line {}: {}", line, source)]
+    Deserialize { line: usize, source: serde_json::Error },
+    #[display(fmt = "{}", _0)]
+    Payload(error::PayloadError),
+}
+
+impl std::error::Error for NdjsonError {}
+
+impl error::ResponseError for NdjsonError {
+    fn status_code(&self) -> http::StatusCode {
+        match self {
+            NdjsonError::ContentType => http::StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            NdjsonError::LineTooLong { .. } => http::StatusCode::PAYLOAD_TOO_LARGE,
+            NdjsonError::Deserialize { .. } | NdjsonError::Payload(_) => http::StatusCode::BAD_REQUEST,
+        }
+    }
+}
+
+/// Limits for the [`Ndjson`] extractor, registered with `app_data`.
+#[derive(Clone, Copy, Debug)]
+pub struct NdjsonConfig {
+    pub line_limit: usize,
+}
+
+impl Default for NdjsonConfig {
+    fn default() -> Self {
+        NdjsonConfig { line_limit: 64 * 1024 }
+    }
+}
+
+/// Request body of newline-delimited JSON, read and parsed one line at a time.
+///
+/// ```ignore
+/// async fn ingest(mut items: Ndjson<Item>) -> Result<HttpResponse, NdjsonError> {
+///     while let Some(item) = items.next().await {
+///         store(item?).await;
+///     }
+///     ...
+/// }
+/// ```
+///
+/// Blank lines are skipped. The stream ends after the first error.
+pub struct Ndjson<T> {
+    payload: dev::Payload,
+    buf: web::BytesMut,
+    line: usize,
+    limit: usize,
+    done: bool,
+    _item: PhantomData<fn() -> T>,
+}
+
+impl<T: DeserializeOwned> Ndjson<T> {
+    /// Takes the next complete line out of the buffer, or the rest of it once the body has ended.
+    fn next_line(&mut self, eof: bool) -> Option<Result<T, NdjsonError>> {
+        loop {
+            let line = match self.buf.iter().position(|&b| b == b'\n') {
+                Some(end) => self.buf.split_to(end + 1),
+                None if eof && !self.buf.is_empty() => self.buf.split(),
+                None if self.buf.len() > self.limit => {
+                    return Some(Err(NdjsonError::LineTooLong {
+                        line: self.line + 1,
+                        limit: self.limit,
+                    }))
+                }
+                None => return None,
+            };
+            self.line += 1;
+
+            if line.len() > self.limit + 1 {
+                return Some(Err(NdjsonError::LineTooLong {
+                    line: self.line,
+                    limit: self.limit,
+                }));
+            }
+            if line.iter().all(u8::is_ascii_whitespace) {
+                continue;
+            }
+            return Some(serde_json::from_slice(&line).map_err(|source| NdjsonError::Deserialize {
+                line: self.line,
+                source,
+            }));
+        }
+    }
+}
+
+impl<T: DeserializeOwned> Stream for Ndjson<T> {
+    type Item = Result<T, NdjsonError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            if this.done {
+                return Poll::Ready(None);
+            }
+            if let Some(item) = this.next_line(false) {
+                this.done = item.is_err();
+                return Poll::Ready(Some(item));
+            }
+
+            match Pin::new(&mut this.payload).poll_next(cx) {
+                Poll::Ready(Some(Ok(chunk))) => this.buf.extend_from_slice(&chunk),
+                Poll::Ready(Some(Err(err))) => {
+                    this.done = true;
+                    return Poll::Ready(Some(Err(NdjsonError::Payload(err))));
+                }
+                Poll::Ready(None) => {
+                    let item = this.next_line(true);
+                    this.done = true;
+                    return Poll::Ready(item);
+                }
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+impl<T: DeserializeOwned> FromRequest for Ndjson<T> {
+    type Error = NdjsonError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut dev::Payload) -> Self::Future {
+        let is_ndjson = req
+            .headers()
+            .get(http::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.starts_with(NDJSON));
+        if !is_ndjson {
+            return ready(Err(NdjsonError::ContentType));
+        }
+        let config = req.app_data::<NdjsonConfig>().copied().unwrap_or_default();
+
+        ready(Ok(Ndjson {
+            payload: payload.take(),
+            buf: web::BytesMut::new(),
+            line: 0,
+            limit: config.line_limit,
+            done: false,
+            _item: PhantomData,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::body::MessageBody;
+    use actix_web::{rt::pin, test, App};
+    use serde::Deserialize;
+
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Item {
+        id: u32,
+    }
+
+    fn items(n: u32) -> impl Stream<Item = Result<Item, Error>> {
+        stream::iter((1..=n).map(|id| Ok(Item { id })))
+    }
+
+    #[actix_web::test]
+    async fn test_json_array() {
+        let req = test::TestRequest::default().to_http_request();
+
+        let res = JsonStream::array(items(3)).respond_to(&req);
+        assert_eq!(res.headers().get(http::header::CONTENT_TYPE).unwrap(), "application/json");
+        let body = body::to_bytes(res.into_body()).await.unwrap();
+        assert_eq!(body, r#"[{"id":1},{"id":2},{"id":3}]"#);
+
+        let res = JsonStream::array(items(0)).respond_to(&req);
+        assert_eq!(body::to_bytes(res.into_body()).await.unwrap(), "[]");
+    }
+
+    #[actix_web::test]
+    async fn test_ndjson() {
+        let req = test::TestRequest::default()
+            .insert_header((http::header::ACCEPT, NDJSON))
+            .to_http_request();
+
+        let res = JsonStream::new(Format::from_req(&req), items(2)).respond_to(&req);
+        assert_eq!(res.headers().get(http::header::CONTENT_TYPE).unwrap(), NDJSON);
+        let body = body::to_bytes(res.into_body()).await.unwrap();
+        assert_eq!(body, "{\"id\":1}\n{\"id\":2}\n");
+    }
+
+    #[actix_web::test]
+    async fn test_backpressure() {
+        let produced = Rc::new(Cell::new(0));
+        let counter = produced.clone();
+        let endless = stream::iter(1..).map(move |id| {
+            counter.set(counter.get() + 1);
+            Ok::<_, Error>(Item { id })
+        });
+
+        let req = test::TestRequest::default().to_http_request();
+        let body = JsonStream::array(endless).respond_to(&req).into_body();
+        assert_eq!(produced.get(), 0);
+
+        pin!(body);
+        for expected in ["[", r#"{"id":1}"#, r#",{"id":2}"#] {
+            let chunk = futures::future::poll_fn(|cx| body.as_mut().poll_next(cx)).await;
+            assert_eq!(chunk.unwrap().unwrap(), expected);
+        }
+        // Items are produced only as fast as the body is read.
+        assert_eq!(produced.get(), 2);
+    }
+
+    async fn ingest(mut items: Ndjson<Item>) -> Result<web::Json<Vec<Item>>, NdjsonError> {
+        let mut ingested = vec![];
+        while let Some(item) = items.next().await {
+            ingested.push(item?);
+        }
+        Ok(web::Json(ingested))
+    }
+
+    #[actix_web::test]
+    async fn test_ndjson_extractor() {
+        let app = test::init_service(
+            App::new()
+                .app_data(NdjsonConfig { line_limit: 32 })
+                .route("/ingest", web::post().to(ingest)),
+        )
+        .await;
+        let post = |body: &'static str| {
+            test::TestRequest::post()
+                .uri("/ingest")
+                .insert_header((http::header::CONTENT_TYPE, NDJSON))
+                .set_payload(body)
+                .to_request()
+        };
+
+        // The last line does not need a trailing newline.
+        let body = test::call_and_read_body(&app, post("{\"id\":1}\n\n{\"id\":2}\r\n{\"id\":3}")).await;
+        assert_eq!(body, r#"[{"id":1},{"id":2},{"id":3}]"#);
+
+        let res = test::call_service(&app, post("{\"id\":1}\n{\"id\":\"two\"}\n")).await;
+        assert_eq!(res.status(), http::StatusCode::BAD_REQUEST);
+        let body = test::read_body(res).await;
+        assert!(std::str::from_utf8(&body).unwrap().starts_with("line 2: "));
+
+        let res = test::call_service(&app, post(r#"{"id":1,"padding":"this line is too long"}"#)).await;
+        assert_eq!(res.status(), http::StatusCode::PAYLOAD_TOO_LARGE);
+
+        let req = test::TestRequest::post()
+            .uri("/ingest")
+            .insert_header(http::header::ContentType::json())
+            .set_payload("{\"id\":1}")
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), http::StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+}
@@ -0,0 +1,201 @@
+use actix_web::body::{BoxBody, EitherBody};
+use actix_web::dev::ServiceResponse;
+use actix_web::middleware::{ErrorHandlerResponse, ErrorHandlers};
+use actix_web::{error, http, web, HttpResponse};
+use tera::{Context, Tera, Value};
+
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use crate::i18n::{Catalog, Locale};
+
+#[derive(Debug, derive_more::Display, derive_more::From)]
+#[display(fmt = "template error: {}", _0)]
+pub struct TemplateError(tera::Error);
+
+impl std::error::Error for TemplateError {}
+
+impl error::ResponseError for TemplateError {}
+
+/// Tera templates loaded from a directory, reloaded on every render when `reload` is set.
+pub struct Templates {
+    tera: RwLock<Tera>,
+    reload: bool,
+}
+
+impl Templates {
+    pub fn new(dir: &str, reload: bool) -> Result<Self, TemplateError> {
+        let mut tera = Tera::new(&format!("{}/**/*.html", dir))?;
+        tera.register_function("t", translate);
+
+        Ok(Templates {
+            tera: RwLock::new(tera),
+            reload,
+        })
+    }
+
+    /// Renders `name` with `lang` set in the context for the `t()` function.
+    pub fn render(&self, name: &str, locale: &Locale, mut ctx: Context) -> Result<String, TemplateError> {
+        if self.reload {
+            self.tera.write().unwrap().full_reload()?;
+        }
+        ctx.insert("lang", &locale.lang().to_string());
+
+        Ok(self.tera.read().unwrap().render(name, &ctx)?)
+    }
+
+    pub fn page(&self, name: &str, locale: &Locale, ctx: Context) -> Result<HttpResponse, TemplateError> {
+        let body = self.render(name, locale, ctx)?;
+
+        Ok(HttpResponse::Ok()
+            .content_type(http::header::ContentType::html())
+            .insert_header((http::header::CONTENT_LANGUAGE, locale.lang().to_string()))
+            .body(body))
+    }
+}
+
+/// `{{ t(key="hello-world", lang=lang) }}`
+fn translate(args: &HashMap<String, Value>) -> tera::Result<Value> {
+    let key = args
+        .get("key")
+        .and_then(Value::as_str)
+        .ok_or("t() requires a `key` argument")?;
+    let lang = args
+        .get("lang")
+        .and_then(Value::as_str)
+        .and_then(|lang| lang.parse().ok())
+        .unwrap_or_default();
+
+    Ok(Value::String(Catalog::global().format(&lang, key, None)))
+}
+
+/// Renders error responses as `error.html` for clients that asked for HTML,
+/// or when the error itself already produced an HTML body.
+pub fn error_pages<B: 'static>() -> ErrorHandlers<B> {
+    ErrorHandlers::new().default_handler(render_error_page)
+}
+
+fn render_error_page<B>(res: ServiceResponse<B>) -> actix_web::Result<ErrorHandlerResponse<B>> {
+    let Some(message) = res.response().error().map(ToString::to_string) else {
+        return Ok(ErrorHandlerResponse::Response(res.map_into_left_body()));
+    };
+    let Some(templates) = res.request().app_data::<web::Data<Templates>>().cloned() else {
+        return Ok(ErrorHandlerResponse::Response(res.map_into_left_body()));
+    };
+    if !is_html(res.response().headers()) && !accepts_html(res.request().headers()) {
+        return Ok(ErrorHandlerResponse::Response(res.map_into_left_body()));
+    }
+
+    let status = res.status();
+    let locale = Locale::from_req(res.request());
+    let mut ctx = Context::new();
+    ctx.insert("status", &status.as_u16());
+    ctx.insert("reason", status.canonical_reason().unwrap_or_default());
+    ctx.insert("message", &message);
+
+    let body = match templates.render("error.html", &locale, ctx) {
+        Ok(body) => body,
+        Err(err) => {
+            log::error!("{}", err);
+            return Ok(ErrorHandlerResponse::Response(res.map_into_left_body()));
+        }
+    };
+
+    let mut res = res.map_body(|_, _| EitherBody::right(BoxBody::new(body)));
+    res.headers_mut().insert(
+        http::header::CONTENT_TYPE,
+        http::header::HeaderValue::from_static("text/html; charset=utf-8"),
+    );
+    Ok(ErrorHandlerResponse::Response(res))
+}
+
+fn is_html(headers: &http::header::HeaderMap) -> bool {
+    headers
+        .get(http::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("text/html"))
+}
+
+fn accepts_html(headers: &http::header::HeaderMap) -> bool {
+    headers
+        .get(http::header::ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.contains("text/html"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test, App};
+
+    fn templates() -> web::Data<Templates> {
+        web::Data::new(Templates::new("templates", false).unwrap())
+    }
+
+    #[actix_web::test]
+    async fn test_render_escapes() {
+        #[derive(serde::Serialize)]
+        struct User {
+            name: &'static str,
+        }
+
+        let mut ctx = Context::new();
+        ctx.insert("users", &[User { name: "<script>" }]);
+        let html = templates()
+            .render("users/show.html", &Locale::default(), ctx)
+            .unwrap();
+
+        assert!(html.contains("<li>&lt;script&gt;</li>"));
+        assert!(html.contains("<title>Users - Actix Web Example</title>"));
+    }
+
+    #[actix_web::test]
+    async fn test_users_page() {
+        let app = test::init_service(
+            App::new()
+                .app_data(templates())
+                .configure(crate::routes::application_routes),
+        )
+        .await;
+        let req = test::TestRequest::get()
+            .uri("/users/show?lang=ja")
+            .to_request();
+        let body = test::call_and_read_body(&app, req).await;
+        let body = std::str::from_utf8(&body).unwrap();
+
+        assert!(body.contains(r#"<html lang="ja">"#));
+        assert!(body.contains("<li>Alice</li>"));
+    }
+
+    #[actix_web::test]
+    async fn test_error_page() {
+        let app = test::init_service(
+            App::new()
+                .app_data(templates())
+                .wrap(error_pages())
+                .configure(crate::routes::error_routes),
+        )
+        .await;
+
+        // CustomErrorEnum responds with HTML, so it gets the branded page.
+        let req = test::TestRequest::get().uri("/custom-error-enum").to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), http::StatusCode::INTERNAL_SERVER_ERROR);
+        let body = test::read_body(res).await;
+        let body = std::str::from_utf8(&body).unwrap();
+        assert!(body.contains("<h1>500 Internal Server Error</h1>"));
+        assert!(body.contains("<p>internal error</p>"));
+
+        // Plain-text errors stay as they are unless the client asks for HTML.
+        let req = test::TestRequest::get().uri("/custom-error").to_request();
+        let body = test::call_and_read_body(&app, req).await;
+        assert_eq!(body, "my error: test");
+
+        let req = test::TestRequest::get()
+            .uri("/custom-error")
+            .insert_header((http::header::ACCEPT, "text/html"))
+            .to_request();
+        let body = test::call_and_read_body(&app, req).await;
+        assert!(std::str::from_utf8(&body).unwrap().contains("<p>my error: test</p>"));
+    }
+}
@@ -0,0 +1,354 @@
+use actix_tls::accept::openssl::TlsStream;
+use actix_web::body::MessageBody;
+use actix_web::dev::{self, Extensions, ServiceRequest, ServiceResponse};
+use actix_web::guard::{Guard, GuardContext};
+use actix_web::middleware::Next;
+use actix_web::{error, rt::net::TcpStream, Error, FromRequest, HttpMessage, HttpRequest};
+use futures::future::{ready, Ready};
+use openssl::error::ErrorStack;
+use openssl::nid::Nid;
+use openssl::ssl::{SslAcceptor, SslAcceptorBuilder, SslFiletype, SslMethod, SslVerifyMode};
+use openssl::x509::{X509NameRef, X509Ref, X509};
+use serde::Serialize;
+
+use std::any::Any;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+
+/// Acceptor serving the certificate chain and private key from PEM files.
+pub fn acceptor(cert_file: &str, key_file: &str) -> Result<SslAcceptorBuilder, ErrorStack> {
+    let mut builder = SslAcceptor::mozilla_intermediate(SslMethod::tls())?;
+    builder.set_private_key_file(key_file, SslFiletype::PEM)?;
+    builder.set_certificate_chain_file(cert_file)?;
+    Ok(builder)
+}
+
+/// Whether clients have to present a certificate signed by a trusted CA.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ClientAuth {
+    /// Certificates are verified when sent, but the handshake succeeds without one.
+    Optional,
+    /// The handshake fails without a valid certificate.
+    Required,
+}
+
+impl FromStr for ClientAuth {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "optional" => Ok(ClientAuth::Optional),
+            "required" => Ok(ClientAuth::Required),
+            _ => Err(format!("unknown client auth mode {:?}, expected \"optional\" or \"required\"", s)),
+        }
+    }
+}
+
+pub fn load_ca_bundle(path: &str) -> std::io::Result<Vec<X509>> {
+    let pem = std::fs::read(path)?;
+    X509::stack_from_pem(&pem).map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))
+}
+
+/// Enables client certificate verification against `cas`.
+pub fn client_auth(builder: &mut SslAcceptorBuilder, cas: &[X509], mode: ClientAuth) -> Result<(), ErrorStack> {
+    for ca in cas {
+        builder.cert_store_mut().add_cert(ca.clone())?;
+        builder.add_client_ca(ca)?;
+    }
+    let verify = match mode {
+        ClientAuth::Optional => SslVerifyMode::PEER,
+        ClientAuth::Required => SslVerifyMode::PEER | SslVerifyMode::FAIL_IF_NO_PEER_CERT,
+    };
+    builder.set_verify(verify);
+    Ok(())
+}
+
+/// Identity taken from a verified client certificate.
+///
+/// Use `Option<ClientCert>` to accept requests without one; otherwise they get 401.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct ClientCert {
+    pub subject: String,
+    pub common_name: Option<String>,
+    /// DNS names, emails, URIs and IP addresses from the subjectAltName extension.
+    pub sans: Vec<String>,
+}
+
+impl ClientCert {
+    fn from_x509(cert: &X509Ref) -> Self {
+        let sans = cert
+            .subject_alt_names()
+            .map(|names| {
+                names
+                    .iter()
+                    .filter_map(|name| {
+                        name.dnsname()
+                            .or_else(|| name.email())
+                            .or_else(|| name.uri())
+                            .map(str::to_owned)
+                            .or_else(|| name.ipaddress().and_then(ip_to_string))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        ClientCert {
+            subject: name_to_string(cert.subject_name()),
+            common_name: cert
+                .subject_name()
+                .entries_by_nid(Nid::COMMONNAME)
+                .next()
+                .and_then(|entry| entry.data().to_string().ok()),
+            sans,
+        }
+    }
+
+    /// Whether the common name or one of the SANs equals `identity`.
+    pub fn is(&self, identity: &str) -> bool {
+        self.common_name.as_deref() == Some(identity) || self.sans.iter().any(|san| san == identity)
+    }
+}
+
+fn name_to_string(name: &X509NameRef) -> String {
+    name.entries()
+        .map(|entry| {
+            let key = entry.object().nid().short_name().unwrap_or("?");
+            let value = entry.data().to_string().unwrap_or_default();
+            format!("{}={}", key, value)
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn ip_to_string(bytes: &[u8]) -> Option<String> {
+    let ip: IpAddr = match bytes.len() {
+        4 => Ipv4Addr::from(<[u8; 4]>::try_from(bytes).ok()?).into(),
+        16 => Ipv6Addr::from(<[u8; 16]>::try_from(bytes).ok()?).into(),
+        _ => return None,
+    };
+    Some(ip.to_string())
+}
+
+/// `HttpServer::on_connect` callback storing the peer's [`ClientCert`] as connection data.
+pub fn peer_certificate(conn: &dyn Any, ext: &mut Extensions) {
+    let Some(stream) = conn.downcast_ref::<TlsStream<TcpStream>>() else {
+        return;
+    };
+    if let Some(cert) = stream.ssl().peer_certificate() {
+        ext.insert(ClientCert::from_x509(&cert));
+    }
+}
+
+impl FromRequest for ClientCert {
+    type Error = Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut dev::Payload) -> Self::Future {
+        ready(
+            req.conn_data::<ClientCert>()
+                .cloned()
+                .ok_or_else(|| error::ErrorUnauthorized("client certificate required")),
+        )
+    }
+}
+
+/// Copies the connection's [`ClientCert`] into the request so guards can see it.
+pub async fn expose_client_cert(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    if let Some(cert) = req.conn_data::<ClientCert>().cloned() {
+        req.extensions_mut().insert(cert);
+    }
+    next.call(req).await
+}
+
+/// Matches requests whose client certificate names one of the allowed identities.
+/// Requires the [`expose_client_cert`] middleware.
+pub struct ClientIdentity {
+    allowed: Vec<String>,
+}
+
+impl ClientIdentity {
+    pub fn new<I, S>(allowed: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        ClientIdentity {
+            allowed: allowed.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl Guard for ClientIdentity {
+    fn check(&self, ctx: &GuardContext<'_>) -> bool {
+        ctx.req_data()
+            .get::<ClientCert>()
+            .is_some_and(|cert| self.allowed.iter().any(|identity| cert.is(identity)))
+    }
+}
+
+/// Certificates generated on the fly for tests.
+#[cfg(any(test, feature = "testing"))]
+pub mod testing {
+    use openssl::asn1::Asn1Time;
+    use openssl::bn::{BigNum, MsbOption};
+    use openssl::ec::{EcGroup, EcKey};
+    use openssl::hash::MessageDigest;
+    use openssl::nid::Nid;
+    use openssl::pkey::{PKey, Private};
+    use openssl::x509::extension::{BasicConstraints, SubjectAlternativeName};
+    use openssl::x509::{X509NameBuilder, X509};
+
+    pub struct CertifiedKey {
+        pub cert: X509,
+        pub key: PKey<Private>,
+    }
+
+    fn key() -> PKey<Private> {
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+        PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap()
+    }
+
+    /// Builds a certificate for `cn` (also used as DNS SAN), signed by `issuer`
+    /// or by itself when there is none.
+    pub fn issue(cn: &str, issuer: Option<&CertifiedKey>, ca: bool) -> CertifiedKey {
+        let key = key();
+        let mut name = X509NameBuilder::new().unwrap();
+        name.append_entry_by_nid(Nid::COMMONNAME, cn).unwrap();
+        let name = name.build();
+
+        let mut serial = BigNum::new().unwrap();
+        serial.rand(64, MsbOption::MAYBE_ZERO, false).unwrap();
+
+        let mut builder = X509::builder().unwrap();
+        builder.set_version(2).unwrap();
+        builder.set_serial_number(&serial.to_asn1_integer().unwrap()).unwrap();
+        builder.set_subject_name(&name).unwrap();
+        builder
+            .set_issuer_name(issuer.map_or(&name, |issuer| issuer.cert.subject_name()))
+            .unwrap();
+        builder.set_pubkey(&key).unwrap();
+        builder.set_not_before(&Asn1Time::days_from_now(0).unwrap()).unwrap();
+        builder.set_not_after(&Asn1Time::days_from_now(1).unwrap()).unwrap();
+        if ca {
+            builder
+                .append_extension(BasicConstraints::new().critical().ca().build().unwrap())
+                .unwrap();
+        } else {
+            let san = SubjectAlternativeName::new()
+                .dns(cn)
+                .ip("127.0.0.1")
+                .build(&builder.x509v3_context(issuer.map(|issuer| &*issuer.cert), None))
+                .unwrap();
+            builder.append_extension(san).unwrap();
+        }
+        let signer = issuer.map_or(&key, |issuer| &issuer.key);
+        builder.sign(signer, MessageDigest::sha256()).unwrap();
+
+        CertifiedKey {
+            cert: builder.build(),
+            key,
+        }
+    }
+
+    pub fn self_signed(cn: &str) -> CertifiedKey {
+        issue(cn, None, false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::testing::{issue, CertifiedKey};
+    use super::*;
+    use actix_web::middleware::from_fn;
+    use actix_web::{http, rt, web, App, HttpResponse, HttpServer};
+
+    use std::net::{SocketAddr, TcpListener};
+
+    async fn peer(cert: Option<ClientCert>) -> HttpResponse {
+        HttpResponse::Ok().json(cert)
+    }
+
+    fn serve(server: &CertifiedKey, ca: &CertifiedKey, mode: ClientAuth) -> SocketAddr {
+        let mut builder = SslAcceptor::mozilla_intermediate(SslMethod::tls()).unwrap();
+        builder.set_private_key(&server.key).unwrap();
+        builder.set_certificate(&server.cert).unwrap();
+        client_auth(&mut builder, std::slice::from_ref(&ca.cert), mode).unwrap();
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = HttpServer::new(|| {
+            App::new()
+                .wrap(from_fn(expose_client_cert))
+                .configure(crate::routes::internal_routes)
+                .route("/peer", web::get().to(peer))
+        })
+        .workers(1)
+        .disable_signals()
+        .on_connect(peer_certificate)
+        .listen_openssl(listener, builder)
+        .unwrap();
+        rt::spawn(server.run());
+        addr
+    }
+
+    fn client(server: &CertifiedKey, addr: SocketAddr, identity: Option<&CertifiedKey>) -> reqwest::Client {
+        let mut builder = reqwest::Client::builder()
+            .add_root_certificate(reqwest::Certificate::from_pem(&server.cert.to_pem().unwrap()).unwrap())
+            .resolve("localhost", addr);
+        if let Some(identity) = identity {
+            let identity = reqwest::Identity::from_pkcs8_pem(
+                &identity.cert.to_pem().unwrap(),
+                &identity.key.private_key_to_pem_pkcs8().unwrap(),
+            )
+            .unwrap();
+            builder = builder.identity(identity);
+        }
+        builder.build().unwrap()
+    }
+
+    #[actix_web::test]
+    async fn test_required_client_cert() {
+        let server = testing::self_signed("localhost");
+        let ca = issue("Test CA", None, true);
+        let ops = issue("ops.internal", Some(&ca), false);
+        let rogue_ca = issue("Rogue CA", None, true);
+        let rogue = issue("ops.internal", Some(&rogue_ca), false);
+        let addr = serve(&server, &ca, ClientAuth::Required);
+        let url = format!("https://localhost:{}/internal/whoami", addr.port());
+
+        let res = client(&server, addr, Some(&ops)).get(&url).send().await.unwrap();
+        assert_eq!(res.status(), http::StatusCode::OK);
+        let cert: serde_json::Value = res.json().await.unwrap();
+        assert_eq!(cert["common_name"], "ops.internal");
+        assert_eq!(cert["sans"], serde_json::json!(["ops.internal", "127.0.0.1"]));
+
+        assert!(client(&server, addr, None).get(&url).send().await.is_err());
+        assert!(client(&server, addr, Some(&rogue)).get(&url).send().await.is_err());
+    }
+
+    #[actix_web::test]
+    async fn test_optional_client_cert() {
+        let server = testing::self_signed("localhost");
+        let ca = issue("Test CA", None, true);
+        let ops = issue("ops.internal", Some(&ca), false);
+        let other = issue("billing.internal", Some(&ca), false);
+        let addr = serve(&server, &ca, ClientAuth::Optional);
+        let url = |path: &str| format!("https://localhost:{}{}", addr.port(), path);
+
+        let anonymous = client(&server, addr, None);
+        let res = anonymous.get(url("/peer")).send().await.unwrap();
+        assert_eq!(res.text().await.unwrap(), "null");
+        let res = anonymous.get(url("/internal/whoami")).send().await.unwrap();
+        assert_eq!(res.status(), http::StatusCode::NOT_FOUND);
+
+        // A trusted certificate for another identity does not pass the guard.
+        let res = client(&server, addr, Some(&other)).get(url("/internal/whoami")).send().await.unwrap();
+        assert_eq!(res.status(), http::StatusCode::NOT_FOUND);
+
+        let res = client(&server, addr, Some(&ops)).get(url("/internal/whoami")).send().await.unwrap();
+        assert_eq!(res.status(), http::StatusCode::OK);
+    }
+}
@@ -0,0 +1,485 @@
+use actix_http::Request;
+use actix_web::dev::{AppConfig, RequestHead, Service, ServiceFactory};
+use actix_web::http;
+use futures::future::LocalBoxFuture;
+use openssl::error::ErrorStack;
+use openssl::ssl::{NameType, SniError, SslAcceptor, SslAcceptorBuilder, SslContext, SslFiletype, SslMethod};
+use serde::Deserialize;
+
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use crate::routes;
+
+#[derive(Debug, derive_more::Display, derive_more::From)]
+pub enum SiteError {
+    #[display(fmt = "cannot read sites: {}", _0)]
+    Io(std::io::Error),
+    #[display(fmt = "invalid sites: {}", _0)]
+    Parse(toml::de::Error),
+    #[display(fmt = "site {}: unknown route module {}", site, module)]
+    #[from(ignore)]
+    UnknownRoutes { site: String, module: String },
+    #[display(fmt = "site {}: {}", site, source)]
+    #[from(ignore)]
+    Tls { site: String, source: ErrorStack },
+}
+
+impl std::error::Error for SiteError {}
+
+/// `www.example.com`, or `*.example.com` for exactly one label in front of `example.com`.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(from = "String")]
+pub struct HostPattern(String);
+
+impl From<String> for HostPattern {
+    fn from(pattern: String) -> Self {
+        HostPattern(pattern.to_ascii_lowercase())
+    }
+}
+
+impl HostPattern {
+    pub fn matches(&self, host: &str) -> bool {
+        let host = host.to_ascii_lowercase();
+        match self.0.strip_prefix("*.") {
+            Some(parent) => host
+                .split_once('.')
+                .is_some_and(|(label, rest)| !label.is_empty() && rest == parent),
+            None => host == self.0,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct TlsFiles {
+    pub cert: PathBuf,
+    pub key: PathBuf,
+}
+
+/// One site from the sites file.
+///
+/// ```toml
+/// [[site]]
+/// name = "docs"
+/// hosts = ["docs.example.com", "*.docs.example.com"]
+/// static_root = "static/sites/docs"
+/// routes = ["health"]
+/// tls = { cert = "certs/docs.pem", key = "certs/docs-key.pem" }
+/// ```
+#[derive(Clone, Debug, Deserialize)]
+pub struct Site {
+    pub name: String,
+    pub hosts: Vec<HostPattern>,
+    /// Served below the site's routes, with `index.html` for directories.
+    pub static_root: Option<PathBuf>,
+    /// Names from [`routes::MODULES`].
+    #[serde(default)]
+    pub routes: Vec<String>,
+    /// Certificate picked through SNI; the listener's default one is used otherwise.
+    pub tls: Option<TlsFiles>,
+}
+
+impl Site {
+    pub fn matches(&self, host: &str) -> bool {
+        self.hosts.iter().any(|pattern| pattern.matches(host))
+    }
+}
+
+/// Sites in the order they are matched.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct Sites {
+    #[serde(default, rename = "site")]
+    pub sites: Vec<Site>,
+}
+
+impl Sites {
+    pub fn parse(source: &str) -> Result<Self, SiteError> {
+        let sites: Sites = toml::from_str(source)?;
+        for site in &sites.sites {
+            if let Some(module) = site
+                .routes
+                .iter()
+                .find(|module| !routes::MODULES.iter().any(|(name, _)| name == module))
+            {
+                return Err(SiteError::UnknownRoutes {
+                    site: site.name.clone(),
+                    module: module.clone(),
+                });
+            }
+        }
+        Ok(sites)
+    }
+
+    /// Reads the sites file, or serves no sites when it does not exist.
+    pub fn load(path: &str) -> Result<Self, SiteError> {
+        match std::fs::read_to_string(path) {
+            Ok(source) => Sites::parse(&source),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Sites::default()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    pub fn find(&self, host: &str) -> Option<&Site> {
+        self.sites.iter().find(|site| site.matches(host))
+    }
+}
+
+fn strip_port(authority: &str) -> &str {
+    match authority.strip_prefix('[') {
+        Some(v6) => v6.split(']').next().unwrap_or(v6),
+        None => authority.split(':').next().unwrap_or(authority),
+    }
+}
+
+/// The request's host, from `Host` or the HTTP/2 `:authority`, without the port.
+fn request_host(head: &RequestHead) -> Option<&str> {
+    head.headers()
+        .get(http::header::HOST)
+        .and_then(|value| value.to_str().ok())
+        .or_else(|| head.uri.authority().map(|authority| authority.as_str()))
+        .map(strip_port)
+}
+
+/// Hands each request to the app of the first site matching its host, or to the main app.
+///
+/// Every site is an app of its own, so its routes and static files never show up in the
+/// main app's router, where a catch-all site scope would shadow `match_pattern` and
+/// `has_resource` for every path.
+pub struct VirtualHosts<T> {
+    main: T,
+    sites: Vec<(Vec<HostPattern>, T)>,
+}
+
+impl<T> VirtualHosts<T> {
+    pub fn new(main: T) -> Self {
+        VirtualHosts { main, sites: vec![] }
+    }
+
+    pub fn site(mut self, site: &Site, app: T) -> Self {
+        self.sites.push((site.hosts.clone(), app));
+        self
+    }
+}
+
+impl<T> ServiceFactory<Request> for VirtualHosts<T>
+where
+    T: ServiceFactory<Request, Config = AppConfig>,
+    T::Future: 'static,
+{
+    type Response = T::Response;
+    type Error = T::Error;
+    type Config = AppConfig;
+    type Service = VirtualHostsService<T::Service>;
+    type InitError = T::InitError;
+    type Future = LocalBoxFuture<'static, Result<Self::Service, Self::InitError>>;
+
+    fn new_service(&self, config: AppConfig) -> Self::Future {
+        let main = self.main.new_service(config.clone());
+        let sites: Vec<_> = self
+            .sites
+            .iter()
+            .map(|(hosts, app)| (hosts.clone(), app.new_service(config.clone())))
+            .collect();
+
+        Box::pin(async move {
+            let main = main.await?;
+            let mut services = Vec::with_capacity(sites.len());
+            for (hosts, site) in sites {
+                services.push((hosts, site.await?));
+            }
+            Ok(VirtualHostsService { main, sites: services })
+        })
+    }
+}
+
+pub struct VirtualHostsService<S> {
+    main: S,
+    sites: Vec<(Vec<HostPattern>, S)>,
+}
+
+impl<S: Service<Request>> Service<Request> for VirtualHostsService<S> {
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        for service in std::iter::once(&self.main).chain(self.sites.iter().map(|(_, site)| site)) {
+            if service.poll_ready(cx)?.is_pending() {
+                return Poll::Pending;
+            }
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&self, req: Request) -> Self::Future {
+        let site = request_host(req.head()).and_then(|host| {
+            self.sites
+                .iter()
+                .find(|(hosts, _)| hosts.iter().any(|pattern| pattern.matches(host)))
+        });
+        match site {
+            Some((_, site)) => site.call(req),
+            None => self.main.call(req),
+        }
+    }
+}
+
+/// Selects each site's certificate by SNI. Connections for other names keep the
+/// builder's own certificate.
+///
+/// A site's context replaces the builder's for the rest of the handshake, so `configure`
+/// must give it everything else the listener does: ALPN and client certificate checks.
+pub fn sni(
+    builder: &mut SslAcceptorBuilder,
+    sites: &Sites,
+    configure: impl Fn(&mut SslAcceptorBuilder) -> Result<(), ErrorStack>,
+) -> Result<(), SiteError> {
+    let mut contexts = vec![];
+    for site in &sites.sites {
+        let Some(tls) = &site.tls else {
+            continue;
+        };
+        let context = site_context(tls, &configure).map_err(|source| SiteError::Tls {
+            site: site.name.clone(),
+            source,
+        })?;
+        contexts.push((site.hosts.clone(), context));
+    }
+    if contexts.is_empty() {
+        return Ok(());
+    }
+
+    let contexts = Arc::new(contexts);
+    builder.set_servername_callback(move |ssl, _alert| {
+        let Some(name) = ssl.servername(NameType::HOST_NAME).map(str::to_owned) else {
+            return Ok(());
+        };
+        let context = contexts
+            .iter()
+            .find(|(hosts, _)| hosts.iter().any(|pattern| pattern.matches(&name)));
+        if let Some((_, context)) = context {
+            ssl.set_ssl_context(context).map_err(|_| SniError::ALERT_FATAL)?;
+        }
+        Ok(())
+    });
+    Ok(())
+}
+
+fn site_context(
+    tls: &TlsFiles,
+    configure: impl Fn(&mut SslAcceptorBuilder) -> Result<(), ErrorStack>,
+) -> Result<SslContext, ErrorStack> {
+    let mut builder = SslAcceptor::mozilla_intermediate(SslMethod::tls())?;
+    builder.set_private_key_file(&tls.key, SslFiletype::PEM)?;
+    builder.set_certificate_chain_file(&tls.cert)?;
+    configure(&mut builder)?;
+    Ok(builder.build().into_context())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::ProtocolConfig;
+    use crate::tls::testing::{issue, self_signed, CertifiedKey};
+    use crate::tls::{self, ClientAuth};
+    use actix_files::Files;
+    use actix_service::IntoServiceFactory;
+    use actix_web::body::MessageBody;
+    use actix_web::dev::{ServiceRequest, ServiceResponse};
+    use actix_web::{rt, test, web, App, HttpResponse, HttpServer};
+    use openssl::ssl::{SslConnector, SslVerifyMode, SslVersion};
+
+    use std::net::{TcpListener, TcpStream};
+
+    const SITES: &str = r#"
+        [[site]]
+        name = "www"
+        hosts = ["www.example.com", "*.pages.example.com"]
+        static_root = "static"
+
+        [[site]]
+        name = "api"
+        hosts = ["api.example.com"]
+        routes = ["health"]
+    "#;
+
+    #[actix_web::test]
+    async fn test_host_pattern() {
+        let pattern = HostPattern::from("*.Example.com".to_owned());
+        assert!(pattern.matches("docs.example.com"));
+        assert!(pattern.matches("DOCS.example.com"));
+        assert!(!pattern.matches("example.com"));
+        assert!(!pattern.matches("a.b.example.com"));
+        assert!(HostPattern::from("example.com".to_owned()).matches("example.com"));
+
+        assert_eq!(strip_port("example.com:8080"), "example.com");
+        assert_eq!(strip_port("[::1]:8080"), "::1");
+    }
+
+    #[actix_web::test]
+    async fn test_parse_sites() {
+        let sites = Sites::parse(SITES).unwrap();
+        assert_eq!(sites.find("blog.pages.example.com").unwrap().name, "www");
+        assert_eq!(sites.find("api.example.com").unwrap().routes, ["health"]);
+        assert!(sites.find("example.com").is_none());
+
+        let err = Sites::parse("[[site]]\nname = \"x\"\nhosts = []\nroutes = [\"nope\"]").unwrap_err();
+        assert_eq!(err.to_string(), "site x: unknown route module nope");
+        assert!(Sites::load("does-not-exist.toml").unwrap().sites.is_empty());
+    }
+
+    fn main_routes(cfg: &mut web::ServiceConfig) {
+        cfg.route("/healthz", web::get().to(|| async { HttpResponse::Ok().body("main") }));
+    }
+
+    fn app(site: Option<&Site>) -> App<
+        impl ServiceFactory<
+            ServiceRequest,
+            Config = (),
+            Response = ServiceResponse<impl MessageBody>,
+            Error = actix_web::Error,
+            InitError = (),
+        >,
+    > {
+        let app = match site.and_then(|site| site.static_root.clone()) {
+            Some(root) => App::new().default_service(Files::new("/", root).index_file("index.html")),
+            None => App::new().default_service(web::to(HttpResponse::NotFound)),
+        };
+        let modules: Vec<routes::InitRoutes> = match site {
+            Some(site) => routes::MODULES
+                .iter()
+                .filter(|(name, _)| site.routes.iter().any(|module| module == name))
+                .map(|(_, init_routes)| *init_routes)
+                .collect(),
+            None => vec![main_routes],
+        };
+        modules.into_iter().fold(app, App::configure)
+    }
+
+    #[actix_web::test]
+    async fn test_site_routing() {
+        let sites = Sites::parse(SITES).unwrap();
+        let hosts = sites
+            .sites
+            .iter()
+            .fold(VirtualHosts::new(app(None).into_factory()), |hosts, site| {
+                hosts.site(site, app(Some(site)).into_factory())
+            });
+        let app = test::init_service(hosts).await;
+        let get = |host: &str, uri: &str| {
+            test::TestRequest::get()
+                .uri(uri)
+                .insert_header((http::header::HOST, host))
+                .to_request()
+        };
+
+        let body = test::call_and_read_body(&app, get("www.example.com:8080", "/")).await;
+        assert!(std::str::from_utf8(&body).unwrap().contains("<h1>Hello World</h1>"));
+        // Routes outside the subset are not reachable through the site.
+        let res = test::call_service(&app, get("www.example.com", "/healthz")).await;
+        assert_eq!(res.status(), http::StatusCode::NOT_FOUND);
+
+        let body = test::call_and_read_body(&app, get("api.example.com", "/healthz")).await;
+        assert_eq!(body, r#"{"status":"ok"}"#);
+        let body = test::call_and_read_body(&app, get("localhost", "/healthz")).await;
+        assert_eq!(body, "main");
+
+        // Sites leave no catch-all behind in the main app's resource map.
+        let res = test::call_service(&app, get("localhost", "/missing")).await;
+        assert_eq!(res.status(), http::StatusCode::NOT_FOUND);
+        assert_eq!(res.request().match_pattern(), None);
+        assert!(!res.request().resource_map().has_resource("/missing"));
+    }
+
+    #[actix_web::test]
+    async fn test_sni_certificates() {
+        let dir = std::env::temp_dir().join(format!("vhost-sni-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let files = |name: &str| {
+            let cert = self_signed(name);
+            let files = TlsFiles {
+                cert: dir.join(format!("{}.pem", name)),
+                key: dir.join(format!("{}-key.pem", name)),
+            };
+            std::fs::write(&files.cert, cert.cert.to_pem().unwrap()).unwrap();
+            std::fs::write(&files.key, cert.key.private_key_to_pem_pkcs8().unwrap()).unwrap();
+            files
+        };
+        let sites = Sites {
+            sites: vec![Site {
+                name: "a".to_owned(),
+                hosts: vec![HostPattern::from("*.a.test".to_owned())],
+                static_root: None,
+                routes: vec![],
+                tls: Some(files("www.a.test")),
+            }],
+        };
+
+        let ca = issue("Test CA", None, true);
+        let configure = |builder: &mut SslAcceptorBuilder| {
+            tls::client_auth(builder, std::slice::from_ref(&ca.cert), ClientAuth::Required)?;
+            ProtocolConfig::default().select_alpn(builder);
+            Ok(())
+        };
+        let fallback = self_signed("localhost");
+        let mut builder = SslAcceptor::mozilla_intermediate(SslMethod::tls()).unwrap();
+        builder.set_private_key(&fallback.key).unwrap();
+        builder.set_certificate(&fallback.cert).unwrap();
+        configure(&mut builder).unwrap();
+        sni(&mut builder, &sites, configure).unwrap();
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = HttpServer::new(|| App::new().route("/", web::get().to(HttpResponse::Ok)))
+            .workers(1)
+            .disable_signals()
+            .listen_openssl(listener, builder)
+            .unwrap();
+        rt::spawn(server.run());
+
+        let handshake = move |server_name: &'static str, identity: Option<CertifiedKey>| {
+            // TLS 1.2, so that a refused client certificate fails the handshake itself.
+            let mut connector = SslConnector::builder(SslMethod::tls()).unwrap();
+            connector.set_max_proto_version(Some(SslVersion::TLS1_2)).unwrap();
+            connector.set_verify(SslVerifyMode::NONE);
+            connector.set_alpn_protos(b"\x02h2\x08http/1.1").unwrap();
+            if let Some(identity) = identity {
+                connector.set_certificate(&identity.cert).unwrap();
+                connector.set_private_key(&identity.key).unwrap();
+            }
+            let stream = TcpStream::connect(addr).unwrap();
+            connector.build().connect(server_name, stream)
+        };
+        let client = issue("client.test", Some(&ca), false);
+
+        for (server_name, expected) in [("www.a.test", "www.a.test"), ("other.test", "localhost")] {
+            let identity = CertifiedKey {
+                cert: client.cert.clone(),
+                key: client.key.clone(),
+            };
+            let (common_name, alpn) = web::block(move || {
+                let ssl = handshake(server_name, Some(identity)).unwrap();
+                let cert = ssl.ssl().peer_certificate().unwrap();
+                let common_name = cert
+                    .subject_name()
+                    .entries_by_nid(openssl::nid::Nid::COMMONNAME)
+                    .next()
+                    .unwrap()
+                    .data()
+                    .as_slice()
+                    .to_vec();
+                (common_name, ssl.ssl().selected_alpn_protocol().map(<[u8]>::to_vec))
+            })
+            .await
+            .unwrap();
+            assert_eq!(common_name, expected.as_bytes());
+            assert_eq!(alpn.as_deref(), Some(&b"h2"[..]));
+
+            // The site's context asks for a client certificate just like the listener's.
+            let anonymous = web::block(move || handshake(server_name, None).is_err()).await.unwrap();
+            assert!(anonymous, "{} accepted a client without a certificate", server_name);
+        }
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,192 @@
+use actix_web::http::Method;
+
+/// A request against one route module and the route pattern expected to serve it.
+#[derive(Clone, Debug)]
+pub struct Fixture {
+    pub module: &'static str,
+    pub name: &'static str,
+    pub method: Method,
+    pub uri: &'static str,
+    pub headers: Vec<(&'static str, &'static str)>,
+    pub body: &'static str,
+    /// Client certificate identity the request is made with.
+    pub identity: Option<&'static str>,
+    /// `None` when no registered path matches and the default service answers.
+    /// Patterns are resolved from the path alone; guards are pinned by the snapshot.
+    pub pattern: Option<&'static str>,
+}
+
+impl Fixture {
+    pub fn new(module: &'static str, name: &'static str, method: Method, uri: &'static str) -> Self {
+        Fixture {
+            module,
+            name,
+            method,
+            uri,
+            headers: vec![],
+            body: "",
+            identity: None,
+            pattern: None,
+        }
+    }
+
+    pub fn header(mut self, name: &'static str, value: &'static str) -> Self {
+        self.headers.push((name, value));
+        self
+    }
+
+    pub fn body(mut self, body: &'static str) -> Self {
+        self.body = body;
+        self
+    }
+
+    pub fn identity(mut self, identity: &'static str) -> Self {
+        self.identity = Some(identity);
+        self
+    }
+
+    pub fn served_by(mut self, pattern: &'static str) -> Self {
+        self.pattern = Some(pattern);
+        self
+    }
+}
+
+fn get(module: &'static str, name: &'static str, uri: &'static str) -> Fixture {
+    Fixture::new(module, name, Method::GET, uri)
+}
+
+fn post(module: &'static str, name: &'static str, uri: &'static str) -> Fixture {
+    Fixture::new(module, name, Method::POST, uri)
+}
+
+/// At least one request for every route registered by `create_app`.
+///
+/// `/sleep` is left out because it takes five seconds to answer.
+pub fn fixtures() -> Vec<Fixture> {
+    vec![
+        // application
+        get("application", "index", "/").served_by("/"),
+        get("application", "hello", "/hello").served_by("/hello"),
+        get("application", "hello-trailing-slash", "/hello/").served_by("/hello"),
+        get("application", "hello-ja", "/hello").header("accept-language", "ja").served_by("/hello"),
+        post("application", "echo", "/echo").body("ping").served_by("/echo"),
+        get("application", "users-show", "/users/show").served_by("/users/show"),
+        get("application", "app-index", "/app/index.html").served_by("/app/index.html"),
+        get("application", "hey", "/hey").served_by("/hey"),
+        get("application", "app1", "/app1").served_by("/app1"),
+        Fixture::new("application", "app1-head", Method::HEAD, "/app1").served_by("/app1"),
+        get("application", "test", "/test").served_by("/test"),
+        // server
+        get("server", "quit", "/quit").served_by("/quit"),
+        // extractors
+        get("extractors", "posts", "/posts/1/alice").served_by("/posts/{post_id}/{friend}"),
+        get("extractors", "query", "/query?name=alice").served_by("/query"),
+        post("extractors", "json", "/json")
+            .header("content-type", "application/json")
+            .body(r#"{"name":"alice"}"#)
+            .served_by("/json"),
+        post("extractors", "json-invalid", "/json")
+            .header("content-type", "application/json")
+            .body("{")
+            .served_by("/json"),
+        post("extractors", "json-idempotency-key", "/json")
+            .header("content-type", "application/json")
+            .header("idempotency-key", "7f3c")
+            .body(r#"{"name":"alice"}"#)
+            .served_by("/json"),
+        post("extractors", "form", "/form")
+            .header("content-type", "application/x-www-form-urlencoded")
+            .body("username=alice")
+            .served_by("/form"),
+        get("extractors", "count", "/count").served_by("/count"),
+        get("extractors", "add-one", "/add-one").served_by("/add-one"),
+        // handlers
+        get("handlers", "responder", "/responder").served_by("/responder"),
+        get("handlers", "responder2", "/responder2").served_by("/responder2"),
+        get("handlers", "custom-type", "/custom-type").served_by("/custom-type"),
+        get("handlers", "stream", "/stream").served_by("/stream"),
+        get("handlers", "stream-ndjson", "/stream")
+            .header("accept", "application/x-ndjson")
+            .served_by("/stream"),
+        get("handlers", "either", "/either").served_by("/either"),
+        // errors
+        get("errors", "static-index", "/static-index").served_by("/static-index"),
+        get("errors", "custom-error", "/custom-error").served_by("/custom-error"),
+        get("errors", "custom-error-html", "/custom-error")
+            .header("accept", "text/html")
+            .served_by("/custom-error"),
+        get("errors", "custom-error-enum", "/custom-error-enum").served_by("/custom-error-enum"),
+        get("errors", "map-err", "/map-err").served_by("/map-err"),
+        get("errors", "err-logging", "/err-logging").served_by("/err-logging"),
+        // url_dispatch
+        get("url_dispatch", "index", "/url-dispatch").served_by("/url-dispatch"),
+        post("url_dispatch", "user", "/url-dispatch/user").served_by("/url-dispatch/user"),
+        get("url_dispatch", "prefix", "/url-dispatch/prefix").served_by("/url-dispatch/prefix"),
+        get("url_dispatch", "user-name", "/url-dispatch/user/alice")
+            .header("content-type", "application/json")
+            .served_by("/url-dispatch/user/{name}"),
+        get("url_dispatch", "path", "/url-dispatch/path")
+            .header("content-type", "text/plain")
+            .served_by("/url-dispatch/path"),
+        get("url_dispatch", "show", "/url-dispatch/show/7").served_by("/url-dispatch/show/{id}"),
+        get("url_dispatch", "match", "/url-dispatch/match/1/2").served_by("/url-dispatch/match/{v1}/{v2}"),
+        get("url_dispatch", "path-info", "/url-dispatch/path/alice/7")
+            .served_by("/url-dispatch/path/{username}/{id}"),
+        get("url_dispatch", "path-info-v2", "/url-dispatch/v2/path/alice/7")
+            .served_by("/url-dispatch/v2/path/{username}/{id}"),
+        get("url_dispatch", "generate-resource-urls", "/url-dispatch/generate-resource-urls/1/2/3")
+            .served_by("/url-dispatch/generate-resource-urls/{a}/{b}/{c}"),
+        get("url_dispatch", "generate-resource-url", "/url-dispatch/generate-resource-url")
+            .served_by("/url-dispatch/generate-resource-url"),
+        get("url_dispatch", "external-resources", "/url-dispatch/external-resources")
+            .served_by("/url-dispatch/external-resources"),
+        get("url_dispatch", "path-normalize", "/url-dispatch/path-normalize")
+            .served_by("/url-dispatch/path-normalize"),
+        get("url_dispatch", "path-normalize-redirect", "/url-dispatch/path-normalize/?lang=ja"),
+        get("url_dispatch", "no-redirect-to-missing", "/url-dispatch/nonexistent/"),
+        post("url_dispatch", "no-redirect-to-other-method", "/url-dispatch/show/7/"),
+        // testing
+        get("testing", "index", "/testing").served_by("/testing"),
+        get("testing", "app-data", "/testing/app-data").served_by("/testing/app-data"),
+        get("testing", "stream", "/testing/stream").served_by("/testing/stream"),
+        // jobs
+        post("jobs", "enqueue-echo", "/jobs/echo").body("hello").served_by("/jobs/echo"),
+        post("jobs", "enqueue-echo-bulk", "/jobs/echo/bulk")
+            .header("content-type", "application/x-ndjson")
+            .body("{\"body\":\"a\"}\n{\"body\":\"b\"}\n")
+            .served_by("/jobs/echo/bulk"),
+        post("jobs", "enqueue-echo-bulk-invalid", "/jobs/echo/bulk")
+            .header("content-type", "application/x-ndjson")
+            .body("{\"body\":\"a\"}\nnot json\n")
+            .served_by("/jobs/echo/bulk"),
+        get("jobs", "list", "/admin/jobs").identity("ops.internal").served_by("/admin/jobs"),
+        get("jobs", "list-anonymous", "/admin/jobs").served_by("/admin/jobs"),
+        get("jobs", "list-untrusted", "/admin/jobs").identity("alice").served_by("/admin/jobs"),
+        get("jobs", "export", "/admin/jobs/export").identity("ops.internal").served_by("/admin/jobs/export"),
+        post("jobs", "retry-missing", "/admin/jobs/1/retry")
+            .identity("ops.internal")
+            .served_by("/admin/jobs/{id}/retry"),
+        post("jobs", "retry-anonymous", "/admin/jobs/1/retry").served_by("/admin/jobs/{id}/retry"),
+        // health
+        get("health", "healthz", "/healthz").served_by("/healthz"),
+        get("health", "readyz", "/readyz").served_by("/readyz"),
+        // internal: the guard only admits trusted client certificates, so without one
+        // the snapshot pins the 404.
+        get("internal", "whoami-anonymous", "/internal/whoami").served_by("/internal/whoami"),
+        // audit: every app starts with an empty log
+        get("audit", "query", "/admin/audit?from=0&route=/echo")
+            .identity("ops.internal")
+            .served_by("/admin/audit"),
+        get("audit", "query-anonymous", "/admin/audit").served_by("/admin/audit"),
+        // sites from sites.toml
+        get("sites", "www-index", "/").header("host", "www.rust-lang.org"),
+        get("sites", "www-outside-subset", "/hello").header("host", "www.rust-lang.org"),
+        get("sites", "users-index", "/").header("host", "users.rust-lang.org:8080"),
+        get("sites", "users-wildcard-health", "/healthz")
+            .header("host", "alice.users.rust-lang.org")
+            .served_by("/healthz"),
+        // default service
+        get("default", "not-found", "/missing"),
+        Fixture::new("default", "method-not-allowed", Method::DELETE, "/hello").served_by("/hello"),
+    ]
+}
@@ -0,0 +1,254 @@
+//! Boots the full application from `create_app` and replays the route fixtures.
+//!
+//! Responses are compared with the snapshots in `tests/golden`; run with
+//! `UPDATE_GOLDEN=1` to rewrite them after an intended change.
+
+mod common;
+
+use actix_example::flags::FlagStore;
+use actix_example::idempotency::IDEMPOTENT_REPLAYED;
+use actix_example::tls::ClientCert;
+use actix_example::{create_app, AppData};
+use actix_http::Request;
+use actix_web::dev::{Service, ServiceResponse};
+use actix_web::introspection::{IntrospectionNode, IntrospectionTree, ResourceType};
+use actix_web::{guard, http, test, web, App, HttpMessage, HttpResponse};
+
+use std::fmt::Write as _;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use common::{fixtures, Fixture};
+
+/// Headers worth pinning in snapshots; dates and lengths are left out.
+const SNAPSHOT_HEADERS: &[http::header::HeaderName] = &[
+    http::header::CONTENT_TYPE,
+    http::header::CONTENT_LANGUAGE,
+    http::header::VARY,
+    http::header::LOCATION,
+    http::header::ALLOW,
+];
+
+struct Served {
+    pattern: Option<String>,
+    snapshot: String,
+}
+
+async fn serve(fixture: &Fixture) -> Served {
+    let app = test::init_service(create_app(AppData::ephemeral().await)).await;
+
+    let mut req = test::TestRequest::default()
+        .method(fixture.method.clone())
+        .uri(fixture.uri);
+    for &(name, value) in &fixture.headers {
+        req = req.insert_header((name, value));
+    }
+    let req = req.set_payload(fixture.body).to_request();
+    if let Some(identity) = fixture.identity {
+        req.extensions_mut().insert(ClientCert {
+            subject: format!("CN={}", identity),
+            common_name: Some(identity.to_owned()),
+            sans: vec![],
+        });
+    }
+    let res = test::call_service(&app, req).await;
+    let pattern = res.request().match_pattern();
+
+    let mut snapshot = format!("{} {}\n{}\n", fixture.method, fixture.uri, res.status());
+    for name in SNAPSHOT_HEADERS {
+        if let Some(value) = res.headers().get(name) {
+            writeln!(snapshot, "{}: {}", name, value.to_str().unwrap()).unwrap();
+        }
+    }
+    let body = test::read_body(res).await;
+    write!(snapshot, "\n{}", String::from_utf8_lossy(&body)).unwrap();
+
+    Served { pattern, snapshot }
+}
+
+fn golden_path(fixture: &Fixture) -> PathBuf {
+    PathBuf::from("tests/golden")
+        .join(fixture.module)
+        .join(format!("{}.txt", fixture.name))
+}
+
+#[actix_web::test]
+async fn test_golden_responses() {
+    let update = std::env::var_os("UPDATE_GOLDEN").is_some();
+    let mut mismatches = vec![];
+
+    for fixture in fixtures() {
+        let served = serve(&fixture).await;
+        let path = golden_path(&fixture);
+
+        if update {
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, &served.snapshot).unwrap();
+            continue;
+        }
+        match std::fs::read_to_string(&path) {
+            Ok(golden) if golden == served.snapshot => {}
+            Ok(golden) => mismatches.push(format!(
+                "{}:\n--- golden\n{}\n--- actual\n{}",
+                path.display(),
+                golden,
+                served.snapshot
+            )),
+            Err(_) => mismatches.push(format!("{}: missing, run with UPDATE_GOLDEN=1", path.display())),
+        }
+    }
+
+    assert!(mismatches.is_empty(), "{}", mismatches.join("\n\n"));
+}
+
+/// Full paths claimed by more than one registration: under different patterns (such as a
+/// scope's `"/"` next to a resource's `"/"`), or under the same one, which the tree flags
+/// as shadowed. A scope's own `""` route shares the scope's node and is reported too, so
+/// register such routes on the full path instead.
+fn collisions(node: &IntrospectionNode, found: &mut Vec<String>) {
+    if matches!(node.kind, ResourceType::Resource) && node.patterns.len() > 1 {
+        found.push(format!(
+            "{} is registered as {:?}, guarded by {:?}",
+            node.full_path, node.patterns, node.guards
+        ));
+    }
+    if node.potentially_unreachable {
+        found.push(format!("{} is shadowed: {:?}", node.full_path, node.reachability_notes));
+    }
+    for child in &node.children {
+        collisions(child, found);
+    }
+}
+
+async fn introspect<S, B>(app: &S) -> web::Data<IntrospectionTree>
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+{
+    let res = test::call_service(app, test::TestRequest::default().to_request()).await;
+    res.request().app_data::<web::Data<IntrospectionTree>>().unwrap().clone()
+}
+
+#[actix_web::test]
+async fn test_route_collisions() {
+    let mut errors = vec![];
+
+    // Every fixture must reach the route its module registered, not one shadowing it.
+    for fixture in fixtures() {
+        let served = serve(&fixture).await;
+        if served.pattern.as_deref() != fixture.pattern {
+            errors.push(format!(
+                "{}/{}: {} {} served by {:?}, expected {:?}",
+                fixture.module, fixture.name, fixture.method, fixture.uri, served.pattern, fixture.pattern
+            ));
+        }
+    }
+
+    // No path may be registered twice, whichever modules did it.
+    let app = test::init_service(create_app(AppData::ephemeral().await)).await;
+    collisions(&introspect(&app).await.root, &mut errors);
+
+    assert!(errors.is_empty(), "{}", errors.join("\n"));
+}
+
+#[actix_web::test]
+async fn test_collisions_between_scopes_and_resources() {
+    // The host-guarded scopes `application` used to register next to its `#[get("/")]` index.
+    let app = test::init_service(
+        App::new()
+            .service(
+                web::scope("/")
+                    .guard(guard::Header("Host", "www.rust-lang.org"))
+                    .route("", web::to(|| async { HttpResponse::Ok().body("www") })),
+            )
+            .service(
+                web::scope("/")
+                    .guard(guard::Header("Host", "users.rust-lang.org"))
+                    .route("", web::to(|| async { HttpResponse::Ok().body("user") })),
+            )
+            .route("/", web::get().to(|| async { "index" }))
+            .route("/hello", web::get().to(|| async { "hello" }))
+            .route("/hello", web::get().to(|| async { "hello again" })),
+    )
+    .await;
+
+    let mut found = vec![];
+    collisions(&introspect(&app).await.root, &mut found);
+    let paths: Vec<_> = found.iter().map(|found| found.split(' ').next().unwrap()).collect();
+    assert_eq!(paths, ["/", "/hello"], "{:?}", found);
+}
+
+#[actix_web::test]
+async fn test_route_flags() {
+    let path = std::env::temp_dir().join(format!("actix-example-route-flags-{}.toml", std::process::id()));
+    std::fs::write(
+        &path,
+        r#"
+            [flags.show]
+            enabled = false
+            route = "/url-dispatch/show/{id}"
+
+            [flags.user-writes]
+            enabled = false
+            route = "/url-dispatch/user/{name}"
+            methods = ["PUT"]
+        "#,
+    )
+    .unwrap();
+    let mut data = AppData::ephemeral().await;
+    data.flags = web::Data::new(FlagStore::open(&path, Duration::ZERO).unwrap());
+    let app = test::init_service(create_app(data)).await;
+
+    let status = |method: http::Method, uri: &'static str| {
+        let req = test::TestRequest::default()
+            .method(method)
+            .uri(uri)
+            .insert_header(("content-type", "application/json"))
+            .set_payload("{}")
+            .to_request();
+        let app = &app;
+        async move { test::call_service(app, req).await.status() }
+    };
+
+    assert_eq!(status(http::Method::GET, "/url-dispatch/show/7").await, http::StatusCode::NOT_FOUND);
+    assert_eq!(status(http::Method::GET, "/url-dispatch/user/alice").await, http::StatusCode::OK);
+    assert_eq!(status(http::Method::PUT, "/url-dispatch/user/alice").await, http::StatusCode::NOT_FOUND);
+    assert_eq!(status(http::Method::GET, "/hello").await, http::StatusCode::OK);
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[actix_web::test]
+async fn test_idempotency_keys() {
+    let app = test::init_service(create_app(AppData::ephemeral().await)).await;
+    let call = |uri: &'static str, body: &'static str| {
+        let req = test::TestRequest::post()
+            .uri(uri)
+            .insert_header(("content-type", "application/json"))
+            .insert_header(("idempotency-key", "k1"))
+            .set_payload(body)
+            .to_request();
+        req.extensions_mut().insert(ClientCert {
+            subject: "CN=ops.internal".to_owned(),
+            common_name: Some("ops.internal".to_owned()),
+            sans: vec![],
+        });
+        let app = &app;
+        async move {
+            let res = test::call_service(app, req).await;
+            let replayed = res.headers().contains_key(IDEMPOTENT_REPLAYED);
+            (res.status().as_u16(), replayed)
+        }
+    };
+
+    assert_eq!(call("/json", r#"{"name":"alice"}"#).await, (200, false));
+    assert_eq!(call("/json", r#"{"name":"alice"}"#).await, (200, true));
+    // Keys belong to the route that served them.
+    assert_eq!(call("/echo", r#"{"name":"alice"}"#).await, (200, false));
+    // Another path of the same route is another request.
+    assert_eq!(call("/admin/jobs/1/retry", "").await, (404, false));
+    assert_eq!(call("/admin/jobs/1/retry", "").await, (404, true));
+    assert_eq!(call("/admin/jobs/2/retry", "").await, (409, false));
+    // Nothing is registered here, so nothing is stored.
+    assert_eq!(call("/missing", "").await, (404, false));
+    assert_eq!(call("/missing", "").await, (404, false));
+}
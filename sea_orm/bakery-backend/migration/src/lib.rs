@@ -2,6 +2,10 @@ pub use sea_orm_migration::prelude::*;
 
 mod m20230429_000001_create_bakery_table;
 mod m20230429_000002_create_chef_table;
+mod m20230501_000001_add_bakery_constraints;
+mod m20230502_000001_type_chef_contact_details;
+mod m20230503_000001_add_deleted_at;
+mod m20230504_000001_create_history_tables;
 
 pub struct Migrator;
 
@@ -11,6 +15,65 @@ impl MigratorTrait for Migrator {
         vec![
             Box::new(m20230429_000001_create_bakery_table::Migration),
             Box::new(m20230429_000002_create_chef_table::Migration),
+            Box::new(m20230501_000001_add_bakery_constraints::Migration),
+            Box::new(m20230502_000001_type_chef_contact_details::Migration),
+            Box::new(m20230503_000001_add_deleted_at::Migration),
+            Box::new(m20230504_000001_create_history_tables::Migration),
         ]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Files named like `m20230429_000001_create_bakery_table.rs`, without the extension.
+    fn migration_files() -> Vec<String> {
+        let mut files: Vec<String> = std::fs::read_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/src"))
+            .unwrap()
+            .filter_map(|entry| {
+                let file = entry.unwrap().file_name().into_string().ok()?;
+                let stem = file.strip_suffix(".rs")?;
+                let digit = stem.chars().nth(1).is_some_and(|c| c.is_ascii_digit());
+                (stem.starts_with('m') && digit).then(|| stem.to_owned())
+            })
+            .collect();
+        files.sort();
+        files
+    }
+
+    #[test]
+    fn test_names_match_files() {
+        let names: Vec<String> = Migrator::migrations()
+            .iter()
+            .map(|migration| migration.name().to_owned())
+            .collect();
+        assert_eq!(names, migration_files());
+    }
+
+    #[test]
+    fn test_strictly_ordered() {
+        let versions: Vec<String> = Migrator::migrations()
+            .iter()
+            .map(|migration| {
+                // `m20230429_000001`
+                let name = migration.name();
+                let version = name.get(..16).unwrap_or(name);
+                assert!(
+                    version[1..].chars().all(|c| c.is_ascii_digit() || c == '_'),
+                    "{} does not start with mYYYYMMDD_NNNNNN",
+                    name
+                );
+                version.to_owned()
+            })
+            .collect();
+        for pair in versions.windows(2) {
+            assert!(
+                pair[0] < pair[1],
+                "{} does not run after {}",
+                pair[1],
+                pair[0]
+            );
+        }
+    }
+}
@@ -0,0 +1,169 @@
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::{ConnectionTrait, DbBackend, Statement};
+
+use super::m20230429_000001_create_bakery_table::Bakery;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20230501_000001_add_bakery_constraints"
+    }
+}
+
+/// `(table, name, condition)` of every check. The profit margin bounds are also the
+/// widest range the app can be configured to accept.
+const CHECKS: [(&str, &str, &str); 3] = [
+    ("bakery", "bakery_name_not_blank", "LENGTH(TRIM(name)) > 0"),
+    (
+        "bakery",
+        "bakery_profit_margin_range",
+        "profit_margin >= 0 AND profit_margin <= 100",
+    ),
+    ("chef", "chef_name_not_blank", "LENGTH(TRIM(name)) > 0"),
+];
+
+const NAME_INDEX: &str = "idx_bakery_name";
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        let backend = manager.get_database_backend();
+
+        // Tidy what can be tidied without guessing, refuse what cannot.
+        for sql in [
+            "UPDATE bakery SET name = TRIM(name)",
+            "UPDATE chef SET name = TRIM(name)",
+        ] {
+            db.execute_unprepared(sql).await?;
+        }
+        let conflicts = db
+            .query_all(Statement::from_string(
+                backend,
+                "SELECT name FROM bakery WHERE name = '' \
+                 UNION ALL SELECT name FROM chef WHERE name = '' \
+                 UNION ALL SELECT name FROM bakery GROUP BY name HAVING COUNT(*) > 1 \
+                 UNION ALL SELECT name FROM bakery WHERE profit_margin < 0 OR profit_margin > 100"
+                    .to_owned(),
+            ))
+            .await?;
+        if !conflicts.is_empty() {
+            return Err(DbErr::Migration(
+                "blank chef or bakery names, bakeries sharing a name, or profit margins \
+                 outside 0 to 100 must be fixed first"
+                    .to_owned(),
+            ));
+        }
+
+        manager
+            .create_index(
+                Index::create()
+                    .name(NAME_INDEX)
+                    .table(Bakery::Table)
+                    .col(Bakery::Name)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        for (table, name, condition) in CHECKS {
+            match backend {
+                // SQLite only takes checks in CREATE TABLE, and rebuilding `bakery` would
+                // cascade into `chef`, so triggers do the checking there.
+                DbBackend::Sqlite => {
+                    for event in ["INSERT", "UPDATE"] {
+                        db.execute_unprepared(&format!(
+                            "CREATE TRIGGER {name}_{event} BEFORE {event} ON {table} \
+                             WHEN NOT ({condition}) \
+                             BEGIN SELECT RAISE(ABORT, 'CHECK constraint failed: {name}'); END",
+                            name = name,
+                            event = event.to_lowercase(),
+                            table = table,
+                            condition = condition
+                                .replace("name", "NEW.name")
+                                .replace("profit_margin", "NEW.profit_margin"),
+                        ))
+                        .await?;
+                    }
+                }
+                _ => {
+                    db.execute_unprepared(&format!(
+                        "ALTER TABLE {} ADD CONSTRAINT {} CHECK ({})",
+                        table, name, condition
+                    ))
+                    .await?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        let backend = manager.get_database_backend();
+
+        for (table, name, _) in CHECKS {
+            match backend {
+                DbBackend::Sqlite => {
+                    for event in ["insert", "update"] {
+                        db.execute_unprepared(&format!(
+                            "DROP TRIGGER IF EXISTS {}_{}",
+                            name, event
+                        ))
+                        .await?;
+                    }
+                }
+                DbBackend::MySql => {
+                    db.execute_unprepared(&format!("ALTER TABLE {} DROP CHECK {}", table, name))
+                        .await?;
+                }
+                DbBackend::Postgres => {
+                    db.execute_unprepared(&format!(
+                        "ALTER TABLE {} DROP CONSTRAINT {}",
+                        table, name
+                    ))
+                    .await?;
+                }
+            }
+        }
+
+        manager
+            .drop_index(
+                Index::drop()
+                    .name(NAME_INDEX)
+                    .table(Bakery::Table)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sea_orm_migration::sea_orm::{ConnectionTrait, Database, Statement};
+    use sea_orm_migration::MigratorTrait;
+
+    #[async_std::test]
+    async fn test_refuses_out_of_range_margins() {
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+        crate::Migrator::up(&db, Some(2)).await.unwrap();
+        db.execute(Statement::from_string(
+            db.get_database_backend(),
+            "INSERT INTO bakery (name, profit_margin) VALUES ('La Boulangerie', 150)".to_owned(),
+        ))
+        .await
+        .unwrap();
+
+        assert!(crate::Migrator::up(&db, Some(1)).await.is_err());
+        let row = db
+            .query_one(Statement::from_string(
+                db.get_database_backend(),
+                "SELECT profit_margin FROM bakery".to_owned(),
+            ))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(row.try_get::<f64>("", "profit_margin").unwrap(), 150.0);
+    }
+}
@@ -0,0 +1,164 @@
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::{ConnectionTrait, JsonValue};
+use serde_json::json;
+
+use super::m20230429_000002_create_chef_table::Chef;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20230502_000001_type_chef_contact_details"
+    }
+}
+
+/// Contact details used to be `{ "email", "phone", "address" }`, each an optional string.
+/// They are now `{ "emails": [..], "phones": [..], "address", "preferred" }`; the column
+/// itself stays JSON, so only the rows change.
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        rewrite(manager, |id, old| {
+            let field = |key: &str| match old.get(key) {
+                None | Some(JsonValue::Null) => Ok(None),
+                Some(JsonValue::String(value)) if value.trim().is_empty() => Ok(None),
+                Some(JsonValue::String(value)) => Ok(Some(value.trim().to_owned())),
+                Some(_) => Err(unexpected(id, old)),
+            };
+            if old.get("emails").is_some() || old.get("phones").is_some() {
+                // Already in the new shape.
+                return Ok(None);
+            }
+            let shape = old.as_object().ok_or_else(|| unexpected(id, old))?;
+            if shape
+                .keys()
+                .any(|key| !["email", "phone", "address"].contains(&key.as_str()))
+            {
+                return Err(unexpected(id, old));
+            }
+            let email = field("email")?.map(|email| email.to_lowercase());
+            Ok(Some(json!({
+                "emails": email.into_iter().collect::<Vec<_>>(),
+                "phones": field("phone")?.into_iter().collect::<Vec<_>>(),
+                "address": field("address")?,
+                "preferred": null,
+            })))
+        })
+        .await
+    }
+
+    /// Keeps only the first email and phone of each chef, and forgets preferred channels.
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        rewrite(manager, |_, new| {
+            let first = |key: &str| new.get(key).and_then(|values| values.get(0)).cloned();
+            Ok(Some(json!({
+                "email": first("emails"),
+                "phone": first("phones"),
+                "address": new.get("address"),
+            })))
+        })
+        .await
+    }
+}
+
+fn unexpected(id: i32, details: &JsonValue) -> DbErr {
+    DbErr::Migration(format!(
+        "contact details of chef {} are not in the expected shape: {}",
+        id, details
+    ))
+}
+
+/// Replaces the contact details of every chef that has some with what `convert` makes
+/// of them, where it makes anything.
+async fn rewrite<F>(manager: &SchemaManager<'_>, convert: F) -> Result<(), DbErr>
+where
+    F: Fn(i32, &JsonValue) -> Result<Option<JsonValue>, DbErr> + Send + Sync,
+{
+    let db = manager.get_connection();
+    let backend = manager.get_database_backend();
+    let select = Query::select()
+        .columns([Chef::Id, Chef::ContactDetails])
+        .from(Chef::Table)
+        .and_where(Expr::col(Chef::ContactDetails).is_not_null())
+        .to_owned();
+
+    for row in db.query_all(backend.build(&select)).await? {
+        let id: i32 = row.try_get("", &Chef::Id.to_string())?;
+        let details: JsonValue = row.try_get("", &Chef::ContactDetails.to_string())?;
+        if let Some(details) = convert(id, &details)? {
+            let update = Query::update()
+                .table(Chef::Table)
+                .value(Chef::ContactDetails, details)
+                .and_where(Expr::col(Chef::Id).eq(id))
+                .to_owned();
+            db.execute(backend.build(&update)).await?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sea_orm_migration::sea_orm::{Database, Statement};
+
+    async fn contact_details(db: &impl ConnectionTrait) -> Vec<JsonValue> {
+        let select = Query::select()
+            .column(Chef::ContactDetails)
+            .from(Chef::Table)
+            .order_by(Chef::Id, Order::Asc)
+            .to_owned();
+        db.query_all(db.get_database_backend().build(&select))
+            .await
+            .unwrap()
+            .iter()
+            .map(|row| {
+                row.try_get("", "contact_details")
+                    .unwrap_or(JsonValue::Null)
+            })
+            .collect()
+    }
+
+    #[async_std::test]
+    async fn test_backfill() {
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+        crate::Migrator::up(&db, Some(3)).await.unwrap();
+        db.execute(Statement::from_string(
+            db.get_database_backend(),
+            r#"INSERT INTO bakery (name, profit_margin) VALUES ('La Boulangerie', 10);
+               INSERT INTO chef (name, bakery_id, contact_details) VALUES
+                 ('Jolie', 1, '{"email": "Jolie@Example.com", "phone": "01 23 45 67 89"}'),
+                 ('Charles', 1, '{"address": "1 rue du Pain"}'),
+                 ('Madeleine', 1, NULL)"#
+                .to_owned(),
+        ))
+        .await
+        .unwrap();
+
+        crate::Migrator::up(&db, Some(1)).await.unwrap();
+        assert_eq!(
+            contact_details(&db).await,
+            [
+                json!({
+                    "emails": ["jolie@example.com"],
+                    "phones": ["01 23 45 67 89"],
+                    "address": null,
+                    "preferred": null,
+                }),
+                json!({
+                    "emails": [],
+                    "phones": [],
+                    "address": "1 rue du Pain",
+                    "preferred": null,
+                }),
+                JsonValue::Null,
+            ]
+        );
+
+        crate::Migrator::down(&db, Some(1)).await.unwrap();
+        assert_eq!(
+            contact_details(&db).await[0],
+            json!({ "email": "jolie@example.com", "phone": "01 23 45 67 89", "address": null })
+        );
+    }
+}
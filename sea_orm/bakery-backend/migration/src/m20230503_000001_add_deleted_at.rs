@@ -0,0 +1,53 @@
+use sea_orm_migration::prelude::*;
+
+use super::m20230429_000001_create_bakery_table::Bakery;
+use super::m20230429_000002_create_chef_table::Chef;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20230503_000001_add_deleted_at"
+    }
+}
+
+/// Deleting a bakery or chef now sets `deleted_at` and keeps the row, so it can be
+/// restored until it is purged.
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // SQLite alters one column per statement, hence a statement per table.
+        for table in [Bakery::Table.into_iden(), Chef::Table.into_iden()] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(table)
+                        .add_column(
+                            ColumnDef::new(SoftDelete::DeletedAt).timestamp_with_time_zone(),
+                        )
+                        .to_owned(),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for table in [Bakery::Table.into_iden(), Chef::Table.into_iden()] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(table)
+                        .drop_column(SoftDelete::DeletedAt)
+                        .to_owned(),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Iden)]
+enum SoftDelete {
+    DeletedAt,
+}
@@ -0,0 +1,141 @@
+use sea_orm_migration::prelude::*;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20230504_000001_create_history_tables"
+    }
+}
+
+/// A table of versions for each of `bakery` and `chef`. They have no foreign key to the
+/// rows they track, which they outlive once those are purged, but each row's versions are
+/// unique, so two writers numbering the same version cannot both commit.
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(history_table(BakeryHistory::Table, BakeryHistory::BakeryId))
+            .await?;
+        manager
+            .create_index(version_index(
+                "idx_bakery_history_version",
+                BakeryHistory::Table,
+                BakeryHistory::BakeryId,
+            ))
+            .await?;
+        manager
+            .create_table(history_table(ChefHistory::Table, ChefHistory::ChefId))
+            .await?;
+        manager
+            .create_index(version_index(
+                "idx_chef_history_version",
+                ChefHistory::Table,
+                ChefHistory::ChefId,
+            ))
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(ChefHistory::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(BakeryHistory::Table).to_owned())
+            .await
+    }
+}
+
+fn history_table(
+    table: impl Iden + 'static,
+    tracked_id: impl Iden + 'static,
+) -> TableCreateStatement {
+    Table::create()
+        .table(table)
+        .if_not_exists()
+        .col(
+            ColumnDef::new(History::Id)
+                .integer()
+                .not_null()
+                .auto_increment()
+                .primary_key(),
+        )
+        .col(ColumnDef::new(tracked_id).integer().not_null())
+        .col(ColumnDef::new(History::Version).integer().not_null())
+        .col(ColumnDef::new(History::Operation).string_len(8).not_null())
+        .col(ColumnDef::new(History::OldValues).json())
+        .col(ColumnDef::new(History::NewValues).json())
+        .col(ColumnDef::new(History::Actor).string().not_null())
+        .col(
+            ColumnDef::new(History::ChangedAt)
+                .timestamp_with_time_zone()
+                .not_null(),
+        )
+        .to_owned()
+}
+
+fn version_index(
+    name: &str,
+    table: impl Iden + 'static,
+    tracked_id: impl Iden + 'static,
+) -> IndexCreateStatement {
+    Index::create()
+        .name(name)
+        .table(table)
+        .col(tracked_id)
+        .col(History::Version)
+        .unique()
+        .if_not_exists()
+        .to_owned()
+}
+
+#[derive(Iden)]
+enum BakeryHistory {
+    Table,
+    BakeryId,
+}
+
+#[derive(Iden)]
+enum ChefHistory {
+    Table,
+    ChefId,
+}
+
+#[derive(Iden)]
+enum History {
+    Id,
+    Version,
+    Operation,
+    OldValues,
+    NewValues,
+    Actor,
+    ChangedAt,
+}
+
+#[cfg(test)]
+mod tests {
+    use sea_orm_migration::sea_orm::{ConnectionTrait, Database, Statement};
+    use sea_orm_migration::MigratorTrait;
+
+    #[async_std::test]
+    async fn test_versions_are_unique() {
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+        crate::Migrator::up(&db, None).await.unwrap();
+
+        for (table, tracked_id) in [("bakery_history", "bakery_id"), ("chef_history", "chef_id")] {
+            let insert = |version: i32| {
+                Statement::from_string(
+                    db.get_database_backend(),
+                    format!(
+                        "INSERT INTO {} ({}, version, operation, actor, changed_at) \
+                         VALUES (1, {}, 'insert', 'tester', '2023-05-04 00:00:00+00:00')",
+                        table, tracked_id, version
+                    ),
+                )
+            };
+            db.execute(insert(1)).await.unwrap();
+            db.execute(insert(2)).await.unwrap();
+            assert!(db.execute(insert(2)).await.is_err(), "{}", table);
+        }
+    }
+}
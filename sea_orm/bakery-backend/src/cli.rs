@@ -0,0 +1,1018 @@
+//! The `bakery`, `chef`, `db` and `fixtures` subcommands.
+
+use migration::{Migrator, MigratorTrait};
+use sea_orm::prelude::DateTimeUtc;
+use sea_orm::*;
+use sea_orm_migration::MigrationStatus;
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+
+use crate::config::{Args, ConfigError};
+use crate::contact::{Channel, ContactDetails};
+use crate::entities::chef;
+use crate::fixtures::{self, FixtureError, Fixtures};
+use crate::history;
+use crate::listing::{
+    BakeryFilter, BakeryQuery, BakerySort, ChefFilter, ChefQuery, ChefSort, Cursor, Listing, Page,
+    Sort, DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE,
+};
+use crate::output::{ChefRow, Format, MigrationRow, Output};
+use crate::repository::{
+    BakeryChanges, BakeryRepository, ChefRepository, NewBakery, NewChef, SeaOrmRepository,
+    Transactional,
+};
+use crate::soft_delete::{self, Scope};
+use crate::validation::ValidationError;
+
+const EXIT_CODES: &str = "EXIT CODES:
+    0  success
+    1  database error
+    2  invalid arguments or configuration
+    3  no such bakery or chef
+    4  the change breaks a rule for bakeries or chefs";
+
+#[derive(Debug, clap::Parser)]
+#[clap(about = "Manage bakeries and their chefs", after_help = EXIT_CODES)]
+pub struct Cli {
+    #[clap(flatten)]
+    pub args: Args,
+
+    #[clap(long, arg_enum, default_value = "table", global = true)]
+    pub format: Format,
+
+    /// Who changes are recorded as made by, `$USER` if not given.
+    #[clap(long, env = "BAKERY_ACTOR", global = true)]
+    pub actor: Option<String>,
+
+    #[clap(subcommand)]
+    pub command: Command,
+}
+
+impl Cli {
+    pub fn actor(&self) -> String {
+        self.actor
+            .clone()
+            .or_else(|| std::env::var("USER").ok())
+            .unwrap_or_else(|| history::DEFAULT_ACTOR.to_owned())
+    }
+}
+
+#[derive(Debug, clap::Subcommand)]
+pub enum Command {
+    /// Open, change and close bakeries.
+    #[clap(subcommand)]
+    Bakery(BakeryCommand),
+    /// Hire, move, fire and list chefs.
+    #[clap(subcommand)]
+    Chef(ChefCommand),
+    /// Apply migrations, reset the schema or show which migrations ran.
+    #[clap(subcommand)]
+    Db(DbCommand),
+    /// Load bakeries and chefs from files, or make up many of them.
+    #[clap(subcommand)]
+    Fixtures(FixturesCommand),
+}
+
+#[derive(Debug, clap::Subcommand)]
+pub enum BakeryCommand {
+    Add {
+        name: String,
+        #[clap(long, default_value = "0.0", allow_hyphen_values = true)]
+        profit_margin: f64,
+    },
+    /// Lists bakeries a page at a time, by id unless sorted otherwise.
+    List {
+        /// Only bakeries with this in their name, in any case of ASCII letters.
+        #[clap(long)]
+        name_contains: Option<String>,
+        #[clap(long, allow_hyphen_values = true)]
+        min_profit_margin: Option<f64>,
+        #[clap(long, allow_hyphen_values = true)]
+        max_profit_margin: Option<f64>,
+        /// Comma-separated keys among id, name and profit_margin, each reversed by a
+        /// leading `-`.
+        #[clap(long, use_value_delimiter = true, allow_hyphen_values = true)]
+        sort: Vec<Sort<BakerySort>>,
+        #[clap(flatten)]
+        scope: ScopeArgs,
+        #[clap(flatten)]
+        page: PageArgs,
+    },
+    /// Shows a bakery and its chefs.
+    Show { id: i32 },
+    Update {
+        id: i32,
+        #[clap(long)]
+        name: Option<String>,
+        #[clap(long, allow_hyphen_values = true)]
+        profit_margin: Option<f64>,
+    },
+    /// Deletes a bakery along with its chefs, until restored or purged.
+    Delete { id: i32 },
+    /// Brings back a deleted bakery and the chefs deleted with it.
+    Restore { id: i32 },
+    /// Shows every change to a bakery, or the bakery as it was at some time.
+    History {
+        id: i32,
+        /// A time such as `2023-05-04T12:00:00Z`.
+        #[clap(long)]
+        at: Option<DateTimeUtc>,
+    },
+}
+
+#[derive(Debug, clap::Subcommand)]
+pub enum ChefCommand {
+    Hire {
+        name: String,
+        /// Id of the bakery to work at.
+        #[clap(long)]
+        bakery: i32,
+        #[clap(flatten)]
+        contact: ContactArgs,
+    },
+    /// Moves a chef to another bakery.
+    Move {
+        id: i32,
+        /// Id of the bakery to work at from now on.
+        #[clap(long)]
+        bakery: i32,
+    },
+    /// Fires a chef, until restored or purged.
+    Fire { id: i32 },
+    /// Brings back a fired chef, unless their bakery is deleted.
+    Restore { id: i32 },
+    /// Shows every change to a chef, or the chef as they were at some time.
+    History {
+        id: i32,
+        /// A time such as `2023-05-04T12:00:00Z`.
+        #[clap(long)]
+        at: Option<DateTimeUtc>,
+    },
+    /// Lists chefs a page at a time, by name unless sorted otherwise.
+    List {
+        /// Only the chefs of this bakery.
+        #[clap(long)]
+        bakery: Option<i32>,
+        /// Only chefs with this in their name, in any case of ASCII letters.
+        #[clap(long)]
+        name_contains: Option<String>,
+        /// Comma-separated keys among id, name and bakery_id, each reversed by a leading `-`.
+        #[clap(long, use_value_delimiter = true, allow_hyphen_values = true)]
+        sort: Vec<Sort<ChefSort>>,
+        #[clap(flatten)]
+        scope: ScopeArgs,
+        #[clap(flatten)]
+        page: PageArgs,
+    },
+    /// Finds the chefs reachable at an email address or phone number.
+    #[clap(group = clap::ArgGroup::new("contact").required(true))]
+    Find {
+        #[clap(long, group = "contact")]
+        email: Option<String>,
+        #[clap(long, group = "contact")]
+        phone: Option<String>,
+    },
+}
+
+#[derive(Debug, clap::Args)]
+pub struct ScopeArgs {
+    /// Deleted rows too.
+    #[clap(long, conflicts_with = "only-deleted")]
+    pub with_deleted: bool,
+    /// Only deleted rows.
+    #[clap(long)]
+    pub only_deleted: bool,
+}
+
+impl ScopeArgs {
+    fn scope(&self) -> Scope {
+        match (self.with_deleted, self.only_deleted) {
+            (true, _) => Scope::WithDeleted,
+            (false, true) => Scope::OnlyDeleted,
+            (false, false) => Scope::Active,
+        }
+    }
+}
+
+/// Numbered pages by default; `--keyset` or `--after` follow cursors instead.
+#[derive(Debug, clap::Args)]
+pub struct PageArgs {
+    /// Which page to show, from 1.
+    #[clap(long, conflicts_with_all = &["keyset", "after"], value_parser = clap::value_parser!(u64).range(1..))]
+    pub page: Option<u64>,
+    #[clap(long, default_value_t = DEFAULT_PAGE_SIZE, value_parser = clap::value_parser!(u64).range(1..=MAX_PAGE_SIZE))]
+    pub per_page: u64,
+    /// Shows the first page with a cursor to the next one.
+    #[clap(long)]
+    pub keyset: bool,
+    /// Shows the page after the cursor a previous page ended with.
+    #[clap(long)]
+    pub after: Option<Cursor>,
+}
+
+impl PageArgs {
+    fn page(self) -> Page {
+        match (self.keyset, self.after) {
+            (false, None) => Page::Offset {
+                number: self.page.unwrap_or(1),
+                size: self.per_page,
+            },
+            (_, cursor) => Page::After {
+                cursor,
+                size: self.per_page,
+            },
+        }
+    }
+}
+
+#[derive(Debug, clap::Args)]
+pub struct ContactArgs {
+    /// An email address of the chef; may be repeated.
+    #[clap(long = "email", multiple_occurrences = true)]
+    pub emails: Vec<String>,
+    /// A phone number of the chef; may be repeated.
+    #[clap(long = "phone", multiple_occurrences = true)]
+    pub phones: Vec<String>,
+    #[clap(long)]
+    pub address: Option<String>,
+    /// How the chef would rather be reached.
+    #[clap(long, arg_enum)]
+    pub prefer: Option<Channel>,
+}
+
+impl ContactArgs {
+    /// The contact details given, if any were.
+    fn details(self) -> Option<ContactDetails> {
+        let details = ContactDetails {
+            emails: self.emails,
+            phones: self.phones,
+            address: self.address,
+            preferred: self.prefer,
+        };
+        match details.is_empty() && details.preferred.is_none() {
+            true => None,
+            false => Some(details),
+        }
+    }
+}
+
+#[derive(Debug, clap::Subcommand)]
+pub enum DbCommand {
+    /// Applies pending migrations.
+    Migrate,
+    /// Drops every table and applies all migrations again.
+    Reset {
+        /// Confirms that all data is to be lost.
+        #[clap(long)]
+        yes: bool,
+    },
+    Status,
+    /// Deletes for good the bakeries and chefs deleted more than some days ago.
+    Purge {
+        #[clap(long)]
+        older_than_days: u32,
+        /// Confirms that the rows cannot be restored afterwards.
+        #[clap(long)]
+        yes: bool,
+    },
+}
+
+#[derive(Debug, clap::Subcommand)]
+pub enum FixturesCommand {
+    /// Creates or updates the bakeries and chefs of YAML, JSON or CSV files, matched by
+    /// name. Chefs name their bakery, which may be in another of the files.
+    Load {
+        #[clap(required = true)]
+        files: Vec<PathBuf>,
+    },
+    /// Makes up bakeries and chefs, and loads them or writes them to a file.
+    Generate {
+        #[clap(long, default_value = "100")]
+        bakeries: usize,
+        #[clap(long, default_value = "5")]
+        chefs_per_bakery: usize,
+        /// The same seed makes the same bakeries and chefs.
+        #[clap(long, default_value = "0")]
+        seed: u64,
+        /// A YAML or JSON file to write them to instead.
+        #[clap(long)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Debug)]
+pub enum CliError {
+    Config(ConfigError),
+    Fixture(FixtureError),
+    Db(DbErr),
+    /// A destructive command was not confirmed.
+    Unconfirmed(&'static str),
+    Invalid(ValidationError),
+}
+
+impl CliError {
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            CliError::Db(DbErr::RecordNotFound(_))
+            | CliError::Fixture(FixtureError::UnknownBakery { .. })
+            | CliError::Fixture(FixtureError::DeletedBakery { .. }) => 3,
+            CliError::Db(_) => 1,
+            CliError::Config(_) | CliError::Fixture(_) | CliError::Unconfirmed(_) => 2,
+            CliError::Invalid(_) => 4,
+        }
+    }
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CliError::Config(err) => write!(f, "{}", err),
+            CliError::Fixture(err) => write!(f, "{}", err),
+            CliError::Db(DbErr::RecordNotFound(what)) => write!(f, "no such {}", what),
+            CliError::Db(err) => write!(f, "{}", err),
+            CliError::Unconfirmed(hint) => write!(f, "{}", hint),
+            CliError::Invalid(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for CliError {}
+
+impl From<ConfigError> for CliError {
+    fn from(err: ConfigError) -> Self {
+        CliError::Config(err)
+    }
+}
+
+impl From<FixtureError> for CliError {
+    fn from(err: FixtureError) -> Self {
+        match err {
+            FixtureError::Db(err) => err.into(),
+            err => CliError::Fixture(err),
+        }
+    }
+}
+
+impl From<DbErr> for CliError {
+    fn from(err: DbErr) -> Self {
+        match ValidationError::from_db_err(&err) {
+            Some(invalid) => CliError::Invalid(invalid.clone()),
+            None => CliError::Db(err),
+        }
+    }
+}
+
+/// Runs `command`, recording the changes it makes as made by `actor`.
+pub async fn execute(
+    command: Command,
+    db: &DatabaseConnection,
+    actor: &str,
+) -> Result<Output, CliError> {
+    let repo = SeaOrmRepository::new(db).acting_as(actor);
+    match command {
+        Command::Bakery(command) => bakery_command(command, &repo).await,
+        Command::Chef(command) => chef_command(command, &repo).await,
+        Command::Db(command) => db_command(command, db, &repo).await,
+        Command::Fixtures(command) => fixtures_command(command, &repo).await,
+    }
+}
+
+async fn bakery_command<R>(command: BakeryCommand, repo: &R) -> Result<Output, CliError>
+where
+    R: BakeryRepository + ChefRepository,
+{
+    let output = match command {
+        BakeryCommand::Add {
+            name,
+            profit_margin,
+        } => {
+            let bakery = repo
+                .create_bakery(NewBakery {
+                    name,
+                    profit_margin,
+                })
+                .await?;
+            Output::Bakeries(vec![bakery])
+        }
+        BakeryCommand::List {
+            name_contains,
+            min_profit_margin,
+            max_profit_margin,
+            sort,
+            scope,
+            page,
+        } => {
+            let query = BakeryQuery {
+                filter: BakeryFilter {
+                    name_contains,
+                    min_profit_margin,
+                    max_profit_margin,
+                    scope: scope.scope(),
+                },
+                sort,
+                page: page.page(),
+            };
+            Output::BakeryListing(repo.query_bakeries(&query).await?)
+        }
+        BakeryCommand::Show { id } => Output::Bakery {
+            bakery: repo.bakery(id).await?,
+            chefs: repo.list_chefs_of(id).await?,
+        },
+        BakeryCommand::Update {
+            id,
+            name,
+            profit_margin,
+        } => {
+            let changes = BakeryChanges {
+                name,
+                profit_margin,
+            };
+            Output::Bakeries(vec![repo.update_bakery(id, changes).await?])
+        }
+        BakeryCommand::Delete { id } => {
+            let bakery = repo.delete_bakery(id).await?;
+            Output::message(format!("deleted bakery {} ({})", bakery.id, bakery.name))
+        }
+        BakeryCommand::History { id, at: None } => {
+            let changes = repo.bakery_history(id).await?;
+            if changes.is_empty() {
+                repo.bakery(id).await?;
+            }
+            Output::history(changes)
+        }
+        BakeryCommand::History { id, at: Some(at) } => match repo.bakery_at(id, at).await? {
+            Some(bakery) => Output::Bakeries(vec![bakery]),
+            None => Output::message(format!("bakery {} did not exist at {}", id, at)),
+        },
+        BakeryCommand::Restore { id } => {
+            let bakery = repo.restore_bakery(id).await?;
+            let chefs = repo.list_chefs_of(id).await?.len();
+            Output::message(format!(
+                "restored bakery {} ({}) with {} chef(s)",
+                bakery.id, bakery.name, chefs
+            ))
+        }
+    };
+    Ok(output)
+}
+
+async fn chef_command<R>(command: ChefCommand, repo: &R) -> Result<Output, CliError>
+where
+    R: BakeryRepository + ChefRepository,
+{
+    let output = match command {
+        ChefCommand::Hire {
+            name,
+            bakery,
+            contact,
+        } => {
+            let chef = repo
+                .hire_chef(NewChef {
+                    name,
+                    bakery_id: bakery,
+                    contact_details: contact.details(),
+                })
+                .await?;
+            let bakery = repo.bakery(chef.bakery_id).await?;
+            Output::message(format!(
+                "hired chef {} ({}) at {}",
+                chef.id, chef.name, bakery.name
+            ))
+        }
+        ChefCommand::Move { id, bakery } => {
+            let chef = repo.move_chef(id, bakery).await?;
+            let bakery = repo.bakery(chef.bakery_id).await?;
+            Output::message(format!(
+                "moved chef {} ({}) to {}",
+                chef.id, chef.name, bakery.name
+            ))
+        }
+        ChefCommand::Fire { id } => {
+            let chef = repo.fire_chef(id).await?;
+            Output::message(format!("fired chef {} ({})", chef.id, chef.name))
+        }
+        ChefCommand::History { id, at: None } => {
+            let changes = repo.chef_history(id).await?;
+            if changes.is_empty() {
+                repo.chef(id).await?;
+            }
+            Output::history(changes)
+        }
+        ChefCommand::History { id, at: Some(at) } => match repo.chef_at(id, at).await? {
+            Some(chef) => Output::Chefs(chef_rows(vec![chef], repo).await?),
+            None => Output::message(format!("chef {} did not exist at {}", id, at)),
+        },
+        ChefCommand::Restore { id } => {
+            let chef = repo.restore_chef(id).await?;
+            Output::message(format!("restored chef {} ({})", chef.id, chef.name))
+        }
+        ChefCommand::List {
+            bakery,
+            name_contains,
+            mut sort,
+            scope,
+            page,
+        } => {
+            let scope = scope.scope();
+            if let (Some(bakery), Scope::Active) = (bakery, scope) {
+                repo.bakery(bakery).await?;
+            }
+            if sort.is_empty() {
+                sort.push("name".parse().expect("name is a chef sort key"));
+            }
+            let query = ChefQuery {
+                filter: ChefFilter {
+                    name_contains,
+                    bakery_id: bakery,
+                    scope,
+                },
+                sort,
+                page: page.page(),
+            };
+            let chefs = repo.query_chefs(&query).await?;
+            let rows = chef_rows(chefs.items, repo).await?;
+            Output::ChefListing(Listing {
+                items: rows,
+                total: chefs.total,
+                next: chefs.next,
+            })
+        }
+        ChefCommand::Find { email, phone } => {
+            let chefs = match (email, phone) {
+                (Some(email), _) => repo.find_chefs_by_email(&email).await?,
+                (None, Some(phone)) => repo.find_chefs_by_phone(&phone).await?,
+                (None, None) => unreachable!("clap requires --email or --phone"),
+            };
+            Output::Chefs(chef_rows(chefs, repo).await?)
+        }
+    };
+    Ok(output)
+}
+
+/// `chefs` along with the names of their bakeries.
+async fn chef_rows<R: BakeryRepository>(
+    chefs: Vec<chef::Model>,
+    repo: &R,
+) -> Result<Vec<ChefRow>, DbErr> {
+    let bakeries = match chefs.iter().any(|chef| chef.deleted_at.is_some()) {
+        false => repo.list_bakeries().await?,
+        // Fired chefs may work at bakeries deleted since.
+        true => {
+            let query = BakeryQuery {
+                filter: BakeryFilter {
+                    scope: Scope::WithDeleted,
+                    ..Default::default()
+                },
+                page: Page::Offset {
+                    number: 1,
+                    size: i64::MAX as u64,
+                },
+                ..Default::default()
+            };
+            repo.query_bakeries(&query).await?.items
+        }
+    };
+    let bakeries: HashMap<i32, String> = bakeries
+        .into_iter()
+        .map(|bakery| (bakery.id, bakery.name))
+        .collect();
+    Ok(chefs
+        .into_iter()
+        .map(|chef| ChefRow {
+            bakery: bakeries.get(&chef.bakery_id).cloned().unwrap_or_default(),
+            id: chef.id,
+            name: chef.name,
+            bakery_id: chef.bakery_id,
+            contact_details: chef.contact_details,
+            deleted_at: chef.deleted_at,
+        })
+        .collect())
+}
+
+async fn db_command<R>(
+    command: DbCommand,
+    db: &DatabaseConnection,
+    repo: &R,
+) -> Result<Output, CliError>
+where
+    R: BakeryRepository + ChefRepository,
+{
+    let output = match command {
+        DbCommand::Migrate => {
+            let pending = Migrator::get_pending_migrations(db).await?.len();
+            Migrator::up(db, None).await?;
+            Output::message(format!("applied {} migration(s)", pending))
+        }
+        DbCommand::Reset { yes: false } => {
+            return Err(CliError::Unconfirmed(
+                "`db reset` deletes all data; pass --yes to go ahead",
+            ));
+        }
+        DbCommand::Purge { yes: false, .. } => {
+            return Err(CliError::Unconfirmed(
+                "`db purge` deletes rows for good; pass --yes to go ahead",
+            ));
+        }
+        DbCommand::Purge {
+            older_than_days,
+            yes: true,
+        } => {
+            let cutoff = soft_delete::cutoff(older_than_days);
+            // Chefs first, so those deleted along with their bakery are counted.
+            let chefs = repo.purge_chefs(cutoff).await?;
+            let bakeries = repo.purge_bakeries(cutoff).await?;
+            Output::message(format!(
+                "purged {} bakery(ies) and {} chef(s) deleted over {} day(s) ago",
+                bakeries, chefs, older_than_days
+            ))
+        }
+        DbCommand::Reset { yes: true } => {
+            Migrator::fresh(db).await?;
+            Output::message("dropped all tables and applied all migrations")
+        }
+        DbCommand::Status => {
+            let applied: Vec<_> = Migrator::get_migration_models(db)
+                .await?
+                .into_iter()
+                .map(|model| model.version)
+                .collect();
+            Output::Migrations(
+                Migrator::migrations()
+                    .iter()
+                    .map(|migration| {
+                        let name = migration.name().to_owned();
+                        let status = match applied.contains(&name) {
+                            true => MigrationStatus::Applied,
+                            false => MigrationStatus::Pending,
+                        };
+                        MigrationRow {
+                            name,
+                            status: status.to_string(),
+                        }
+                    })
+                    .collect(),
+            )
+        }
+    };
+    Ok(output)
+}
+
+async fn fixtures_command<R>(command: FixturesCommand, repo: &R) -> Result<Output, CliError>
+where
+    R: Transactional,
+{
+    let fixtures = match command {
+        FixturesCommand::Load { files } => Fixtures::read_all(&files)?,
+        FixturesCommand::Generate {
+            bakeries,
+            chefs_per_bakery,
+            seed,
+            output: Some(path),
+        } => {
+            fixtures::generate(seed, bakeries, chefs_per_bakery).write(&path)?;
+            return Ok(Output::message(format!(
+                "wrote {} bakery(ies) and {} chef(s) to {}",
+                bakeries,
+                bakeries * chefs_per_bakery,
+                path.display()
+            )));
+        }
+        FixturesCommand::Generate {
+            bakeries,
+            chefs_per_bakery,
+            seed,
+            output: None,
+        } => fixtures::generate(seed, bakeries, chefs_per_bakery),
+    };
+    let loaded = fixtures::load(repo, &fixtures).await?;
+    Ok(Output::message(format!(
+        "bakeries: {}; chefs: {}",
+        loaded.bakeries, loaded.chefs
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::DatabaseConfig;
+    use crate::entities::{bakery, chef};
+    use clap::Parser;
+    use futures::executor::block_on;
+    use std::collections::BTreeMap;
+
+    fn command(args: &[&str]) -> Command {
+        Cli::try_parse_from(std::iter::once("bakery-backend").chain(args.iter().copied()))
+            .unwrap()
+            .command
+    }
+
+    fn run(args: &[&str], db: &DatabaseConnection) -> Result<Output, CliError> {
+        block_on(execute(command(args), db, "tester"))
+    }
+
+    fn bakery(id: i32, name: &str, profit_margin: f64) -> bakery::Model {
+        bakery::Model {
+            id,
+            name: name.to_owned(),
+            profit_margin,
+            deleted_at: None,
+        }
+    }
+
+    /// The rows the sample fixtures make in an empty database.
+    fn sample() -> (Vec<bakery::Model>, Vec<chef::Model>) {
+        let fixtures = Fixtures::read("fixtures/sample.yaml".as_ref()).unwrap();
+        let bakeries: Vec<_> = fixtures
+            .bakeries
+            .iter()
+            .zip(1..)
+            .map(|(fixture, id)| bakery(id, &fixture.name, fixture.profit_margin))
+            .collect();
+        let chefs = fixtures
+            .chefs
+            .into_iter()
+            .zip(1..)
+            .map(|(fixture, id)| chef::Model {
+                id,
+                name: fixture.name,
+                contact_details: fixture.contact_details,
+                bakery_id: bakeries
+                    .iter()
+                    .find(|bakery| bakery.name == fixture.bakery)
+                    .unwrap()
+                    .id,
+                deleted_at: None,
+            })
+            .collect();
+        (bakeries, chefs)
+    }
+
+    #[test]
+    fn test_mock_output() {
+        let (bakeries, chefs) = sample();
+        let la_boulangerie = bakeries[2].clone();
+        let count = BTreeMap::from([("num_items", Value::BigInt(Some(3)))]);
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results(vec![vec![count]])
+            .append_query_results(vec![bakeries])
+            .append_query_results(vec![vec![la_boulangerie.clone()]])
+            .append_query_results(vec![vec![la_boulangerie]])
+            .append_query_results(vec![chefs])
+            .append_query_results(vec![Vec::<bakery::Model>::new()])
+            .into_connection();
+
+        let bakeries = run(&["bakery", "list"], &db).unwrap();
+        assert_eq!(
+            bakeries.render(Format::Table),
+            "id  name            profit_margin\n\
+             1   Happy Bakery    0\n\
+             2   Sad Bakery      100\n\
+             3   La Boulangerie  17.89\n\
+             3 of 3"
+        );
+
+        let la_boulangerie = run(&["bakery", "show", "3"], &db).unwrap();
+        let json: serde_json::Value =
+            serde_json::from_str(&la_boulangerie.render(Format::Json)).unwrap();
+        assert_eq!(json["name"], "La Boulangerie");
+        assert_eq!(json["chefs"].as_array().unwrap().len(), 4);
+        assert_eq!(json["chefs"][3]["name"], "Frederic");
+
+        let missing = run(&["bakery", "show", "9"], &db).unwrap_err();
+        assert_eq!(missing.exit_code(), 3);
+        assert_eq!(missing.to_string(), "no such bakery 9");
+
+        assert_eq!(
+            db.into_transaction_log()[1],
+            Transaction::from_sql_and_values(
+                DatabaseBackend::Postgres,
+                r#"SELECT "bakery"."id", "bakery"."name", "bakery"."profit_margin", "bakery"."deleted_at" FROM "bakery" WHERE "bakery"."deleted_at" IS NULL ORDER BY "bakery"."id" ASC LIMIT $1 OFFSET $2"#,
+                [20u64.into(), 0u64.into()]
+            )
+        );
+    }
+
+    #[test]
+    fn test_sqlite_flow() {
+        let db = &block_on(DatabaseConfig::default().connect()).unwrap();
+        assert_eq!(run(&["db", "reset"], db).unwrap_err().exit_code(), 2);
+        run(&["db", "migrate"], db).unwrap();
+
+        run(&["bakery", "add", "Happy Bakery"], db).unwrap();
+        let sad_bakery = run(&["bakery", "update", "1", "--name", "Sad Bakery"], db).unwrap();
+        assert_eq!(
+            sad_bakery,
+            Output::Bakeries(vec![bakery(1, "Sad Bakery", 0.0)])
+        );
+        run(&["chef", "hire", "John", "--bakery", "1"], db).unwrap();
+        run(&["chef", "fire", "1"], db).unwrap();
+        run(&["bakery", "delete", "1"], db).unwrap();
+        assert_eq!(
+            run(&["bakery", "list"], db).unwrap(),
+            Output::BakeryListing(Listing {
+                items: vec![],
+                total: 0,
+                next: None,
+            })
+        );
+        assert_eq!(
+            run(&["bakery", "delete", "1"], db).unwrap_err().exit_code(),
+            3
+        );
+        let deleted = run(&["bakery", "list", "--only-deleted"], db).unwrap();
+        let deleted = deleted.render(Format::Table);
+        assert!(deleted.starts_with("id  name        profit_margin  deleted_at\n1   Sad Bakery  0"));
+        assert_eq!(
+            run(&["chef", "restore", "1"], db).unwrap_err().exit_code(),
+            3
+        );
+        assert_eq!(
+            run(&["bakery", "restore", "1"], db).unwrap(),
+            Output::message("restored bakery 1 (Sad Bakery) with 0 chef(s)")
+        );
+        run(&["chef", "restore", "1"], db).unwrap();
+        run(&["bakery", "delete", "1"], db).unwrap();
+        let history = run(&["bakery", "history", "1"], db).unwrap();
+        let history = history.render(Format::Table);
+        let lines: Vec<&str> = history.lines().collect();
+        assert_eq!(lines.len(), 6, "{}", history);
+        assert!(lines[0].starts_with("version  changed_at"));
+        assert!(
+            lines[1].ends_with("tester  insert     id: 1, name: Happy Bakery, profit_margin: 0.0")
+        );
+        assert!(lines[2].ends_with("name: Happy Bakery → Sad Bakery"));
+        assert!(lines[5].contains("tester  delete     deleted_at: null → "));
+        let before = ["bakery", "history", "1", "--at", "2000-01-01T00:00:00Z"];
+        assert_eq!(
+            run(&before, db).unwrap(),
+            Output::message("bakery 1 did not exist at 2000-01-01 00:00:00 UTC")
+        );
+        assert_eq!(
+            run(&["chef", "history", "9"], db).unwrap_err().exit_code(),
+            3
+        );
+
+        run(
+            &[
+                "bakery",
+                "add",
+                "La Boulangerie",
+                "--profit-margin",
+                "17.89",
+            ],
+            db,
+        )
+        .unwrap();
+        for name in ["Jolie", "Charles", "Madeleine", "Frederic"] {
+            run(&["chef", "hire", name, "--bakery", "2"], db).unwrap();
+        }
+        let chefs = match run(&["chef", "list", "--bakery", "2"], db).unwrap() {
+            Output::ChefListing(chefs) => chefs.items,
+            other => panic!("expected chefs, got {:?}", other),
+        };
+        let names: Vec<_> = chefs.iter().map(|chef| chef.name.as_str()).collect();
+        assert_eq!(names, ["Charles", "Frederic", "Jolie", "Madeleine"]);
+        assert!(chefs.iter().all(|chef| chef.bakery == "La Boulangerie"));
+
+        let first = run(&["chef", "list", "--per-page", "3", "--keyset"], db).unwrap();
+        let footer = first.render(Format::Table);
+        assert!(
+            footer.ends_with(r#"3 of 4; next: --after '["Jolie",2]' --per-page 3"#),
+            "{}",
+            footer
+        );
+        let after = [
+            "chef",
+            "list",
+            "--per-page",
+            "3",
+            "--after",
+            r#"["Jolie",2]"#,
+        ];
+        match run(&after, db).unwrap() {
+            Output::ChefListing(chefs) => assert_eq!(chefs.items[0].name, "Madeleine"),
+            other => panic!("expected chefs, got {:?}", other),
+        }
+        let bad_sort = ["bakery", "list", "--sort", "-profit_margin,flavour"];
+        assert!(Cli::try_parse_from(std::iter::once("bakery-backend").chain(bad_sort)).is_err());
+        assert!(Cli::try_parse_from(["bakery-backend", "bakery", "list", "--page", "0"]).is_err());
+        let far = [
+            "bakery",
+            "list",
+            "--page",
+            "18446744073709551615",
+            "--per-page",
+            "2",
+        ];
+        assert!(run(&far, db).is_err());
+        let far = [
+            "bakery",
+            "list",
+            "--page",
+            "4611686018427387905",
+            "--per-page",
+            "2",
+        ];
+        assert!(run(&far, db).is_err());
+        for per_page in ["1001", "18446744073709551615"] {
+            for keyset in [&[][..], &["--keyset"]] {
+                let args = ["bakery-backend", "bakery", "list", "--per-page", per_page];
+                let args = args.iter().chain(keyset);
+                assert!(Cli::try_parse_from(args).is_err());
+            }
+        }
+
+        run(&["bakery", "add", "Le Fournil"], db).unwrap();
+        run(&["chef", "move", "2", "--bakery", "3"], db).unwrap();
+        match run(&["bakery", "show", "3"], db).unwrap() {
+            Output::Bakery { chefs, .. } => assert_eq!(chefs.len(), 1),
+            other => panic!("expected a bakery, got {:?}", other),
+        }
+
+        let hire = [
+            "chef",
+            "hire",
+            "Odile",
+            "--bakery",
+            "3",
+            "--email",
+            "odile@example.com",
+            "--phone",
+            "01 23 45 67 89",
+            "--prefer",
+            "phone",
+        ];
+        run(&hire, db).unwrap();
+        let found = run(&["chef", "find", "--email", "Odile@Example.com"], db).unwrap();
+        assert_eq!(
+            found.render(Format::Table),
+            "id  name   bakery_id  bakery      contact_details\n\
+             6   Odile  3          Le Fournil  odile@example.com, 01 23 45 67 89 (prefers phone)"
+        );
+        assert!(Cli::try_parse_from(["bakery-backend", "chef", "find"]).is_err());
+        let unreachable = ["chef", "hire", "Paul", "--bakery", "3", "--prefer", "post"];
+        assert_eq!(run(&unreachable, db).unwrap_err().exit_code(), 4);
+
+        let purge = ["db", "purge", "--older-than-days", "0"];
+        assert_eq!(run(&purge, db).unwrap_err().exit_code(), 2);
+        assert_eq!(
+            run(&[&purge[..], &["--yes"]].concat(), db).unwrap(),
+            Output::message("purged 1 bakery(ies) and 1 chef(s) deleted over 0 day(s) ago")
+        );
+        let deleted = ["chef", "list", "--with-deleted", "--per-page", "100"];
+        match run(&deleted, db).unwrap() {
+            Output::ChefListing(chefs) => assert_eq!(chefs.total, 5),
+            other => panic!("expected chefs, got {:?}", other),
+        }
+
+        let status = run(&["db", "status"], db).unwrap().render(Format::Table);
+        assert!(
+            status.lines().skip(1).all(|line| line.ends_with("Applied")),
+            "{}",
+            status
+        );
+    }
+
+    #[test]
+    fn test_fixtures() {
+        let db = &block_on(DatabaseConfig::default().connect()).unwrap();
+        run(&["db", "migrate"], db).unwrap();
+
+        let load = ["fixtures", "load", "fixtures/sample.yaml"];
+        assert_eq!(
+            run(&load, db).unwrap(),
+            Output::message(
+                "bakeries: 3 created, 0 updated, 0 unchanged; \
+                 chefs: 4 created, 0 updated, 0 unchanged"
+            )
+        );
+        assert_eq!(
+            run(&load, db).unwrap(),
+            Output::message(
+                "bakeries: 0 created, 0 updated, 3 unchanged; \
+                 chefs: 0 created, 0 updated, 4 unchanged"
+            )
+        );
+        let missing = run(&["fixtures", "load", "fixtures/missing.yaml"], db).unwrap_err();
+        assert_eq!(missing.exit_code(), 2);
+
+        let path = std::env::temp_dir().join("bakery-backend-fixtures.yaml");
+        let path = path.to_str().unwrap();
+        let generate = ["fixtures", "generate", "--bakeries", "10", "--seed", "3"];
+        assert_eq!(
+            run(&[&generate[..], &["--output", path]].concat(), db).unwrap(),
+            Output::message(format!("wrote 10 bakery(ies) and 50 chef(s) to {}", path))
+        );
+        run(&generate, db).unwrap();
+        assert_eq!(
+            run(&["fixtures", "load", path], db).unwrap(),
+            Output::message(
+                "bakeries: 0 created, 0 updated, 10 unchanged; \
+                 chefs: 0 created, 0 updated, 50 unchanged"
+            )
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,150 @@
+//! How to reach a chef, stored as JSON in `chef.contact_details`.
+//!
+//! The column is plain JSON on every backend, so looking chefs up by one of their
+//! contacts takes a JSON expression that differs per backend: [`has_email`] and
+//! [`has_phone`] build it.
+
+use sea_orm::sea_query::{Expr, SimpleExpr};
+use sea_orm::{DbBackend, FromJsonQueryResult};
+use serde::{Deserialize, Serialize};
+
+use std::fmt;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, FromJsonQueryResult)]
+#[serde(default, deny_unknown_fields)]
+pub struct ContactDetails {
+    /// Lowercase once validated, so lookups ignore case.
+    pub emails: Vec<String>,
+    pub phones: Vec<String>,
+    pub address: Option<String>,
+    /// Must be a channel the chef has a contact for.
+    pub preferred: Option<Channel>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, clap::ArgEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Channel {
+    Email,
+    Phone,
+    Post,
+}
+
+impl ContactDetails {
+    pub fn is_empty(&self) -> bool {
+        self.emails.is_empty() && self.phones.is_empty() && self.address.is_none()
+    }
+
+    /// Whether there is a contact to use `channel` with.
+    pub fn reachable_by(&self, channel: Channel) -> bool {
+        match channel {
+            Channel::Email => !self.emails.is_empty(),
+            Channel::Phone => !self.phones.is_empty(),
+            Channel::Post => self.address.is_some(),
+        }
+    }
+}
+
+impl fmt::Display for Channel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let channel = match self {
+            Channel::Email => "email",
+            Channel::Phone => "phone",
+            Channel::Post => "post",
+        };
+        write!(f, "{}", channel)
+    }
+}
+
+/// Every contact on one line, e.g. `jolie@example.com, +33 1 23 45 67 89 (prefers phone)`.
+impl fmt::Display for ContactDetails {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let contacts: Vec<&str> = self
+            .emails
+            .iter()
+            .chain(&self.phones)
+            .chain(&self.address)
+            .map(String::as_str)
+            .collect();
+        write!(f, "{}", contacts.join(", "))?;
+        if let Some(channel) = self.preferred {
+            write!(f, " (prefers {})", channel)?;
+        }
+        Ok(())
+    }
+}
+
+/// Chefs with `email` among their emails. Emails are stored lowercase.
+pub fn has_email(backend: DbBackend, email: &str) -> SimpleExpr {
+    array_contains(backend, "emails", email.trim().to_lowercase())
+}
+
+/// Chefs with `phone`, exactly as it was stored, among their phones.
+pub fn has_phone(backend: DbBackend, phone: &str) -> SimpleExpr {
+    array_contains(backend, "phones", phone.trim().to_owned())
+}
+
+/// Whether the `key` array of `chef.contact_details` holds the string `value`.
+fn array_contains(backend: DbBackend, key: &str, value: String) -> SimpleExpr {
+    let sql = match backend {
+        // `?` is jsonb's "has string element" here, `$1` the placeholder.
+        DbBackend::Postgres => format!(r#"("chef"."contact_details"::jsonb -> '{}') ? $1"#, key),
+        DbBackend::MySql => format!(
+            "JSON_CONTAINS(`chef`.`contact_details`, JSON_QUOTE(?), '$.{}')",
+            key
+        ),
+        // No containment operator, so the array is searched as a table.
+        DbBackend::Sqlite => format!(
+            r#"EXISTS (SELECT 1 FROM json_each("chef"."contact_details", '$.{}') WHERE json_each.value = ?)"#,
+            key
+        ),
+    };
+    Expr::cust_with_values(&sql, [value])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entities::{chef, prelude::Chef};
+    use sea_orm::{EntityTrait, QueryFilter, QuerySelect, QueryTrait};
+
+    fn find_by_email(backend: DbBackend) -> String {
+        Chef::find()
+            .select_only()
+            .column(chef::Column::Id)
+            .filter(has_email(backend, " Jolie@Example.com "))
+            .build(backend)
+            .to_string()
+    }
+
+    #[test]
+    fn test_has_email() {
+        assert_eq!(
+            find_by_email(DbBackend::Postgres),
+            r#"SELECT "chef"."id" FROM "chef" WHERE ("chef"."contact_details"::jsonb -> 'emails') ? 'jolie@example.com'"#
+        );
+        assert_eq!(
+            find_by_email(DbBackend::MySql),
+            "SELECT `chef`.`id` FROM `chef` WHERE JSON_CONTAINS(`chef`.`contact_details`, JSON_QUOTE('jolie@example.com'), '$.emails')"
+        );
+        assert_eq!(
+            find_by_email(DbBackend::Sqlite),
+            r#"SELECT "chef"."id" FROM "chef" WHERE EXISTS (SELECT 1 FROM json_each("chef"."contact_details", '$.emails') WHERE json_each.value = 'jolie@example.com')"#
+        );
+    }
+
+    #[test]
+    fn test_display() {
+        let details = ContactDetails {
+            emails: vec!["jolie@example.com".to_owned()],
+            phones: vec!["+33 1 23 45 67 89".to_owned()],
+            address: None,
+            preferred: Some(Channel::Phone),
+        };
+        assert_eq!(
+            details.to_string(),
+            "jolie@example.com, +33 1 23 45 67 89 (prefers phone)"
+        );
+        assert!(details.reachable_by(Channel::Email));
+        assert!(!details.reachable_by(Channel::Post));
+    }
+}
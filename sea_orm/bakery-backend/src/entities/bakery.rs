@@ -1,15 +1,24 @@
-//! `SeaORM` Entity. Generated by sea-orm-codegen 0.11.3
+//! `SeaORM` Entity. Generated by sea-orm-codegen 0.11.3, then given hand-written
+//! `ActiveModelBehavior` hooks that validate saves and deletes: do not regenerate over it.
 
 use sea_orm::entity::prelude::*;
+use sea_orm::{ActiveValue, ConnectionTrait};
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
+use crate::soft_delete::SoftDelete;
+use crate::validation::{self, ValidationError};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
 #[sea_orm(table_name = "bakery")]
 pub struct Model {
     #[sea_orm(primary_key)]
     pub id: i32,
+    #[sea_orm(unique)]
     pub name: String,
     #[sea_orm(column_type = "Double")]
     pub profit_margin: f64,
+    /// When the row was soft-deleted; see [`crate::soft_delete`].
+    pub deleted_at: Option<DateTimeUtc>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -24,4 +33,50 @@ impl Related<super::chef::Entity> for Entity {
     }
 }
 
-impl ActiveModelBehavior for ActiveModel {}
+#[async_trait::async_trait]
+impl ActiveModelBehavior for ActiveModel {
+    /// Trims the name, and checks it is not blank nor taken by another bakery, and that the
+    /// profit margin is within the configured range.
+    async fn before_save<C>(mut self, db: &C, _insert: bool) -> Result<Self, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        if let ActiveValue::Set(name) = &self.name {
+            let name = validation::name("bakery", name)?;
+            let mut same_name = Entity::find().filter(Column::Name.eq(name.as_str()));
+            if let Some(id) = self.id.clone().into_value() {
+                same_name = same_name.filter(Column::Id.ne(id));
+            }
+            if same_name.one(db).await?.is_some() {
+                return Err(ValidationError::DuplicateBakeryName { name }.into());
+            }
+            self.name = ActiveValue::Set(name);
+        }
+        if let ActiveValue::Set(profit_margin) = self.profit_margin {
+            validation::profit_margin(profit_margin)?;
+        }
+        Ok(self)
+    }
+
+    /// Refuses to delete for good a bakery that is not soft-deleted.
+    async fn before_delete<C>(self, db: &C) -> Result<Self, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        if let Some(id) = self.id.clone().into_value() {
+            let active = Entity::find_active()
+                .filter(Column::Id.eq(id))
+                .one(db)
+                .await?;
+            if let Some(bakery) = active {
+                let id = bakery.id;
+                return Err(ValidationError::NotSoftDeleted {
+                    entity: "bakery",
+                    id,
+                }
+                .into());
+            }
+        }
+        Ok(self)
+    }
+}
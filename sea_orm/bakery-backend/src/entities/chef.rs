@@ -1,15 +1,25 @@
-//! `SeaORM` Entity. Generated by sea-orm-codegen 0.11.3
+//! `SeaORM` Entity. Generated by sea-orm-codegen 0.11.3, then given hand-written
+//! `ActiveModelBehavior` hooks that validate saves and deletes: do not regenerate over it.
 
 use sea_orm::entity::prelude::*;
+use sea_orm::{ActiveValue, ConnectionTrait};
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+use crate::contact::ContactDetails;
+use crate::soft_delete::SoftDelete;
+use crate::validation::{self, ValidationError};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
 #[sea_orm(table_name = "chef")]
 pub struct Model {
     #[sea_orm(primary_key)]
     pub id: i32,
     pub name: String,
-    pub contact_details: Option<Json>,
+    #[sea_orm(column_type = "Json")]
+    pub contact_details: Option<ContactDetails>,
     pub bakery_id: i32,
+    /// When the row was soft-deleted; see [`crate::soft_delete`].
+    pub deleted_at: Option<DateTimeUtc>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -30,4 +40,39 @@ impl Related<super::bakery::Entity> for Entity {
     }
 }
 
-impl ActiveModelBehavior for ActiveModel {}
+#[async_trait::async_trait]
+impl ActiveModelBehavior for ActiveModel {
+    /// Trims the name and checks it is not blank, and tidies and checks the contact
+    /// details.
+    async fn before_save<C>(mut self, _db: &C, _insert: bool) -> Result<Self, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        if let ActiveValue::Set(name) = &self.name {
+            self.name = ActiveValue::Set(validation::name("chef", name)?);
+        }
+        if let ActiveValue::Set(Some(details)) = &self.contact_details {
+            let details = validation::contact_details(details.clone())?;
+            self.contact_details = ActiveValue::Set(Some(details));
+        }
+        Ok(self)
+    }
+
+    /// Refuses to delete for good a chef who is not fired.
+    async fn before_delete<C>(self, db: &C) -> Result<Self, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        if let Some(id) = self.id.clone().into_value() {
+            let active = Entity::find_active()
+                .filter(Column::Id.eq(id))
+                .one(db)
+                .await?;
+            if let Some(chef) = active {
+                let id = chef.id;
+                return Err(ValidationError::NotSoftDeleted { entity: "chef", id }.into());
+            }
+        }
+        Ok(self)
+    }
+}
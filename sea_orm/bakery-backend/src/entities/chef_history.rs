@@ -0,0 +1,29 @@
+//! `SeaORM` Entity for the `chef_history` table, written by hand.
+
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::history::Operation;
+
+/// A version of a chef; see [`crate::history`].
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "chef_history")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub chef_id: i32,
+    /// Unique with `chef_id`.
+    pub version: i32,
+    pub operation: Operation,
+    #[sea_orm(column_type = "Json")]
+    pub old_values: Option<Json>,
+    #[sea_orm(column_type = "Json")]
+    pub new_values: Option<Json>,
+    pub actor: String,
+    pub changed_at: DateTimeUtc,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -1,6 +1,10 @@
-//! `SeaORM` Entity. Generated by sea-orm-codegen 0.11.3
+//! `SeaORM` Entities. `bakery` and `chef` were generated by sea-orm-codegen 0.11.3 and
+//! have since been extended by hand, and the history entities are hand-written, so this
+//! directory must not be regenerated.
 
 pub mod prelude;
 
 pub mod bakery;
+pub mod bakery_history;
 pub mod chef;
+pub mod chef_history;
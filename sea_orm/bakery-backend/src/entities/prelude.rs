@@ -1,4 +1,6 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.11.3
 
 pub use super::bakery::Entity as Bakery;
+pub use super::bakery_history::Entity as BakeryHistory;
 pub use super::chef::Entity as Chef;
+pub use super::chef_history::Entity as ChefHistory;
@@ -0,0 +1,681 @@
+//! Bakeries and chefs kept in YAML, JSON or CSV files, for sample data and tests.
+//!
+//! Chefs name their bakery rather than give its id, so the same files load into any
+//! database. Loading is an upsert: bakeries are matched by name and chefs by name within
+//! their bakery, and only what differs is written, so loading a file twice changes
+//! nothing the second time. Rows missing from the files are left alone.
+//!
+//! [`generate`] makes up as many bakeries and chefs as needed from a seed.
+
+mod random;
+
+use sea_orm::DbErr;
+use serde::{Deserialize, Serialize};
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+
+use crate::contact::{Channel, ContactDetails};
+use crate::entities::{bakery, chef};
+use crate::listing::{
+    BakeryFilter, BakeryQuery, ChefFilter, ChefQuery, Listing, Page, MAX_PAGE_SIZE,
+};
+use crate::repository::{
+    BakeryChanges, BakeryRepository, ChefChanges, ChefRepository, NewBakery, NewChef, Transaction,
+    Transactional,
+};
+use crate::soft_delete::Scope;
+use crate::validation;
+
+pub use random::generate;
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Fixtures {
+    pub bakeries: Vec<BakeryFixture>,
+    pub chefs: Vec<ChefFixture>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BakeryFixture {
+    pub name: String,
+    pub profit_margin: f64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ChefFixture {
+    pub name: String,
+    /// Name of the bakery, among the fixtures or already in the database.
+    pub bakery: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub contact_details: Option<ContactDetails>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileFormat {
+    Yaml,
+    Json,
+    /// Bakeries with `name` and `profit_margin` columns, or chefs with `name`, `bakery`,
+    /// `emails`, `phones`, `address` and `preferred` ones, several emails or phones
+    /// separated by `;`. Only read.
+    Csv,
+}
+
+impl FileFormat {
+    /// The format of `path`, by its extension.
+    pub fn of(path: &Path) -> Result<FileFormat, FixtureError> {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("yaml" | "yml") => Ok(FileFormat::Yaml),
+            Some("json") => Ok(FileFormat::Json),
+            Some("csv") => Ok(FileFormat::Csv),
+            _ => Err(FixtureError::UnknownFormat(path.to_owned())),
+        }
+    }
+}
+
+/// A chef as a CSV row, with no nesting.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ChefRecord {
+    name: String,
+    bakery: String,
+    #[serde(default)]
+    emails: String,
+    #[serde(default)]
+    phones: String,
+    #[serde(default)]
+    address: Option<String>,
+    #[serde(default)]
+    preferred: Option<Channel>,
+}
+
+impl From<ChefRecord> for ChefFixture {
+    fn from(record: ChefRecord) -> Self {
+        let list = |contacts: &str| -> Vec<String> {
+            contacts
+                .split(';')
+                .map(str::trim)
+                .filter(|contact| !contact.is_empty())
+                .map(str::to_owned)
+                .collect()
+        };
+        let details = ContactDetails {
+            emails: list(&record.emails),
+            phones: list(&record.phones),
+            address: record.address,
+            preferred: record.preferred,
+        };
+        ChefFixture {
+            name: record.name,
+            bakery: record.bakery,
+            contact_details: match details.is_empty() && details.preferred.is_none() {
+                true => None,
+                false => Some(details),
+            },
+        }
+    }
+}
+
+impl Fixtures {
+    pub fn read(path: &Path) -> Result<Fixtures, FixtureError> {
+        let format = FileFormat::of(path)?;
+        let text = std::fs::read_to_string(path)
+            .map_err(|err| FixtureError::Read(path.to_owned(), err))?;
+        Fixtures::parse(&text, format).map_err(|err| FixtureError::Parse(path.to_owned(), err))
+    }
+
+    /// The fixtures of all the files, in order, so that chefs in one may work at
+    /// bakeries in another.
+    pub fn read_all(paths: &[PathBuf]) -> Result<Fixtures, FixtureError> {
+        let mut fixtures = Fixtures::default();
+        for path in paths {
+            fixtures.extend(Fixtures::read(path)?);
+        }
+        Ok(fixtures)
+    }
+
+    pub fn parse(text: &str, format: FileFormat) -> Result<Fixtures, String> {
+        match format {
+            FileFormat::Yaml => serde_yaml::from_str(text).map_err(|err| err.to_string()),
+            FileFormat::Json => serde_json::from_str(text).map_err(|err| err.to_string()),
+            FileFormat::Csv => parse_csv(text).map_err(|err| err.to_string()),
+        }
+    }
+
+    /// Writes the fixtures to a YAML or JSON file.
+    pub fn write(&self, path: &Path) -> Result<(), FixtureError> {
+        let text = match FileFormat::of(path)? {
+            FileFormat::Yaml => serde_yaml::to_string(self).expect("fixtures serialize"),
+            FileFormat::Json => serde_json::to_string_pretty(self).expect("fixtures serialize"),
+            FileFormat::Csv => return Err(FixtureError::CsvOutput(path.to_owned())),
+        };
+        std::fs::write(path, text).map_err(|err| FixtureError::Write(path.to_owned(), err))
+    }
+
+    pub fn extend(&mut self, other: Fixtures) {
+        self.bakeries.extend(other.bakeries);
+        self.chefs.extend(other.chefs);
+    }
+}
+
+/// Chefs if there is a `bakery` column, bakeries otherwise.
+fn parse_csv(text: &str) -> Result<Fixtures, csv::Error> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(text.as_bytes());
+    let mut fixtures = Fixtures::default();
+    if reader.headers()?.iter().any(|header| header == "bakery") {
+        for record in reader.deserialize::<ChefRecord>() {
+            fixtures.chefs.push(record?.into());
+        }
+    } else {
+        fixtures.bakeries = reader.deserialize().collect::<Result<_, _>>()?;
+    }
+    Ok(fixtures)
+}
+
+/// How many rows of a kind a load created, updated or found as they were.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Counts {
+    pub created: usize,
+    pub updated: usize,
+    pub unchanged: usize,
+}
+
+impl fmt::Display for Counts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} created, {} updated, {} unchanged",
+            self.created, self.updated, self.unchanged
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Loaded {
+    pub bakeries: Counts,
+    pub chefs: Counts,
+}
+
+/// Creates or updates the bakeries, then the chefs, of `fixtures`, all in one
+/// transaction. A name given twice takes the last values given for it. Deleted bakeries
+/// and fired chefs named in the fixtures are restored, and counted as updated; a bakery
+/// brings back the chefs deleted along with it.
+pub async fn load<R: Transactional>(repo: &R, fixtures: &Fixtures) -> Result<Loaded, FixtureError> {
+    let txn = repo.begin().await?;
+    let loaded = upsert(&txn, fixtures).await?;
+    txn.commit().await?;
+    Ok(loaded)
+}
+
+/// Every row of a listing, fetching `page` after `page`.
+async fn every_page<T, F, Fut>(mut fetch: F) -> Result<Vec<T>, DbErr>
+where
+    F: FnMut(Page) -> Fut,
+    Fut: Future<Output = Result<Listing<T>, DbErr>>,
+{
+    let mut page = Page::After {
+        cursor: None,
+        size: MAX_PAGE_SIZE,
+    };
+    let mut rows = vec![];
+    loop {
+        let listing = fetch(page).await?;
+        rows.extend(listing.items);
+        match listing.next {
+            Some(next) => page = next,
+            None => return Ok(rows),
+        }
+    }
+}
+
+async fn deleted_bakeries<R: BakeryRepository>(repo: &R) -> Result<Vec<bakery::Model>, DbErr> {
+    every_page(|page| async move {
+        let query = BakeryQuery {
+            filter: BakeryFilter {
+                scope: Scope::OnlyDeleted,
+                ..BakeryFilter::default()
+            },
+            page,
+            ..BakeryQuery::default()
+        };
+        repo.query_bakeries(&query).await
+    })
+    .await
+}
+
+async fn fired_chefs<R: ChefRepository>(repo: &R) -> Result<Vec<chef::Model>, DbErr> {
+    every_page(|page| async move {
+        let query = ChefQuery {
+            filter: ChefFilter {
+                scope: Scope::OnlyDeleted,
+                ..ChefFilter::default()
+            },
+            page,
+            ..ChefQuery::default()
+        };
+        repo.query_chefs(&query).await
+    })
+    .await
+}
+
+async fn upsert<R>(repo: &R, fixtures: &Fixtures) -> Result<Loaded, FixtureError>
+where
+    R: BakeryRepository + ChefRepository,
+{
+    let mut bakeries: HashMap<String, bakery::Model> = repo
+        .list_bakeries()
+        .await?
+        .into_iter()
+        .map(|bakery| (bakery.name.clone(), bakery))
+        .collect();
+    let mut deleted: HashMap<String, bakery::Model> = deleted_bakeries(repo)
+        .await?
+        .into_iter()
+        .map(|bakery| (bakery.name.clone(), bakery))
+        .collect();
+    let known: HashSet<&str> = bakeries
+        .keys()
+        .map(String::as_str)
+        .chain(fixtures.bakeries.iter().map(|bakery| bakery.name.trim()))
+        .collect();
+    if let Some(chef) = fixtures
+        .chefs
+        .iter()
+        .find(|chef| !known.contains(chef.bakery.trim()))
+    {
+        let (chef, bakery) = (chef.name.clone(), chef.bakery.clone());
+        return Err(match deleted.contains_key(bakery.trim()) {
+            true => FixtureError::DeletedBakery { chef, bakery },
+            false => FixtureError::UnknownBakery { chef, bakery },
+        });
+    }
+
+    let mut loaded = Loaded::default();
+    let mut new_bakeries: Vec<NewBakery> = vec![];
+    for fixture in &fixtures.bakeries {
+        let name = fixture.name.trim();
+        if let Some(new) = new_bakeries.iter_mut().find(|new| new.name == name) {
+            new.profit_margin = fixture.profit_margin;
+            continue;
+        }
+        let restored = match deleted.remove(name) {
+            Some(bakery) => {
+                let bakery = repo.restore_bakery(bakery.id).await?;
+                bakeries.insert(bakery.name.clone(), bakery);
+                true
+            }
+            None => false,
+        };
+        match bakeries.get(name) {
+            Some(bakery) if bakery.profit_margin == fixture.profit_margin => match restored {
+                true => loaded.bakeries.updated += 1,
+                false => loaded.bakeries.unchanged += 1,
+            },
+            Some(bakery) => {
+                let changes = BakeryChanges {
+                    profit_margin: Some(fixture.profit_margin),
+                    ..BakeryChanges::default()
+                };
+                let bakery = repo.update_bakery(bakery.id, changes).await?;
+                bakeries.insert(bakery.name.clone(), bakery);
+                loaded.bakeries.updated += 1;
+            }
+            None => new_bakeries.push(NewBakery {
+                name: name.to_owned(),
+                profit_margin: fixture.profit_margin,
+            }),
+        }
+    }
+    loaded.bakeries.created = new_bakeries.len();
+    for bakery in repo.create_bakeries(new_bakeries).await? {
+        bakeries.insert(bakery.name.clone(), bakery);
+    }
+
+    // The first chef of each name at a bakery is the one fixtures update, or the first
+    // fired one when no chef of that name works there.
+    let mut chefs: HashMap<(i32, String), chef::Model> = HashMap::new();
+    for chef in repo
+        .list_chefs()
+        .await?
+        .into_iter()
+        .chain(fired_chefs(repo).await?)
+    {
+        chefs
+            .entry((chef.bakery_id, chef.name.clone()))
+            .or_insert(chef);
+    }
+    let mut new_chefs: Vec<NewChef> = vec![];
+    for fixture in &fixtures.chefs {
+        let bakery_id = bakeries[fixture.bakery.trim()].id;
+        let name = fixture.name.trim();
+        // Compared as they would be stored.
+        let contact_details = fixture
+            .contact_details
+            .clone()
+            .map(validation::contact_details)
+            .transpose()
+            .map_err(DbErr::from)?;
+        if let Some(new) = new_chefs
+            .iter_mut()
+            .find(|new| new.bakery_id == bakery_id && new.name == name)
+        {
+            new.contact_details = contact_details;
+            continue;
+        }
+        let key = (bakery_id, name.to_owned());
+        let mut restored = false;
+        if let Some(chef) = chefs.get_mut(&key).filter(|chef| chef.deleted_at.is_some()) {
+            *chef = repo.restore_chef(chef.id).await?;
+            restored = true;
+        }
+        match chefs.get(&key) {
+            Some(chef) if chef.contact_details == contact_details => match restored {
+                true => loaded.chefs.updated += 1,
+                false => loaded.chefs.unchanged += 1,
+            },
+            Some(chef) => {
+                let changes = ChefChanges {
+                    contact_details: Some(contact_details),
+                    ..ChefChanges::default()
+                };
+                let chef = repo.update_chef(chef.id, changes).await?;
+                chefs.insert(key, chef);
+                loaded.chefs.updated += 1;
+            }
+            None => new_chefs.push(NewChef {
+                name: name.to_owned(),
+                bakery_id,
+                contact_details,
+            }),
+        }
+    }
+    loaded.chefs.created = new_chefs.len();
+    repo.hire_chefs(new_chefs).await?;
+    Ok(loaded)
+}
+
+#[derive(Debug)]
+pub enum FixtureError {
+    Read(PathBuf, std::io::Error),
+    Write(PathBuf, std::io::Error),
+    Parse(PathBuf, String),
+    UnknownFormat(PathBuf),
+    /// CSV has room for bakeries or chefs, not both.
+    CsvOutput(PathBuf),
+    /// A chef's bakery is neither among the fixtures nor in the database.
+    UnknownBakery {
+        chef: String,
+        bakery: String,
+    },
+    /// A chef's bakery is deleted, and not among the fixtures to bring it back.
+    DeletedBakery {
+        chef: String,
+        bakery: String,
+    },
+    Db(DbErr),
+}
+
+impl fmt::Display for FixtureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FixtureError::Read(path, err) => write!(f, "cannot read {}: {}", path.display(), err),
+            FixtureError::Write(path, err) => {
+                write!(f, "cannot write {}: {}", path.display(), err)
+            }
+            FixtureError::Parse(path, err) => {
+                write!(f, "invalid fixtures {}: {}", path.display(), err)
+            }
+            FixtureError::UnknownFormat(path) => write!(
+                f,
+                "{} is not a .yaml, .yml, .json or .csv file",
+                path.display()
+            ),
+            FixtureError::CsvOutput(path) => write!(
+                f,
+                "cannot write {}: fixtures are written as YAML or JSON",
+                path.display()
+            ),
+            FixtureError::UnknownBakery { chef, bakery } => {
+                write!(f, "chef {} works at unknown bakery {:?}", chef, bakery)
+            }
+            FixtureError::DeletedBakery { chef, bakery } => write!(
+                f,
+                "chef {} works at deleted bakery {:?}; restore it or list it among the bakeries",
+                chef, bakery
+            ),
+            FixtureError::Db(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for FixtureError {}
+
+impl From<DbErr> for FixtureError {
+    fn from(err: DbErr) -> Self {
+        FixtureError::Db(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::DatabaseConfig;
+    use crate::repository::{MemoryRepository, SeaOrmRepository};
+    use futures::executor::block_on;
+    use migration::{Migrator, MigratorTrait};
+
+    fn sample() -> Fixtures {
+        Fixtures::read(Path::new("fixtures/sample.yaml")).unwrap()
+    }
+
+    #[test]
+    fn test_formats() {
+        let json = r#"{
+            "bakeries": [{ "name": "Le Fournil", "profit_margin": 12.5 }],
+            "chefs": [{
+                "name": "Odile",
+                "bakery": "Le Fournil",
+                "contact_details": {
+                    "emails": ["odile@example.com", "odile@fournil.fr"],
+                    "preferred": "email"
+                }
+            }]
+        }"#;
+        let yaml = "
+            bakeries:
+              - name: Le Fournil
+                profit_margin: 12.5
+            chefs:
+              - name: Odile
+                bakery: Le Fournil
+                contact_details:
+                  emails: [odile@example.com, odile@fournil.fr]
+                  preferred: email
+        ";
+        let bakeries = "name,profit_margin\nLe Fournil,12.5\n";
+        let chefs = "name,bakery,emails,phones,preferred\n\
+                     Odile,Le Fournil,odile@example.com; odile@fournil.fr,,email\n";
+
+        let expected = Fixtures::parse(json, FileFormat::Json).unwrap();
+        assert_eq!(
+            expected.chefs[0]
+                .contact_details
+                .as_ref()
+                .unwrap()
+                .emails
+                .len(),
+            2
+        );
+        assert_eq!(Fixtures::parse(yaml, FileFormat::Yaml).unwrap(), expected);
+        let mut csv = Fixtures::parse(bakeries, FileFormat::Csv).unwrap();
+        csv.extend(Fixtures::parse(chefs, FileFormat::Csv).unwrap());
+        assert_eq!(csv, expected);
+
+        let csv = Fixtures::parse("name,bakery\nMarie,Le Fournil\n", FileFormat::Csv).unwrap();
+        assert_eq!(csv.chefs[0].contact_details, None);
+        assert!(Fixtures::parse("name,flavour\nLe Fournil,rye\n", FileFormat::Csv).is_err());
+        assert!(Fixtures::parse("bakeries: [{ nom: Le Fournil }]", FileFormat::Yaml).is_err());
+        // A missing margin would otherwise reset the stored one.
+        assert!(Fixtures::parse("bakeries: [{ name: Le Fournil }]", FileFormat::Yaml).is_err());
+        assert!(Fixtures::parse("name\nLe Fournil\n", FileFormat::Csv).is_err());
+        assert!(matches!(
+            Fixtures::read(Path::new("fixtures/sample.toml")),
+            Err(FixtureError::UnknownFormat(_))
+        ));
+    }
+
+    #[test]
+    fn test_load() {
+        block_on(async {
+            let repo = MemoryRepository::default();
+            let loaded = load(&repo, &sample()).await.unwrap();
+            assert_eq!(loaded.bakeries.created, 3);
+            assert_eq!(loaded.chefs.created, 4);
+            let again = load(&repo, &sample()).await.unwrap();
+            assert_eq!(again.bakeries.unchanged, 3);
+            assert_eq!(again.chefs.unchanged, 4);
+            assert_eq!((again.bakeries.created, again.chefs.created), (0, 0));
+
+            let boulangerie = repo
+                .find_bakery_by_name("La Boulangerie")
+                .await
+                .unwrap()
+                .unwrap();
+            let chefs = repo.list_chefs_of(boulangerie.id).await.unwrap();
+            let names: Vec<_> = chefs.iter().map(|chef| chef.name.as_str()).collect();
+            assert_eq!(names, ["Jolie", "Charles", "Madeleine", "Frederic"]);
+
+            // Chefs may work at bakeries already in the database.
+            let mut changed = Fixtures::parse(
+                "
+                bakeries:
+                  - name: La Boulangerie
+                    profit_margin: 20.0
+                chefs:
+                  - name: Jolie
+                    bakery: La Boulangerie
+                  - name: Marie
+                    bakery: Happy Bakery
+                ",
+                FileFormat::Yaml,
+            )
+            .unwrap();
+            let loaded = load(&repo, &changed).await.unwrap();
+            let counts = |created, updated, unchanged| Counts {
+                created,
+                updated,
+                unchanged,
+            };
+            assert_eq!(loaded.bakeries, counts(0, 1, 0));
+            assert_eq!(loaded.chefs, counts(1, 1, 0));
+            assert_eq!(repo.chef(chefs[0].id).await.unwrap().contact_details, None);
+            assert_eq!(repo.list_chefs().await.unwrap().len(), 5);
+
+            changed.chefs[1].bakery = "Le Fournil".to_owned();
+            assert!(matches!(
+                load(&repo, &changed).await,
+                Err(FixtureError::UnknownBakery { .. })
+            ));
+            assert_eq!(repo.list_chefs().await.unwrap().len(), 5);
+
+            // Nothing is written by a load that fails part way.
+            let mut failing = changed.clone();
+            failing.bakeries[0].profit_margin = 30.0;
+            failing.bakeries.push(BakeryFixture {
+                name: "Le Fournil".to_owned(),
+                profit_margin: 10.0,
+            });
+            failing.chefs[0].contact_details = Some(ContactDetails {
+                emails: vec!["jolie".to_owned()],
+                ..ContactDetails::default()
+            });
+            assert!(load(&repo, &failing).await.is_err());
+            assert_eq!(
+                repo.bakery(boulangerie.id).await.unwrap().profit_margin,
+                20.0
+            );
+            assert_eq!(repo.find_bakery_by_name("Le Fournil").await.unwrap(), None);
+
+            // Deleted bakeries come back when listed, and only then.
+            let happy = repo
+                .find_bakery_by_name("Happy Bakery")
+                .await
+                .unwrap()
+                .unwrap();
+            repo.delete_bakery(happy.id).await.unwrap();
+            let marie = Fixtures::parse(
+                "chefs: [{ name: Marie, bakery: Happy Bakery }]",
+                FileFormat::Yaml,
+            )
+            .unwrap();
+            assert!(matches!(
+                load(&repo, &marie).await,
+                Err(FixtureError::DeletedBakery { .. })
+            ));
+            let mut restored = marie.clone();
+            restored.bakeries.push(BakeryFixture {
+                name: "Happy Bakery".to_owned(),
+                profit_margin: happy.profit_margin,
+            });
+            let loaded = load(&repo, &restored).await.unwrap();
+            assert_eq!(loaded.bakeries, counts(0, 1, 0));
+            assert_eq!(loaded.chefs, counts(0, 0, 1));
+            assert_eq!(repo.bakery(happy.id).await.unwrap().deleted_at, None);
+
+            // Fired chefs are brought back rather than hired again.
+            repo.fire_chef(chefs[0].id).await.unwrap();
+            let loaded = load(&repo, &sample()).await.unwrap();
+            assert_eq!((loaded.chefs.created, loaded.chefs.updated), (0, 1));
+            let rehired = repo.list_chefs_of(boulangerie.id).await.unwrap();
+            assert_eq!(rehired.len(), 4);
+            assert_eq!(rehired[0].id, chefs[0].id);
+            assert_eq!(rehired[0].deleted_at, None);
+        });
+    }
+
+    #[test]
+    fn test_generate() {
+        let fixtures = generate(7, 40, 3);
+        assert_eq!(fixtures, generate(7, 40, 3));
+        assert_ne!(fixtures, generate(8, 40, 3));
+        assert_eq!((fixtures.bakeries.len(), fixtures.chefs.len()), (40, 120));
+
+        let path = std::env::temp_dir().join("bakery-backend-generated.json");
+        fixtures.write(&path).unwrap();
+        assert_eq!(Fixtures::read(&path).unwrap(), fixtures);
+        std::fs::remove_file(path).unwrap();
+
+        // More rows than a batch of history, through the database.
+        let fixtures = generate(1, 1200, 1);
+        block_on(async {
+            let db = DatabaseConfig::default().connect().await.unwrap();
+            Migrator::up(&db, None).await.unwrap();
+            let repo = SeaOrmRepository::new(&db);
+            let loaded = load(&repo, &fixtures).await.unwrap();
+            assert_eq!(
+                (loaded.bakeries.created, loaded.chefs.created),
+                (1200, 1200)
+            );
+            let again = load(&repo, &fixtures).await.unwrap();
+            assert_eq!(
+                (again.bakeries.unchanged, again.chefs.unchanged),
+                (1200, 1200)
+            );
+
+            let mut failing = generate(2, 1, 1);
+            failing.chefs[0].contact_details = Some(ContactDetails {
+                emails: vec!["not an email".to_owned()],
+                ..ContactDetails::default()
+            });
+            assert!(load(&repo, &failing).await.is_err());
+            let name = &failing.bakeries[0].name;
+            assert_eq!(repo.find_bakery_by_name(name).await.unwrap(), None);
+        });
+    }
+}
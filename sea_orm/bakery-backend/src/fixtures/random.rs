@@ -0,0 +1,101 @@
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use super::{BakeryFixture, ChefFixture, Fixtures};
+use crate::contact::{Channel, ContactDetails};
+
+const BAKERY_NAMES: &[&str] = &[
+    "La Boulangerie",
+    "Le Fournil",
+    "Au Bon Pain",
+    "La Mie Dorée",
+    "Le Pétrin",
+    "Maison Levain",
+];
+const PLACES: &[&str] = &[
+    "du Marché",
+    "des Halles",
+    "du Port",
+    "de la Gare",
+    "Saint-Honoré",
+    "du Château",
+];
+const FIRST_NAMES: &[&str] = &[
+    "Jolie",
+    "Charles",
+    "Madeleine",
+    "Frederic",
+    "Odile",
+    "Lucie",
+    "Pierre",
+    "Marie",
+    "Jonas",
+    "Camille",
+];
+const LAST_NAMES: &[&str] = &[
+    "Martin", "Bernard", "Dubois", "Durand", "Lefebvre", "Moreau", "Laurent", "Roux",
+];
+const DOMAINS: &[&str] = &["example.com", "example.fr", "example.org"];
+
+/// `bakeries` made-up bakeries with `chefs_per_bakery` chefs each, the same for the same
+/// `seed`. Names are numbered, so that none is taken twice.
+pub fn generate(seed: u64, bakeries: usize, chefs_per_bakery: usize) -> Fixtures {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    let mut fixtures = Fixtures::default();
+    for number in 1..=bakeries {
+        let bakery = BakeryFixture {
+            name: format!(
+                "{} {} {}",
+                pick(&mut rng, BAKERY_NAMES),
+                pick(&mut rng, PLACES),
+                number
+            ),
+            // In cents, as a person would put it.
+            profit_margin: rng.gen_range(0..5000) as f64 / 100.0,
+        };
+        for number in 1..=chefs_per_bakery {
+            fixtures.chefs.push(chef(&mut rng, &bakery.name, number));
+        }
+        fixtures.bakeries.push(bakery);
+    }
+    fixtures
+}
+
+fn chef(rng: &mut ChaCha8Rng, bakery: &str, number: usize) -> ChefFixture {
+    let (first_name, last_name) = (pick(rng, FIRST_NAMES), pick(rng, LAST_NAMES));
+    let mut details = ContactDetails::default();
+    if rng.gen_bool(0.7) {
+        details.emails.push(format!(
+            "{}.{}{}@{}",
+            first_name.to_lowercase(),
+            last_name.to_lowercase(),
+            number,
+            pick(rng, DOMAINS)
+        ));
+    }
+    if rng.gen_bool(0.5) {
+        let digits: Vec<String> = (0..4)
+            .map(|_| format!("{:02}", rng.gen_range(0..100)))
+            .collect();
+        details.phones.push(format!("+33 6 {}", digits.join(" ")));
+    }
+    let channels: Vec<Channel> = [Channel::Email, Channel::Phone]
+        .into_iter()
+        .filter(|&channel| details.reachable_by(channel))
+        .collect();
+    details.preferred = channels.choose(rng).copied();
+
+    ChefFixture {
+        name: format!("{} {} {}", first_name, last_name, number),
+        bakery: bakery.to_owned(),
+        contact_details: match details.is_empty() {
+            true => None,
+            false => Some(details),
+        },
+    }
+}
+
+fn pick<'a>(rng: &mut ChaCha8Rng, words: &[&'a str]) -> &'a str {
+    words.choose(rng).expect("the word lists are not empty")
+}
@@ -0,0 +1,301 @@
+//! Every change to a bakery or chef is kept as a numbered version in `bakery_history` or
+//! `chef_history`: what was done, the row before and after as JSON, who did it and when.
+//! The repositories write the versions along with the change itself, and [`state_at`]
+//! rebuilds a row as it was at some time from them.
+//!
+//! Only writes made through a repository are tracked: the entities' own hooks do not know
+//! the actor, so changes made with plain SQL or `ActiveModel::save` leave no version, and
+//! show up as the old values of the row's next one. Versions are unique per row, so of two
+//! writers numbering the same version, only one commits.
+//!
+//! Rows from before the history tables get their first version the next time they
+//! change, with the values they had until then as its old ones.
+
+use sea_orm::entity::prelude::*;
+use sea_orm::sea_query::Expr;
+use sea_orm::{ActiveValue, ConnectionTrait, QueryOrder, QuerySelect};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use std::collections::HashMap;
+
+use crate::entities::{bakery, bakery_history, chef, chef_history};
+
+/// Who changes are made by when the repository is not told.
+pub const DEFAULT_ACTOR: &str = "system";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize, Deserialize)]
+#[sea_orm(rs_type = "String", db_type = "String(Some(8))")]
+#[serde(rename_all = "lowercase")]
+pub enum Operation {
+    #[sea_orm(string_value = "insert")]
+    Insert,
+    #[sea_orm(string_value = "update")]
+    Update,
+    /// A soft delete, after which the row can still be restored.
+    #[sea_orm(string_value = "delete")]
+    Delete,
+    #[sea_orm(string_value = "restore")]
+    Restore,
+    /// The row is gone for good.
+    #[sea_orm(string_value = "purge")]
+    Purge,
+}
+
+/// A version of a row, with the row before the change (`None` for an insert) and after
+/// it (`None` for a purge).
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Change<M> {
+    /// From 1, for each row.
+    pub version: i32,
+    pub operation: Operation,
+    pub old: Option<M>,
+    pub new: Option<M>,
+    pub actor: String,
+    pub changed_at: DateTimeUtc,
+}
+
+impl<M> Change<M> {
+    pub fn map<N, E>(self, mut f: impl FnMut(M) -> Result<N, E>) -> Result<Change<N>, E> {
+        Ok(Change {
+            version: self.version,
+            operation: self.operation,
+            old: self.old.map(&mut f).transpose()?,
+            new: self.new.map(&mut f).transpose()?,
+            actor: self.actor,
+            changed_at: self.changed_at,
+        })
+    }
+}
+
+/// A row before and after a change, `None` where there is none.
+pub type Rows<M> = (Option<M>, Option<M>);
+
+/// The row as it was at `at`, from all its versions oldest first: `None` before it was
+/// inserted or after it was purged.
+pub fn state_at<M: Clone>(changes: &[Change<M>], at: DateTimeUtc) -> Option<M> {
+    match changes.iter().rev().find(|change| change.changed_at <= at) {
+        Some(change) => change.new.clone(),
+        // Until its first version, the row was as that version found it.
+        None => changes.first().and_then(|change| change.old.clone()),
+    }
+}
+
+/// A history table, keeping the versions of rows of type `Tracked`.
+pub trait History: EntityTrait {
+    type Tracked: Clone + PartialEq + Serialize + DeserializeOwned + Send + Sync;
+    type ActiveModel: ActiveModelTrait<Entity = Self> + Send;
+
+    fn id_of(tracked: &Self::Tracked) -> i32;
+
+    /// The column with the id of the row changed.
+    fn tracked_id() -> Self::Column;
+
+    fn version() -> Self::Column;
+
+    fn active_model(id: i32, change: Change<Json>) -> <Self as History>::ActiveModel;
+
+    fn change(model: Self::Model) -> Change<Json>;
+}
+
+impl History for bakery_history::Entity {
+    type Tracked = bakery::Model;
+    type ActiveModel = bakery_history::ActiveModel;
+
+    fn id_of(bakery: &bakery::Model) -> i32 {
+        bakery.id
+    }
+
+    fn tracked_id() -> bakery_history::Column {
+        bakery_history::Column::BakeryId
+    }
+
+    fn version() -> bakery_history::Column {
+        bakery_history::Column::Version
+    }
+
+    fn active_model(id: i32, change: Change<Json>) -> bakery_history::ActiveModel {
+        bakery_history::ActiveModel {
+            id: ActiveValue::NotSet,
+            bakery_id: ActiveValue::Set(id),
+            version: ActiveValue::Set(change.version),
+            operation: ActiveValue::Set(change.operation),
+            old_values: ActiveValue::Set(change.old),
+            new_values: ActiveValue::Set(change.new),
+            actor: ActiveValue::Set(change.actor),
+            changed_at: ActiveValue::Set(change.changed_at),
+        }
+    }
+
+    fn change(model: bakery_history::Model) -> Change<Json> {
+        Change {
+            version: model.version,
+            operation: model.operation,
+            old: model.old_values,
+            new: model.new_values,
+            actor: model.actor,
+            changed_at: model.changed_at,
+        }
+    }
+}
+
+impl History for chef_history::Entity {
+    type Tracked = chef::Model;
+    type ActiveModel = chef_history::ActiveModel;
+
+    fn id_of(chef: &chef::Model) -> i32 {
+        chef.id
+    }
+
+    fn tracked_id() -> chef_history::Column {
+        chef_history::Column::ChefId
+    }
+
+    fn version() -> chef_history::Column {
+        chef_history::Column::Version
+    }
+
+    fn active_model(id: i32, change: Change<Json>) -> chef_history::ActiveModel {
+        chef_history::ActiveModel {
+            id: ActiveValue::NotSet,
+            chef_id: ActiveValue::Set(id),
+            version: ActiveValue::Set(change.version),
+            operation: ActiveValue::Set(change.operation),
+            old_values: ActiveValue::Set(change.old),
+            new_values: ActiveValue::Set(change.new),
+            actor: ActiveValue::Set(change.actor),
+            changed_at: ActiveValue::Set(change.changed_at),
+        }
+    }
+
+    fn change(model: chef_history::Model) -> Change<Json> {
+        Change {
+            version: model.version,
+            operation: model.operation,
+            old: model.old_values,
+            new: model.new_values,
+            actor: model.actor,
+            changed_at: model.changed_at,
+        }
+    }
+}
+
+/// Rows recorded per statement, well within what every backend takes in parameters.
+const BATCH_SIZE: usize = 1000;
+
+/// Writes a version for each row `(old, new)` that differs, numbered after the last
+/// version of that row.
+pub async fn record<H: History, C: ConnectionTrait>(
+    db: &C,
+    operation: Operation,
+    actor: &str,
+    changed_at: DateTimeUtc,
+    changes: Vec<Rows<H::Tracked>>,
+) -> Result<(), DbErr> {
+    let mut changes = changes
+        .into_iter()
+        .filter(|(old, new)| old != new)
+        .map(|(old, new)| {
+            let row = new.as_ref().or(old.as_ref()).expect("a change has a row");
+            (H::id_of(row), (old, new))
+        });
+    loop {
+        let batch: Vec<_> = changes.by_ref().take(BATCH_SIZE).collect();
+        if batch.is_empty() {
+            return Ok(());
+        }
+        record_batch::<H, C>(db, operation, actor, changed_at, batch).await?;
+    }
+}
+
+async fn record_batch<H: History, C: ConnectionTrait>(
+    db: &C,
+    operation: Operation,
+    actor: &str,
+    changed_at: DateTimeUtc,
+    changes: Vec<(i32, Rows<H::Tracked>)>,
+) -> Result<(), DbErr> {
+    let ids = changes.iter().map(|(id, ..)| *id);
+    let mut versions: HashMap<i32, i32> = H::find()
+        .select_only()
+        .column(H::tracked_id())
+        .column_as(Expr::col(H::version()).max(), "version")
+        .filter(H::tracked_id().is_in(ids))
+        .group_by(H::tracked_id())
+        .into_tuple()
+        .all(db)
+        .await?
+        .into_iter()
+        .collect();
+    let mut models = vec![];
+    for (id, (old, new)) in changes {
+        let version = versions.entry(id).or_default();
+        *version += 1;
+        let change = Change {
+            version: *version,
+            operation,
+            old,
+            new,
+            actor: actor.to_owned(),
+            changed_at,
+        };
+        let change = change.map(serde_json::to_value).map_err(json_err)?;
+        models.push(H::active_model(id, change));
+    }
+    H::insert_many(models).exec(db).await?;
+    Ok(())
+}
+
+/// The versions of row `id`, oldest first.
+pub async fn changes<H: History, C: ConnectionTrait>(
+    db: &C,
+    id: i32,
+) -> Result<Vec<Change<H::Tracked>>, DbErr> {
+    H::find()
+        .filter(H::tracked_id().eq(id))
+        .order_by_asc(H::version())
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|model| {
+            H::change(model)
+                .map(serde_json::from_value)
+                .map_err(json_err)
+        })
+        .collect()
+}
+
+fn json_err(err: serde_json::Error) -> DbErr {
+    DbErr::Json(err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    #[test]
+    fn test_state_at() {
+        let at = |minute| Utc.with_ymd_and_hms(2023, 5, 4, 12, minute, 0).unwrap();
+        let change = |version, old, new| Change {
+            version,
+            operation: Operation::Update,
+            old,
+            new,
+            actor: DEFAULT_ACTOR.to_owned(),
+            changed_at: at(version as u32 * 10),
+        };
+        let changes = [
+            change(1, Some("before"), Some("first")),
+            change(2, Some("first"), Some("second")),
+            change(3, Some("second"), None),
+        ];
+
+        assert_eq!(state_at(&changes, at(0)), Some("before"));
+        assert_eq!(state_at(&changes, at(10)), Some("first"));
+        assert_eq!(state_at(&changes, at(19)), Some("first"));
+        assert_eq!(state_at(&changes, at(20)), Some("second"));
+        assert_eq!(state_at(&changes, at(30)), None);
+        assert_eq!(state_at::<&str>(&[], at(0)), None);
+    }
+}
@@ -0,0 +1,15 @@
+//! Bakeries and their chefs, managed through the `bakery-backend` command line.
+
+pub mod cli;
+pub mod config;
+pub mod contact;
+pub mod entities;
+pub mod fixtures;
+pub mod history;
+pub mod listing;
+pub mod output;
+pub mod repository;
+#[cfg(test)]
+mod schema;
+pub mod soft_delete;
+pub mod validation;
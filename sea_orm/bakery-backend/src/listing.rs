@@ -0,0 +1,587 @@
+//! Filtered, sorted and paginated lists of bakeries and chefs.
+//!
+//! A [`ListQuery`] is the same whoever lists: [`fetch`] turns it into a `Select` on a
+//! database and [`page`] applies it to models already in memory, with the same results.
+//! Names are matched ignoring the case of ASCII letters only, as SQLite's `LOWER` does;
+//! Postgres and MySQL lower other letters too, so `é` also finds `Élise` on those.
+//! Pages are either numbered, skipping rows with `OFFSET`, or follow a [`Cursor`], which
+//! holds the sort keys of the last row seen so the next page starts right after it
+//! however many rows were added or removed before.
+
+use sea_orm::sea_query::{Expr, Func, LikeExpr, SimpleExpr};
+use sea_orm::*;
+use serde::{Deserialize, Serialize};
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::entities::{bakery, chef};
+use crate::soft_delete::Scope;
+
+pub const DEFAULT_PAGE_SIZE: u64 = 20;
+
+/// The most rows the CLI shows on a page.
+pub const MAX_PAGE_SIZE: u64 = 1000;
+
+/// The most rows `LIMIT` and `OFFSET` take, being signed 64-bit numbers in SQL.
+const MAX_ROWS: u64 = i64::MAX as u64;
+
+/// Which rows of an entity to list.
+pub trait Filter: Send + Sync {
+    type Entity: EntityTrait;
+
+    fn condition(&self) -> Condition;
+
+    /// Whether `model` is among the rows `condition` selects.
+    fn matches(&self, model: &<Self::Entity as EntityTrait>::Model) -> bool;
+}
+
+/// A column rows can be sorted and paged by.
+pub trait SortKey: Copy + PartialEq + Send + Sync {
+    type Entity: EntityTrait;
+
+    /// The primary key, which breaks ties so every row has its own place.
+    const ID: Self;
+
+    fn column(self) -> <Self::Entity as EntityTrait>::Column;
+
+    fn value(self, model: &<Self::Entity as EntityTrait>::Model) -> Key;
+
+    /// Whether `key` could be a value of this column.
+    fn accepts(self, key: &Key) -> bool;
+}
+
+/// The value of a sort key, as stored in a cursor.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Key {
+    Int(i64),
+    Float(f64),
+    Text(String),
+}
+
+impl Key {
+    fn cmp(&self, other: &Key) -> Ordering {
+        match (self, other) {
+            (Key::Int(a), Key::Int(b)) => a.cmp(b),
+            (Key::Float(a), Key::Float(b)) => a.total_cmp(b),
+            (Key::Text(a), Key::Text(b)) => a.cmp(b),
+            // A cursor of another sort; `check` turns those away first.
+            _ => Ordering::Equal,
+        }
+    }
+}
+
+impl From<Key> for Value {
+    fn from(key: Key) -> Value {
+        match key {
+            Key::Int(value) => value.into(),
+            Key::Float(value) => value.into(),
+            Key::Text(value) => value.into(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Direction {
+    #[default]
+    Asc,
+    Desc,
+}
+
+impl From<Direction> for Order {
+    fn from(direction: Direction) -> Order {
+        match direction {
+            Direction::Asc => Order::Asc,
+            Direction::Desc => Order::Desc,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Sort<S> {
+    pub key: S,
+    pub direction: Direction,
+}
+
+/// `name` sorts by name, `-name` by name in reverse.
+impl<S: FromStr<Err = String>> FromStr for Sort<S> {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (direction, key) = match s.strip_prefix('-') {
+            Some(key) => (Direction::Desc, key),
+            None => (Direction::Asc, s),
+        };
+        Ok(Sort {
+            key: key.parse()?,
+            direction,
+        })
+    }
+}
+
+/// The sort keys of the last row of a page, for the page after it. Written as a JSON
+/// array, but meant to be passed back as it is.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Cursor(Vec<Key>);
+
+impl fmt::Display for Cursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let json = serde_json::to_string(&self.0).map_err(|_| fmt::Error)?;
+        write!(f, "{}", json)
+    }
+}
+
+impl FromStr for Cursor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_json::from_str(s).map_err(|_| format!("{:?} is not a cursor", s))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Page {
+    /// The `number`th page, from 1, of `size` rows.
+    Offset { number: u64, size: u64 },
+    /// `size` rows from right after `cursor`, or from the start without one.
+    After { cursor: Option<Cursor>, size: u64 },
+}
+
+impl Default for Page {
+    fn default() -> Self {
+        Page::Offset {
+            number: 1,
+            size: DEFAULT_PAGE_SIZE,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ListQuery<F, S> {
+    pub filter: F,
+    /// Ties, and an empty list, are sorted by id.
+    pub sort: Vec<Sort<S>>,
+    pub page: Page,
+}
+
+/// Everything, by id, a page at a time.
+impl<F: Default, S> Default for ListQuery<F, S> {
+    fn default() -> Self {
+        ListQuery {
+            filter: F::default(),
+            sort: vec![],
+            page: Page::default(),
+        }
+    }
+}
+
+impl<F, S: SortKey> ListQuery<F, S> {
+    fn keys(&self) -> Vec<Sort<S>> {
+        let mut keys = self.sort.clone();
+        if !keys.iter().any(|sort| sort.key == S::ID) {
+            keys.push(Sort {
+                key: S::ID,
+                direction: Direction::Asc,
+            });
+        }
+        keys
+    }
+}
+
+/// A page of rows.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Listing<T> {
+    pub items: Vec<T>,
+    /// How many rows match the filter, on every page.
+    pub total: u64,
+    /// The page after this one, unless this is the last.
+    pub next: Option<Page>,
+}
+
+impl<T> Listing<T> {
+    pub fn map<U>(self, f: impl FnMut(T) -> U) -> Listing<U> {
+        Listing {
+            items: self.items.into_iter().map(f).collect(),
+            total: self.total,
+            next: self.next,
+        }
+    }
+}
+
+fn cursor<S: SortKey>(keys: &[Sort<S>], model: &<S::Entity as EntityTrait>::Model) -> Cursor {
+    Cursor(keys.iter().map(|sort| sort.key.value(model)).collect())
+}
+
+/// Fails unless `cursor` was made for a list sorted by `keys`.
+fn check<S: SortKey>(keys: &[Sort<S>], cursor: &Cursor) -> Result<(), DbErr> {
+    let matches = cursor.0.len() == keys.len()
+        && keys
+            .iter()
+            .zip(&cursor.0)
+            .all(|(sort, key)| sort.key.accepts(key));
+    match matches {
+        true => Ok(()),
+        false => Err(DbErr::Custom(format!(
+            "cursor {} does not belong to this sort order",
+            cursor
+        ))),
+    }
+}
+
+/// Rows before page `number` of `size` rows, or an error past the last page there could be.
+fn skipped(number: u64, size: u64) -> Result<u64, DbErr> {
+    number
+        .saturating_sub(1)
+        .checked_mul(size)
+        .filter(|skipped| skipped.checked_add(size).is_some_and(|end| end <= MAX_ROWS))
+        .ok_or_else(|| DbErr::Custom(format!("page {} of {} rows is out of range", number, size)))
+}
+
+/// Rows to read for a page of `size` after a cursor: one more tells whether there is a
+/// next page.
+fn look_ahead(size: u64) -> Result<u64, DbErr> {
+    size.checked_add(1)
+        .filter(|rows| *rows <= MAX_ROWS)
+        .ok_or_else(|| DbErr::Custom(format!("pages of {} rows are out of range", size)))
+}
+
+/// Rows past `cursor` in the order of `keys`: `(a > x) OR (a = x AND b > y) OR ...`,
+/// with `<` for descending keys.
+fn after<S: SortKey>(keys: &[Sort<S>], cursor: &Cursor) -> Condition {
+    let mut any = Condition::any();
+    for (i, (sort, key)) in keys.iter().zip(&cursor.0).enumerate() {
+        let mut all = Condition::all();
+        for (equal, key) in keys.iter().zip(&cursor.0).take(i) {
+            all = all.add(equal.key.column().eq(key.clone()));
+        }
+        let column = sort.key.column();
+        all = all.add(match sort.direction {
+            Direction::Asc => column.gt(key.clone()),
+            Direction::Desc => column.lt(key.clone()),
+        });
+        any = any.add(all);
+    }
+    any
+}
+
+/// The page of `query` on a database.
+pub async fn fetch<C, F, S>(
+    db: &C,
+    query: &ListQuery<F, S>,
+) -> Result<Listing<<F::Entity as EntityTrait>::Model>, DbErr>
+where
+    C: ConnectionTrait,
+    F: Filter,
+    S: SortKey<Entity = F::Entity>,
+    <F::Entity as EntityTrait>::Model: Sync,
+{
+    let mut select = F::Entity::find();
+    let condition = query.filter.condition();
+    if !condition.is_empty() {
+        select = select.filter(condition);
+    }
+    let total = select.clone().count(db).await?;
+
+    let keys = query.keys();
+    for sort in &keys {
+        select = select.order_by(sort.key.column(), sort.direction.into());
+    }
+
+    match &query.page {
+        Page::Offset { number, size } => {
+            let skipped = skipped(*number, *size)?;
+            let items = select.offset(skipped).limit(*size).all(db).await?;
+            let next = (skipped + (items.len() as u64) < total).then(|| Page::Offset {
+                number: number + 1,
+                size: *size,
+            });
+            Ok(Listing { items, total, next })
+        }
+        Page::After { cursor, size } => {
+            if let Some(cursor) = cursor {
+                check(&keys, cursor)?;
+                select = select.filter(after(&keys, cursor));
+            }
+            let mut items = select.limit(look_ahead(*size)?).all(db).await?;
+            let more = items.len() as u64 > *size;
+            items.truncate(*size as usize);
+            let next = match items.last() {
+                Some(last) if more => Some(Page::After {
+                    cursor: Some(self::cursor(&keys, last)),
+                    size: *size,
+                }),
+                _ => None,
+            };
+            Ok(Listing { items, total, next })
+        }
+    }
+}
+
+/// The page of `query` among `models`, as [`fetch`] would list them.
+pub fn page<F, S>(
+    models: Vec<<F::Entity as EntityTrait>::Model>,
+    query: &ListQuery<F, S>,
+) -> Result<Listing<<F::Entity as EntityTrait>::Model>, DbErr>
+where
+    F: Filter,
+    S: SortKey<Entity = F::Entity>,
+{
+    let keys = query.keys();
+    let order = |a: &Cursor, b: &Cursor| {
+        keys.iter()
+            .zip(a.0.iter().zip(&b.0))
+            .map(|(sort, (a, b))| match sort.direction {
+                Direction::Asc => a.cmp(b),
+                Direction::Desc => b.cmp(a),
+            })
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or(Ordering::Equal)
+    };
+
+    let mut rows: Vec<_> = models
+        .into_iter()
+        .filter(|model| query.filter.matches(model))
+        .map(|model| (cursor(&keys, &model), model))
+        .collect();
+    rows.sort_by(|(a, _), (b, _)| order(a, b));
+    let total = rows.len() as u64;
+
+    match &query.page {
+        Page::Offset { number, size } => {
+            let skipped = skipped(*number, *size)?;
+            let items: Vec<_> = rows
+                .into_iter()
+                .skip(skipped as usize)
+                .take(*size as usize)
+                .map(|(_, model)| model)
+                .collect();
+            let next = (skipped + (items.len() as u64) < total).then(|| Page::Offset {
+                number: number + 1,
+                size: *size,
+            });
+            Ok(Listing { items, total, next })
+        }
+        Page::After { cursor, size } => {
+            look_ahead(*size)?;
+            if let Some(cursor) = cursor {
+                check(&keys, cursor)?;
+                rows.retain(|(keys, _)| order(keys, cursor).is_gt());
+            }
+            let more = rows.len() as u64 > *size;
+            rows.truncate(*size as usize);
+            let next = match rows.last() {
+                Some((last, _)) if more => Some(Page::After {
+                    cursor: Some(last.clone()),
+                    size: *size,
+                }),
+                _ => None,
+            };
+            let items = rows.into_iter().map(|(_, model)| model).collect();
+            Ok(Listing { items, total, next })
+        }
+    }
+}
+
+/// `column` holds `needle`, ignoring the case of ASCII letters, with `%` and `_` taken
+/// literally.
+fn contains(column: impl ColumnTrait, needle: &str) -> SimpleExpr {
+    let escaped: String = needle
+        .to_ascii_lowercase()
+        .chars()
+        .flat_map(|c| match c {
+            '%' | '_' | '\\' => vec!['\\', c],
+            c => vec![c],
+        })
+        .collect();
+    Expr::expr(Func::lower(Expr::col((column.entity_name(), column))))
+        .like(LikeExpr::new(format!("%{}%", escaped)).escape('\\'))
+}
+
+fn unknown_key(key: &str, keys: &[&str]) -> String {
+    format!("cannot sort by {:?}, only by {}", key, keys.join(", "))
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BakeryFilter {
+    /// Part of the name, in any case of ASCII letters.
+    pub name_contains: Option<String>,
+    pub min_profit_margin: Option<f64>,
+    pub max_profit_margin: Option<f64>,
+    pub scope: Scope,
+}
+
+impl Filter for BakeryFilter {
+    type Entity = bakery::Entity;
+
+    fn condition(&self) -> Condition {
+        Condition::all()
+            .add_option(
+                self.name_contains
+                    .as_deref()
+                    .map(|name| contains(bakery::Column::Name, name)),
+            )
+            .add_option(
+                self.min_profit_margin
+                    .map(|min| bakery::Column::ProfitMargin.gte(min)),
+            )
+            .add_option(
+                self.max_profit_margin
+                    .map(|max| bakery::Column::ProfitMargin.lte(max)),
+            )
+            .add_option(self.scope.condition(bakery::Column::DeletedAt))
+    }
+
+    fn matches(&self, bakery: &bakery::Model) -> bool {
+        let name = bakery.name.to_ascii_lowercase();
+        self.name_contains
+            .as_ref()
+            .is_none_or(|part| name.contains(&part.to_ascii_lowercase()))
+            && self
+                .min_profit_margin
+                .is_none_or(|min| bakery.profit_margin >= min)
+            && self
+                .max_profit_margin
+                .is_none_or(|max| bakery.profit_margin <= max)
+            && self.scope.includes(bakery.deleted_at)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BakerySort {
+    Id,
+    Name,
+    ProfitMargin,
+}
+
+impl SortKey for BakerySort {
+    type Entity = bakery::Entity;
+
+    const ID: Self = BakerySort::Id;
+
+    fn column(self) -> bakery::Column {
+        match self {
+            BakerySort::Id => bakery::Column::Id,
+            BakerySort::Name => bakery::Column::Name,
+            BakerySort::ProfitMargin => bakery::Column::ProfitMargin,
+        }
+    }
+
+    fn value(self, bakery: &bakery::Model) -> Key {
+        match self {
+            BakerySort::Id => Key::Int(bakery.id.into()),
+            BakerySort::Name => Key::Text(bakery.name.clone()),
+            BakerySort::ProfitMargin => Key::Float(bakery.profit_margin),
+        }
+    }
+
+    fn accepts(self, key: &Key) -> bool {
+        matches!(
+            (self, key),
+            (BakerySort::Id, Key::Int(_))
+                | (BakerySort::Name, Key::Text(_))
+                | (BakerySort::ProfitMargin, Key::Float(_))
+        )
+    }
+}
+
+impl FromStr for BakerySort {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "id" => Ok(BakerySort::Id),
+            "name" => Ok(BakerySort::Name),
+            "profit_margin" => Ok(BakerySort::ProfitMargin),
+            _ => Err(unknown_key(s, &["id", "name", "profit_margin"])),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ChefFilter {
+    /// Part of the name, in any case of ASCII letters.
+    pub name_contains: Option<String>,
+    pub bakery_id: Option<i32>,
+    pub scope: Scope,
+}
+
+impl Filter for ChefFilter {
+    type Entity = chef::Entity;
+
+    fn condition(&self) -> Condition {
+        Condition::all()
+            .add_option(
+                self.name_contains
+                    .as_deref()
+                    .map(|name| contains(chef::Column::Name, name)),
+            )
+            .add_option(self.bakery_id.map(|id| chef::Column::BakeryId.eq(id)))
+            .add_option(self.scope.condition(chef::Column::DeletedAt))
+    }
+
+    fn matches(&self, chef: &chef::Model) -> bool {
+        let name = chef.name.to_ascii_lowercase();
+        self.name_contains
+            .as_ref()
+            .is_none_or(|part| name.contains(&part.to_ascii_lowercase()))
+            && self.bakery_id.is_none_or(|id| chef.bakery_id == id)
+            && self.scope.includes(chef.deleted_at)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChefSort {
+    Id,
+    Name,
+    BakeryId,
+}
+
+impl SortKey for ChefSort {
+    type Entity = chef::Entity;
+
+    const ID: Self = ChefSort::Id;
+
+    fn column(self) -> chef::Column {
+        match self {
+            ChefSort::Id => chef::Column::Id,
+            ChefSort::Name => chef::Column::Name,
+            ChefSort::BakeryId => chef::Column::BakeryId,
+        }
+    }
+
+    fn value(self, chef: &chef::Model) -> Key {
+        match self {
+            ChefSort::Id => Key::Int(chef.id.into()),
+            ChefSort::Name => Key::Text(chef.name.clone()),
+            ChefSort::BakeryId => Key::Int(chef.bakery_id.into()),
+        }
+    }
+
+    fn accepts(self, key: &Key) -> bool {
+        matches!(
+            (self, key),
+            (ChefSort::Id | ChefSort::BakeryId, Key::Int(_)) | (ChefSort::Name, Key::Text(_))
+        )
+    }
+}
+
+impl FromStr for ChefSort {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "id" => Ok(ChefSort::Id),
+            "name" => Ok(ChefSort::Name),
+            "bakery_id" => Ok(ChefSort::BakeryId),
+            _ => Err(unknown_key(s, &["id", "name", "bakery_id"])),
+        }
+    }
+}
+
+pub type BakeryQuery = ListQuery<BakeryFilter, BakerySort>;
+
+pub type ChefQuery = ListQuery<ChefFilter, ChefSort>;
@@ -1,46 +0,0 @@
-use sea_orm_migration::prelude::*;
-
-pub struct Migration;
-
-impl MigrationName for Migration {
-    fn name(&self) -> &str {
-        "m20230429_000001_create_bakery_table"
-    }
-}
-
-#[async_trait::async_trait]
-impl MigrationTrait for Migration {
-    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
-        manager
-            .create_table(
-                Table::create()
-                    .table(Bakery::Table)
-                    .if_not_exists()
-                    .col(
-                        ColumnDef::new(Bakery::Id)
-                            .integer()
-                            .not_null()
-                            .auto_increment()
-                            .primary_key(),
-                    )
-                    .col(ColumnDef::new(Bakery::Name).string().not_null())
-                    .col(ColumnDef::new(Bakery::ProfitMargin).double().not_null())
-                    .to_owned(),
-            )
-            .await
-    }
-
-    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
-        manager
-            .drop_table(Table::drop().table(Bakery::Table).to_owned())
-            .await
-    }
-}
-
-#[derive(Iden)]
-pub enum Bakery {
-    Table,
-    Id,
-    Name,
-    ProfitMargin,
-}
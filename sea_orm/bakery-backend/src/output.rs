@@ -0,0 +1,270 @@
+//! What commands print, as aligned text tables or as JSON.
+
+use sea_orm::prelude::{DateTimeUtc, Json};
+use sea_orm::ActiveEnum;
+use serde::Serialize;
+
+use std::fmt::{self, Write as _};
+
+use crate::contact::ContactDetails;
+use crate::entities::{bakery, chef};
+use crate::history::Change;
+use crate::listing::{Listing, Page, DEFAULT_PAGE_SIZE};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ArgEnum)]
+pub enum Format {
+    Table,
+    Json,
+}
+
+/// A chef along with the name of the bakery employing them.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct ChefRow {
+    pub id: i32,
+    pub name: String,
+    pub bakery_id: i32,
+    pub bakery: String,
+    pub contact_details: Option<ContactDetails>,
+    pub deleted_at: Option<DateTimeUtc>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct MigrationRow {
+    pub name: String,
+    pub status: String,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum Output {
+    Bakeries(Vec<bakery::Model>),
+    Bakery {
+        #[serde(flatten)]
+        bakery: bakery::Model,
+        chefs: Vec<chef::Model>,
+    },
+    Chefs(Vec<ChefRow>),
+    BakeryListing(Listing<bakery::Model>),
+    ChefListing(Listing<ChefRow>),
+    /// The versions of a bakery or chef, as JSON to fit both.
+    History(Vec<Change<Json>>),
+    Migrations(Vec<MigrationRow>),
+    Message {
+        message: String,
+    },
+}
+
+impl Output {
+    pub fn message(message: impl Into<String>) -> Output {
+        Output::Message {
+            message: message.into(),
+        }
+    }
+
+    pub fn history<M: Serialize>(changes: Vec<Change<M>>) -> Output {
+        let changes = changes
+            .into_iter()
+            .map(|change| change.map(serde_json::to_value))
+            .collect::<Result<_, _>>()
+            .expect("models serialize to JSON");
+        Output::History(changes)
+    }
+
+    pub fn render(&self, format: Format) -> String {
+        match format {
+            Format::Json => serde_json::to_string_pretty(self).expect("outputs serialize to JSON"),
+            Format::Table => self.table(),
+        }
+    }
+
+    fn table(&self) -> String {
+        match self {
+            Output::Bakeries(bakeries) => bakery_table(bakeries).to_string(),
+            Output::Bakery { bakery, chefs } => {
+                let mut table = Table::new(["id", "name", "contact_details"]);
+                for chef in chefs {
+                    table.row([
+                        chef.id.to_string(),
+                        chef.name.clone(),
+                        contact_cell(&chef.contact_details),
+                    ]);
+                }
+                format!(
+                    "{}\nchefs:\n{}",
+                    bakery_table(std::slice::from_ref(bakery)),
+                    table
+                )
+            }
+            Output::Chefs(chefs) => chef_table(chefs).to_string(),
+            Output::BakeryListing(listing) => {
+                format!("{}\n{}", bakery_table(&listing.items), footer(listing))
+            }
+            Output::ChefListing(listing) => {
+                format!("{}\n{}", chef_table(&listing.items), footer(listing))
+            }
+            Output::History(changes) => {
+                let mut table =
+                    Table::new(["version", "changed_at", "actor", "operation", "changes"]);
+                for change in changes {
+                    table.row([
+                        change.version.to_string(),
+                        timestamp(change.changed_at),
+                        change.actor.clone(),
+                        change.operation.to_value(),
+                        changes_cell(change),
+                    ]);
+                }
+                table.to_string()
+            }
+            Output::Migrations(migrations) => {
+                let mut table = Table::new(["name", "status"]);
+                for migration in migrations {
+                    table.row([migration.name.clone(), migration.status.clone()]);
+                }
+                table.to_string()
+            }
+            Output::Message { message } => message.clone(),
+        }
+    }
+}
+
+fn bakery_table(bakeries: &[bakery::Model]) -> Table {
+    let mut table = Table::new(["id", "name", "profit_margin"]);
+    for bakery in bakeries {
+        table.row([
+            bakery.id.to_string(),
+            bakery.name.clone(),
+            bakery.profit_margin.to_string(),
+        ]);
+    }
+    table.deleted_at(bakeries.iter().map(|bakery| bakery.deleted_at));
+    table
+}
+
+fn chef_table(chefs: &[ChefRow]) -> Table {
+    let mut table = Table::new(["id", "name", "bakery_id", "bakery", "contact_details"]);
+    for chef in chefs {
+        table.row([
+            chef.id.to_string(),
+            chef.name.clone(),
+            chef.bakery_id.to_string(),
+            chef.bakery.clone(),
+            contact_cell(&chef.contact_details),
+        ]);
+    }
+    table.deleted_at(chefs.iter().map(|chef| chef.deleted_at));
+    table
+}
+
+/// How much of the list the page shows, and how to get the next one.
+fn footer<T>(listing: &Listing<T>) -> String {
+    let shown = format!("{} of {}", listing.items.len(), listing.total);
+    let (next, size) = match &listing.next {
+        Some(Page::Offset { number, size }) => (format!("--page {}", number), size),
+        Some(Page::After {
+            cursor: Some(cursor),
+            size,
+        }) => (format!("--after '{}'", cursor), size),
+        _ => return shown,
+    };
+    match *size == DEFAULT_PAGE_SIZE {
+        true => format!("{}; next: {}", shown, next),
+        false => format!("{}; next: {} --per-page {}", shown, next, size),
+    }
+}
+
+/// The fields a change set, as `name: old → new`, or just `name: new` for an insert.
+fn changes_cell(change: &Change<Json>) -> String {
+    let fields = |row: &Option<Json>| row.as_ref().and_then(Json::as_object).cloned();
+    let (old, new) = match (fields(&change.old), fields(&change.new)) {
+        (old, Some(new)) => (old, new),
+        // Nothing is left to tell about a purged row.
+        (_, None) => return String::new(),
+    };
+    let value = |value: &Json| match value {
+        Json::String(text) => text.clone(),
+        other => other.to_string(),
+    };
+    new.iter()
+        .filter_map(|(name, new)| match old.as_ref().map(|old| old.get(name)) {
+            None if new.is_null() => None,
+            None => Some(format!("{}: {}", name, value(new))),
+            Some(Some(old)) if old == new => None,
+            Some(old) => Some(format!(
+                "{}: {} → {}",
+                name,
+                old.map(value).unwrap_or_default(),
+                value(new)
+            )),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn timestamp(at: DateTimeUtc) -> String {
+    at.format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+fn contact_cell(details: &Option<ContactDetails>) -> String {
+    details
+        .as_ref()
+        .map(ContactDetails::to_string)
+        .unwrap_or_default()
+}
+
+/// Columns padded to their widest cell, under a header.
+struct Table {
+    headers: Vec<String>,
+    rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    fn new<const N: usize>(headers: [&str; N]) -> Table {
+        Table {
+            headers: headers.iter().map(|header| header.to_string()).collect(),
+            rows: vec![],
+        }
+    }
+
+    fn row<const N: usize>(&mut self, cells: [String; N]) {
+        self.rows.push(cells.into());
+    }
+
+    /// A last column with when each row was deleted, if any was.
+    fn deleted_at(&mut self, deleted_at: impl Iterator<Item = Option<DateTimeUtc>>) {
+        let cells: Vec<String> = deleted_at
+            .map(|at| at.map(timestamp).unwrap_or_default())
+            .collect();
+        if cells.iter().all(String::is_empty) {
+            return;
+        }
+        self.headers.push("deleted_at".to_owned());
+        for (row, cell) in self.rows.iter_mut().zip(cells) {
+            row.push(cell);
+        }
+    }
+}
+
+impl fmt::Display for Table {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let widths: Vec<usize> = (0..self.headers.len())
+            .map(|column| {
+                std::iter::once(&self.headers)
+                    .chain(&self.rows)
+                    .map(|row| row[column].chars().count())
+                    .max()
+                    .unwrap_or_default()
+            })
+            .collect();
+
+        let mut lines = vec![];
+        for row in std::iter::once(&self.headers).chain(&self.rows) {
+            let mut line = String::new();
+            for (cell, width) in row.iter().zip(&widths) {
+                write!(line, "{:width$}  ", cell, width = width)?;
+            }
+            lines.push(line.trim_end().to_owned());
+        }
+        write!(f, "{}", lines.join("\n"))
+    }
+}
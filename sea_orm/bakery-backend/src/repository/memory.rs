@@ -0,0 +1,517 @@
+use async_trait::async_trait;
+use sea_orm::prelude::DateTimeUtc;
+use sea_orm::DbErr;
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use super::{
+    bakery_not_found, chef_not_found, deleted_bakery_not_found, deleted_chef_not_found,
+    BakeryChanges, BakeryRepository, ChefChanges, ChefRepository, NewBakery, NewChef, Transaction,
+    Transactional,
+};
+use crate::contact::ContactDetails;
+use crate::entities::{bakery, chef};
+use crate::history::{self, Change, Operation};
+use crate::listing::{self, BakeryQuery, ChefQuery, Listing};
+use crate::soft_delete;
+use crate::validation::{self, ValidationError};
+
+#[derive(Clone, Debug, Default)]
+struct State {
+    bakeries: BTreeMap<i32, bakery::Model>,
+    chefs: BTreeMap<i32, chef::Model>,
+    last_bakery_id: i32,
+    last_chef_id: i32,
+    bakery_history: BTreeMap<i32, Vec<Change<bakery::Model>>>,
+    chef_history: BTreeMap<i32, Vec<Change<chef::Model>>>,
+}
+
+impl State {
+    fn bakery(&self, id: i32) -> Result<&bakery::Model, DbErr> {
+        self.bakeries
+            .get(&id)
+            .filter(|bakery| bakery.deleted_at.is_none())
+            .ok_or_else(|| bakery_not_found(id))
+    }
+
+    fn chef_mut(&mut self, id: i32) -> Result<&mut chef::Model, DbErr> {
+        self.chefs
+            .get_mut(&id)
+            .filter(|chef| chef.deleted_at.is_none())
+            .ok_or_else(|| chef_not_found(id))
+    }
+
+    fn active_bakeries(&self) -> impl Iterator<Item = &bakery::Model> {
+        self.bakeries
+            .values()
+            .filter(|bakery| bakery.deleted_at.is_none())
+    }
+
+    fn active_chefs(&self) -> impl Iterator<Item = &chef::Model> {
+        self.chefs.values().filter(|chef| chef.deleted_at.is_none())
+    }
+
+    /// The bakery `id` would be with a valid `name` and `profit_margin`, as the
+    /// `before_save` of `bakery::ActiveModel` has it.
+    fn validate_bakery(
+        &self,
+        id: i32,
+        name: &str,
+        profit_margin: f64,
+    ) -> Result<bakery::Model, DbErr> {
+        let name = validation::name("bakery", name)?;
+        if self
+            .bakeries
+            .values()
+            .any(|bakery| bakery.id != id && bakery.name == name)
+        {
+            return Err(ValidationError::DuplicateBakeryName { name }.into());
+        }
+        validation::profit_margin(profit_margin)?;
+        Ok(bakery::Model {
+            id,
+            name,
+            profit_margin,
+            deleted_at: None,
+        })
+    }
+
+    fn validate_chef(&self, id: i32, chef: NewChef) -> Result<chef::Model, DbErr> {
+        self.bakery(chef.bakery_id)?;
+        let name = validation::name("chef", &chef.name)?;
+        let contact_details = chef
+            .contact_details
+            .map(validation::contact_details)
+            .transpose()?;
+        Ok(chef::Model {
+            id,
+            name,
+            contact_details,
+            bakery_id: chef.bakery_id,
+            deleted_at: None,
+        })
+    }
+
+    fn insert_bakery(&mut self, bakery: NewBakery) -> Result<bakery::Model, DbErr> {
+        let bakery =
+            self.validate_bakery(self.last_bakery_id + 1, &bakery.name, bakery.profit_margin)?;
+        self.last_bakery_id = bakery.id;
+        self.bakeries.insert(bakery.id, bakery.clone());
+        Ok(bakery)
+    }
+
+    fn insert_chef(&mut self, chef: NewChef) -> Result<chef::Model, DbErr> {
+        let chef = self.validate_chef(self.last_chef_id + 1, chef)?;
+        self.last_chef_id = chef.id;
+        self.chefs.insert(chef.id, chef.clone());
+        Ok(chef)
+    }
+
+    fn delete_bakery(&mut self, id: i32, deleted_at: DateTimeUtc) -> Option<bakery::Model> {
+        let bakery = self.bakeries.get_mut(&id)?;
+        if bakery.deleted_at.is_some() {
+            return None;
+        }
+        bakery.deleted_at = Some(deleted_at);
+        let bakery = bakery.clone();
+        for chef in self.chefs.values_mut() {
+            if chef.bakery_id == id && chef.deleted_at.is_none() {
+                chef.deleted_at = Some(deleted_at);
+            }
+        }
+        Some(bakery)
+    }
+
+    fn fire_chef(&mut self, id: i32, deleted_at: DateTimeUtc) -> Option<chef::Model> {
+        let chef = self.chef_mut(id).ok()?;
+        chef.deleted_at = Some(deleted_at);
+        Some(chef.clone())
+    }
+}
+
+/// Adds a version for each row that differs between `before` and `after`.
+fn record<M: Clone + PartialEq>(
+    history: &mut BTreeMap<i32, Vec<Change<M>>>,
+    before: &BTreeMap<i32, M>,
+    after: &BTreeMap<i32, M>,
+    operation: Operation,
+    actor: &str,
+    changed_at: DateTimeUtc,
+) {
+    let ids: BTreeSet<&i32> = before.keys().chain(after.keys()).collect();
+    for id in ids {
+        let (old, new) = (before.get(id), after.get(id));
+        if old == new {
+            continue;
+        }
+        let versions = history.entry(*id).or_default();
+        versions.push(Change {
+            version: versions.len() as i32 + 1,
+            operation,
+            old: old.cloned(),
+            new: new.cloned(),
+            actor: actor.to_owned(),
+            changed_at,
+        });
+    }
+}
+
+/// The repositories on plain maps. Ids count up from 1 and are never reused, as with an
+/// auto-increment column. Clones share their data.
+///
+/// A transaction works on a copy of the data, which replaces the original when it is
+/// committed, so changes made outside it in the meantime are lost.
+#[derive(Clone, Debug)]
+pub struct MemoryRepository {
+    state: Arc<Mutex<State>>,
+    actor: String,
+    /// The data of the repository this transaction was begun from.
+    outer: Option<Arc<Mutex<State>>>,
+}
+
+impl Default for MemoryRepository {
+    fn default() -> Self {
+        MemoryRepository {
+            state: Arc::default(),
+            actor: history::DEFAULT_ACTOR.to_owned(),
+            outer: None,
+        }
+    }
+}
+
+impl MemoryRepository {
+    /// Records the changes as made by `actor`.
+    pub fn acting_as(self, actor: impl Into<String>) -> Self {
+        MemoryRepository {
+            actor: actor.into(),
+            ..self
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap()
+    }
+
+    /// Makes a change at one time, and records the rows it changed. On error the change
+    /// is undone, as a transaction would be.
+    fn write<T>(
+        &self,
+        operation: Operation,
+        change: impl FnOnce(&mut State, DateTimeUtc) -> Result<T, DbErr>,
+    ) -> Result<T, DbErr> {
+        let mut state = self.state();
+        let (bakeries, chefs) = (state.bakeries.clone(), state.chefs.clone());
+        let last_ids = (state.last_bakery_id, state.last_chef_id);
+        let now = soft_delete::now();
+        let result = change(&mut state, now);
+
+        let state = &mut *state;
+        if result.is_err() {
+            (state.bakeries, state.chefs) = (bakeries, chefs);
+            (state.last_bakery_id, state.last_chef_id) = last_ids;
+            return result;
+        }
+        let actor = &self.actor;
+        record(
+            &mut state.bakery_history,
+            &bakeries,
+            &state.bakeries,
+            operation,
+            actor,
+            now,
+        );
+        record(
+            &mut state.chef_history,
+            &chefs,
+            &state.chefs,
+            operation,
+            actor,
+            now,
+        );
+        result
+    }
+
+    fn find_chefs(&self, matches: impl Fn(&ContactDetails) -> bool) -> Vec<chef::Model> {
+        self.state()
+            .active_chefs()
+            .filter(|chef| chef.contact_details.as_ref().is_some_and(&matches))
+            .cloned()
+            .collect()
+    }
+}
+
+#[async_trait]
+impl BakeryRepository for MemoryRepository {
+    async fn create_bakery(&self, bakery: NewBakery) -> Result<bakery::Model, DbErr> {
+        self.write(Operation::Insert, |state, _| state.insert_bakery(bakery))
+    }
+
+    async fn create_bakeries(&self, bakeries: Vec<NewBakery>) -> Result<Vec<bakery::Model>, DbErr> {
+        self.write(Operation::Insert, |state, _| {
+            bakeries
+                .into_iter()
+                .map(|bakery| state.insert_bakery(bakery))
+                .collect()
+        })
+    }
+
+    async fn bakery(&self, id: i32) -> Result<bakery::Model, DbErr> {
+        self.state().bakery(id).cloned()
+    }
+
+    async fn find_bakery_by_name(&self, name: &str) -> Result<Option<bakery::Model>, DbErr> {
+        let state = self.state();
+        let bakery = state
+            .active_bakeries()
+            .find(|bakery| bakery.name == name)
+            .cloned();
+        Ok(bakery)
+    }
+
+    async fn list_bakeries(&self) -> Result<Vec<bakery::Model>, DbErr> {
+        Ok(self.state().active_bakeries().cloned().collect())
+    }
+
+    async fn query_bakeries(&self, query: &BakeryQuery) -> Result<Listing<bakery::Model>, DbErr> {
+        let bakeries = self.state().bakeries.values().cloned().collect();
+        listing::page(bakeries, query)
+    }
+
+    async fn update_bakery(&self, id: i32, changes: BakeryChanges) -> Result<bakery::Model, DbErr> {
+        self.write(Operation::Update, |state, _| {
+            let bakery = state.bakery(id)?;
+            if changes.is_empty() {
+                return Ok(bakery.clone());
+            }
+
+            let name = changes.name.unwrap_or_else(|| bakery.name.clone());
+            let profit_margin = changes.profit_margin.unwrap_or(bakery.profit_margin);
+            let bakery = state.validate_bakery(id, &name, profit_margin)?;
+            state.bakeries.insert(id, bakery.clone());
+            Ok(bakery)
+        })
+    }
+
+    async fn delete_bakery(&self, id: i32) -> Result<bakery::Model, DbErr> {
+        self.write(Operation::Delete, |state, now| {
+            state
+                .delete_bakery(id, now)
+                .ok_or_else(|| bakery_not_found(id))
+        })
+    }
+
+    async fn delete_bakeries(&self, ids: &[i32]) -> Result<u64, DbErr> {
+        self.write(Operation::Delete, |state, now| {
+            Ok(ids
+                .iter()
+                .filter_map(|&id| state.delete_bakery(id, now))
+                .count() as u64)
+        })
+    }
+
+    async fn restore_bakery(&self, id: i32) -> Result<bakery::Model, DbErr> {
+        self.write(Operation::Restore, |state, _| {
+            let bakery = state
+                .bakeries
+                .get_mut(&id)
+                .filter(|bakery| bakery.deleted_at.is_some())
+                .ok_or_else(|| deleted_bakery_not_found(id))?;
+            let deleted_at = bakery.deleted_at.take();
+            let bakery = bakery.clone();
+            for chef in state.chefs.values_mut() {
+                if chef.bakery_id == id && chef.deleted_at == deleted_at {
+                    chef.deleted_at = None;
+                }
+            }
+            Ok(bakery)
+        })
+    }
+
+    async fn purge_bakeries(&self, deleted_before: DateTimeUtc) -> Result<u64, DbErr> {
+        self.write(Operation::Purge, |state, _| {
+            let purged: Vec<i32> = state
+                .bakeries
+                .values()
+                .filter(|bakery| bakery.deleted_at.is_some_and(|at| at < deleted_before))
+                .map(|bakery| bakery.id)
+                .collect();
+            state.bakeries.retain(|id, _| !purged.contains(id));
+            state
+                .chefs
+                .retain(|_, chef| !purged.contains(&chef.bakery_id));
+            Ok(purged.len() as u64)
+        })
+    }
+
+    async fn bakery_history(&self, id: i32) -> Result<Vec<Change<bakery::Model>>, DbErr> {
+        let state = self.state();
+        Ok(state.bakery_history.get(&id).cloned().unwrap_or_default())
+    }
+}
+
+#[async_trait]
+impl ChefRepository for MemoryRepository {
+    async fn hire_chef(&self, chef: NewChef) -> Result<chef::Model, DbErr> {
+        Ok(self.hire_chefs(vec![chef]).await?.remove(0))
+    }
+
+    async fn hire_chefs(&self, chefs: Vec<NewChef>) -> Result<Vec<chef::Model>, DbErr> {
+        self.write(Operation::Insert, |state, _| {
+            chefs
+                .into_iter()
+                .map(|chef| state.insert_chef(chef))
+                .collect()
+        })
+    }
+
+    async fn chef(&self, id: i32) -> Result<chef::Model, DbErr> {
+        self.state().chef_mut(id).map(|chef| chef.clone())
+    }
+
+    async fn list_chefs(&self) -> Result<Vec<chef::Model>, DbErr> {
+        Ok(self.state().active_chefs().cloned().collect())
+    }
+
+    async fn list_chefs_of(&self, bakery_id: i32) -> Result<Vec<chef::Model>, DbErr> {
+        let state = self.state();
+        state.bakery(bakery_id)?;
+        Ok(state
+            .active_chefs()
+            .filter(|chef| chef.bakery_id == bakery_id)
+            .cloned()
+            .collect())
+    }
+
+    async fn query_chefs(&self, query: &ChefQuery) -> Result<Listing<chef::Model>, DbErr> {
+        let chefs = self.state().chefs.values().cloned().collect();
+        listing::page(chefs, query)
+    }
+
+    async fn find_chefs_by_email(&self, email: &str) -> Result<Vec<chef::Model>, DbErr> {
+        let email = email.trim().to_lowercase();
+        Ok(self.find_chefs(|details| details.emails.contains(&email)))
+    }
+
+    async fn find_chefs_by_phone(&self, phone: &str) -> Result<Vec<chef::Model>, DbErr> {
+        let phone = phone.trim();
+        Ok(self.find_chefs(|details| details.phones.iter().any(|p| p == phone)))
+    }
+
+    async fn update_chef(&self, id: i32, changes: ChefChanges) -> Result<chef::Model, DbErr> {
+        self.write(Operation::Update, |state, _| {
+            let chef = state.chef_mut(id)?.clone();
+            if changes.is_empty() {
+                return Ok(chef);
+            }
+
+            let chef = NewChef {
+                name: changes.name.unwrap_or(chef.name),
+                bakery_id: chef.bakery_id,
+                contact_details: changes.contact_details.unwrap_or(chef.contact_details),
+            };
+            let chef = state.validate_chef(id, chef)?;
+            state.chefs.insert(id, chef.clone());
+            Ok(chef)
+        })
+    }
+
+    async fn move_chef(&self, id: i32, bakery_id: i32) -> Result<chef::Model, DbErr> {
+        self.write(Operation::Update, |state, _| {
+            state.chef_mut(id)?;
+            state.bakery(bakery_id)?;
+
+            let chef = state.chef_mut(id)?;
+            chef.bakery_id = bakery_id;
+            Ok(chef.clone())
+        })
+    }
+
+    async fn move_chefs(&self, from_bakery_id: i32, to_bakery_id: i32) -> Result<u64, DbErr> {
+        self.write(Operation::Update, |state, _| {
+            state.bakery(from_bakery_id)?;
+            state.bakery(to_bakery_id)?;
+
+            let mut moved = 0;
+            for chef in state
+                .chefs
+                .values_mut()
+                .filter(|chef| chef.bakery_id == from_bakery_id && chef.deleted_at.is_none())
+            {
+                chef.bakery_id = to_bakery_id;
+                moved += 1;
+            }
+            Ok(moved)
+        })
+    }
+
+    async fn fire_chef(&self, id: i32) -> Result<chef::Model, DbErr> {
+        self.write(Operation::Delete, |state, now| {
+            state.fire_chef(id, now).ok_or_else(|| chef_not_found(id))
+        })
+    }
+
+    async fn fire_chefs(&self, ids: &[i32]) -> Result<u64, DbErr> {
+        self.write(Operation::Delete, |state, now| {
+            Ok(ids
+                .iter()
+                .filter_map(|&id| state.fire_chef(id, now))
+                .count() as u64)
+        })
+    }
+
+    async fn restore_chef(&self, id: i32) -> Result<chef::Model, DbErr> {
+        self.write(Operation::Restore, |state, _| {
+            let bakery_id = state
+                .chefs
+                .get(&id)
+                .filter(|chef| chef.deleted_at.is_some())
+                .ok_or_else(|| deleted_chef_not_found(id))?
+                .bakery_id;
+            state.bakery(bakery_id)?;
+            let chef = state.chefs.get_mut(&id).expect("the chef was just found");
+            chef.deleted_at = None;
+            Ok(chef.clone())
+        })
+    }
+
+    async fn purge_chefs(&self, deleted_before: DateTimeUtc) -> Result<u64, DbErr> {
+        self.write(Operation::Purge, |state, _| {
+            let before = state.chefs.len();
+            state
+                .chefs
+                .retain(|_, chef| chef.deleted_at.is_none_or(|at| at >= deleted_before));
+            Ok((before - state.chefs.len()) as u64)
+        })
+    }
+
+    async fn chef_history(&self, id: i32) -> Result<Vec<Change<chef::Model>>, DbErr> {
+        let state = self.state();
+        Ok(state.chef_history.get(&id).cloned().unwrap_or_default())
+    }
+}
+
+#[async_trait]
+impl Transactional for MemoryRepository {
+    type Transaction = MemoryRepository;
+
+    async fn begin(&self) -> Result<Self::Transaction, DbErr> {
+        Ok(MemoryRepository {
+            state: Arc::new(Mutex::new(self.state().clone())),
+            actor: self.actor.clone(),
+            outer: Some(self.state.clone()),
+        })
+    }
+}
+
+/// Committing a repository that is not a transaction changes nothing, its changes being
+/// made already.
+#[async_trait]
+impl Transaction for MemoryRepository {
+    async fn commit(self) -> Result<(), DbErr> {
+        if let Some(outer) = &self.outer {
+            *outer.lock().unwrap() = self.state().clone();
+        }
+        Ok(())
+    }
+}
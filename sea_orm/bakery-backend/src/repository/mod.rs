@@ -0,0 +1,797 @@
+//! Data access for bakeries and chefs, shared by the CLI and anything else that manages them.
+//!
+//! [`SeaOrmRepository`] works on a database; [`MemoryRepository`] keeps everything in
+//! memory with the same rules, for tests that need no database. Missing rows are reported
+//! as [`DbErr::RecordNotFound`] by both, and so are deleted ones outside of the
+//! `restore_*` methods and [`listing`](crate::listing) scopes.
+//!
+//! Both keep the [`history`] of every row they change, recorded as made by the actor
+//! they are given with `acting_as`.
+
+mod memory;
+mod orm;
+
+use async_trait::async_trait;
+use sea_orm::prelude::DateTimeUtc;
+use sea_orm::DbErr;
+
+use crate::contact::ContactDetails;
+use crate::entities::{bakery, chef};
+use crate::history::{self, Change};
+use crate::listing::{BakeryQuery, ChefQuery, Listing};
+
+pub use memory::MemoryRepository;
+pub use orm::SeaOrmRepository;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct NewBakery {
+    pub name: String,
+    pub profit_margin: f64,
+}
+
+/// Fields left `None` keep their value.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BakeryChanges {
+    pub name: Option<String>,
+    pub profit_margin: Option<f64>,
+}
+
+impl BakeryChanges {
+    pub fn is_empty(&self) -> bool {
+        self.name.is_none() && self.profit_margin.is_none()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NewChef {
+    pub name: String,
+    pub bakery_id: i32,
+    pub contact_details: Option<ContactDetails>,
+}
+
+/// Fields left `None` keep their value; `contact_details: Some(None)` removes them.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ChefChanges {
+    pub name: Option<String>,
+    pub contact_details: Option<Option<ContactDetails>>,
+}
+
+impl ChefChanges {
+    pub fn is_empty(&self) -> bool {
+        self.name.is_none() && self.contact_details.is_none()
+    }
+}
+
+#[async_trait]
+pub trait BakeryRepository: Send + Sync {
+    async fn create_bakery(&self, bakery: NewBakery) -> Result<bakery::Model, DbErr>;
+
+    /// Creates all the bakeries or, on error, none of them.
+    async fn create_bakeries(&self, bakeries: Vec<NewBakery>) -> Result<Vec<bakery::Model>, DbErr>;
+
+    async fn bakery(&self, id: i32) -> Result<bakery::Model, DbErr>;
+
+    async fn find_bakery_by_name(&self, name: &str) -> Result<Option<bakery::Model>, DbErr>;
+
+    /// All bakeries, by id.
+    async fn list_bakeries(&self) -> Result<Vec<bakery::Model>, DbErr>;
+
+    /// A page of the bakeries `query` filters, in its order.
+    async fn query_bakeries(&self, query: &BakeryQuery) -> Result<Listing<bakery::Model>, DbErr>;
+
+    async fn update_bakery(&self, id: i32, changes: BakeryChanges) -> Result<bakery::Model, DbErr>;
+
+    /// Soft-deletes the bakery and its chefs, all at the same time.
+    async fn delete_bakery(&self, id: i32) -> Result<bakery::Model, DbErr>;
+
+    /// Soft-deletes the bakeries that exist among `ids`, with their chefs, and counts them.
+    async fn delete_bakeries(&self, ids: &[i32]) -> Result<u64, DbErr>;
+
+    /// Brings back a deleted bakery with the chefs deleted along with it, but not those
+    /// fired before.
+    async fn restore_bakery(&self, id: i32) -> Result<bakery::Model, DbErr>;
+
+    /// Deletes for good the bakeries deleted before `deleted_before`, with their chefs,
+    /// and counts the bakeries.
+    async fn purge_bakeries(&self, deleted_before: DateTimeUtc) -> Result<u64, DbErr>;
+
+    /// Every version of the bakery, oldest first, even once it is purged. None for a
+    /// bakery unchanged since before the history tables, or that never existed.
+    async fn bakery_history(&self, id: i32) -> Result<Vec<Change<bakery::Model>>, DbErr>;
+
+    /// The bakery as it was at `at`, deleted or not, or `None` if it did not exist then.
+    async fn bakery_at(&self, id: i32, at: DateTimeUtc) -> Result<Option<bakery::Model>, DbErr> {
+        let changes = self.bakery_history(id).await?;
+        match changes.is_empty() {
+            // Unchanged as far as anyone knows.
+            true => self.bakery(id).await.map(Some),
+            false => Ok(history::state_at(&changes, at)),
+        }
+    }
+}
+
+#[async_trait]
+pub trait ChefRepository: Send + Sync {
+    /// Fails with `RecordNotFound` unless the chef's bakery exists.
+    async fn hire_chef(&self, chef: NewChef) -> Result<chef::Model, DbErr>;
+
+    /// Hires all the chefs or, on error, none of them.
+    async fn hire_chefs(&self, chefs: Vec<NewChef>) -> Result<Vec<chef::Model>, DbErr>;
+
+    async fn chef(&self, id: i32) -> Result<chef::Model, DbErr>;
+
+    /// All chefs, by id.
+    async fn list_chefs(&self) -> Result<Vec<chef::Model>, DbErr>;
+
+    /// The chefs of a bakery, by id.
+    async fn list_chefs_of(&self, bakery_id: i32) -> Result<Vec<chef::Model>, DbErr>;
+
+    /// A page of the chefs `query` filters, in its order.
+    async fn query_chefs(&self, query: &ChefQuery) -> Result<Listing<chef::Model>, DbErr>;
+
+    /// The chefs with `email` among their contacts, in any case, by id.
+    async fn find_chefs_by_email(&self, email: &str) -> Result<Vec<chef::Model>, DbErr>;
+
+    /// The chefs with exactly `phone` among their contacts, by id.
+    async fn find_chefs_by_phone(&self, phone: &str) -> Result<Vec<chef::Model>, DbErr>;
+
+    async fn update_chef(&self, id: i32, changes: ChefChanges) -> Result<chef::Model, DbErr>;
+
+    /// Moves a chef to another bakery.
+    async fn move_chef(&self, id: i32, bakery_id: i32) -> Result<chef::Model, DbErr>;
+
+    /// Moves every chef of one bakery to another and counts them.
+    async fn move_chefs(&self, from_bakery_id: i32, to_bakery_id: i32) -> Result<u64, DbErr>;
+
+    /// Soft-deletes the chef.
+    async fn fire_chef(&self, id: i32) -> Result<chef::Model, DbErr>;
+
+    /// Fires the chefs that exist among `ids` and counts them.
+    async fn fire_chefs(&self, ids: &[i32]) -> Result<u64, DbErr>;
+
+    /// Brings back a fired chef, unless their bakery is deleted.
+    async fn restore_chef(&self, id: i32) -> Result<chef::Model, DbErr>;
+
+    /// Deletes for good the chefs fired before `deleted_before` and counts them.
+    async fn purge_chefs(&self, deleted_before: DateTimeUtc) -> Result<u64, DbErr>;
+
+    /// Every version of the chef, oldest first, even once they are purged. None for a
+    /// chef unchanged since before the history tables, or who never existed.
+    async fn chef_history(&self, id: i32) -> Result<Vec<Change<chef::Model>>, DbErr>;
+
+    /// The chef as they were at `at`, fired or not, or `None` if they did not exist then.
+    async fn chef_at(&self, id: i32, at: DateTimeUtc) -> Result<Option<chef::Model>, DbErr> {
+        let changes = self.chef_history(id).await?;
+        match changes.is_empty() {
+            true => self.chef(id).await.map(Some),
+            false => Ok(history::state_at(&changes, at)),
+        }
+    }
+}
+
+/// Repositories that can group changes so that they are all made or none are.
+#[async_trait]
+pub trait Transactional: BakeryRepository + ChefRepository {
+    type Transaction: Transaction;
+
+    /// The repositories within a new transaction, acting as the same actor. Nothing
+    /// outside sees its changes before it is committed, and dropping it undoes them.
+    async fn begin(&self) -> Result<Self::Transaction, DbErr>;
+}
+
+#[async_trait]
+pub trait Transaction: BakeryRepository + ChefRepository {
+    async fn commit(self) -> Result<(), DbErr>;
+}
+
+fn bakery_not_found(id: i32) -> DbErr {
+    DbErr::RecordNotFound(format!("bakery {}", id))
+}
+
+fn chef_not_found(id: i32) -> DbErr {
+    DbErr::RecordNotFound(format!("chef {}", id))
+}
+
+fn deleted_bakery_not_found(id: i32) -> DbErr {
+    DbErr::RecordNotFound(format!("deleted bakery {}", id))
+}
+
+fn deleted_chef_not_found(id: i32) -> DbErr {
+    DbErr::RecordNotFound(format!("deleted chef {}", id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::DatabaseConfig;
+    use crate::contact::Channel;
+    use crate::history::Operation::{self, *};
+    use crate::listing::{BakeryFilter, ChefFilter, Page};
+    use crate::soft_delete::{self, Scope};
+    use crate::validation::ValidationError;
+    use futures::executor::block_on;
+    use migration::{Migrator, MigratorTrait};
+
+    fn new_bakery(name: &str) -> NewBakery {
+        NewBakery {
+            name: name.to_owned(),
+            profit_margin: 0.0,
+        }
+    }
+
+    fn new_chef(name: &str, bakery_id: i32) -> NewChef {
+        NewChef {
+            name: name.to_owned(),
+            bakery_id,
+            contact_details: None,
+        }
+    }
+
+    fn operations<M>(changes: &[Change<M>]) -> Vec<Operation> {
+        changes.iter().map(|change| change.operation).collect()
+    }
+
+    fn names<T>(models: &[T], name: impl Fn(&T) -> &str) -> Vec<&str> {
+        models.iter().map(name).collect()
+    }
+
+    /// The same behavior is expected from every implementation.
+    async fn exercise<R: Transactional>(repo: &R) {
+        let happy = repo
+            .create_bakery(new_bakery("Happy Bakery"))
+            .await
+            .unwrap();
+        let changes = BakeryChanges {
+            name: Some("Sad Bakery".to_owned()),
+            ..BakeryChanges::default()
+        };
+        let sad = repo.update_bakery(happy.id, changes).await.unwrap();
+        assert_eq!((sad.id, sad.name.as_str()), (happy.id, "Sad Bakery"));
+        assert_eq!(
+            repo.find_bakery_by_name("Sad Bakery").await.unwrap(),
+            Some(sad.clone())
+        );
+        assert_eq!(
+            repo.update_bakery(sad.id, BakeryChanges::default())
+                .await
+                .unwrap(),
+            sad
+        );
+
+        let john = repo.hire_chef(new_chef("John", sad.id)).await.unwrap();
+        assert!(matches!(
+            repo.hire_chef(new_chef("Nobody", 999)).await,
+            Err(DbErr::RecordNotFound(_))
+        ));
+        let deleted = repo.delete_bakery(sad.id).await.unwrap();
+        assert_eq!(deleted.id, sad.id);
+        assert!(deleted.deleted_at.is_some());
+        assert!(matches!(
+            repo.chef(john.id).await,
+            Err(DbErr::RecordNotFound(_))
+        ));
+        assert!(matches!(
+            repo.bakery(sad.id).await,
+            Err(DbErr::RecordNotFound(_))
+        ));
+        assert!(repo.list_bakeries().await.unwrap().is_empty());
+
+        let bakeries = repo
+            .create_bakeries(vec![new_bakery("La Boulangerie"), new_bakery("Le Fournil")])
+            .await
+            .unwrap();
+        let (boulangerie, fournil) = (bakeries[0].id, bakeries[1].id);
+        let chefs = ["Jolie", "Charles", "Madeleine", "Frederic"]
+            .into_iter()
+            .map(|name| new_chef(name, boulangerie))
+            .collect();
+        let chefs = repo.hire_chefs(chefs).await.unwrap();
+        let listed = repo.list_chefs_of(boulangerie).await.unwrap();
+        assert_eq!(
+            names(&listed, |chef| &chef.name),
+            ["Jolie", "Charles", "Madeleine", "Frederic"]
+        );
+
+        // A failed bulk hire leaves no one behind.
+        let hires = vec![new_chef("Marie", fournil), new_chef("Pierre", 999)];
+        assert!(repo.hire_chefs(hires).await.is_err());
+        assert!(repo.list_chefs_of(fournil).await.unwrap().is_empty());
+
+        let moved = repo.move_chef(chefs[0].id, fournil).await.unwrap();
+        assert_eq!(moved.bakery_id, fournil);
+        assert!(matches!(
+            repo.move_chef(chefs[0].id, 999).await,
+            Err(DbErr::RecordNotFound(_))
+        ));
+        assert_eq!(repo.move_chefs(boulangerie, fournil).await.unwrap(), 3);
+        assert!(repo.list_chefs_of(boulangerie).await.unwrap().is_empty());
+        assert_eq!(repo.list_chefs_of(fournil).await.unwrap().len(), 4);
+
+        assert_eq!(repo.fire_chef(chefs[1].id).await.unwrap().name, "Charles");
+        assert_eq!(
+            repo.fire_chefs(&[chefs[1].id, chefs[2].id, chefs[3].id])
+                .await
+                .unwrap(),
+            2
+        );
+        assert_eq!(
+            names(&repo.list_chefs().await.unwrap(), |chef| &chef.name),
+            ["Jolie"]
+        );
+
+        assert_eq!(
+            repo.delete_bakeries(&[boulangerie, fournil, 999])
+                .await
+                .unwrap(),
+            2
+        );
+        assert!(repo.list_chefs().await.unwrap().is_empty());
+
+        let deleted = BakeryQuery {
+            filter: BakeryFilter {
+                scope: Scope::OnlyDeleted,
+                ..BakeryFilter::default()
+            },
+            ..BakeryQuery::default()
+        };
+        assert_eq!(
+            names(
+                &repo.query_bakeries(&deleted).await.unwrap().items,
+                |bakery| &bakery.name
+            ),
+            ["Sad Bakery", "La Boulangerie", "Le Fournil"]
+        );
+        // Only Jolie was still there when Le Fournil was deleted.
+        repo.restore_bakery(fournil).await.unwrap();
+        assert_eq!(
+            names(&repo.list_chefs_of(fournil).await.unwrap(), |chef| &chef
+                .name),
+            ["Jolie"]
+        );
+        assert!(matches!(
+            repo.restore_bakery(fournil).await,
+            Err(DbErr::RecordNotFound(_))
+        ));
+        assert!(matches!(
+            repo.restore_chef(john.id).await,
+            Err(DbErr::RecordNotFound(_))
+        ));
+        assert_eq!(
+            repo.restore_chef(chefs[1].id).await.unwrap().name,
+            "Charles"
+        );
+        assert_eq!(repo.list_chefs().await.unwrap().len(), 2);
+
+        let all_chefs = ChefQuery {
+            filter: ChefFilter {
+                scope: Scope::WithDeleted,
+                ..ChefFilter::default()
+            },
+            ..ChefQuery::default()
+        };
+        assert_eq!(repo.query_chefs(&all_chefs).await.unwrap().total, 5);
+        assert_eq!(repo.purge_chefs(soft_delete::cutoff(1)).await.unwrap(), 0);
+        assert_eq!(
+            repo.purge_bakeries(soft_delete::cutoff(1)).await.unwrap(),
+            0
+        );
+        let later = soft_delete::now() + chrono::Duration::days(1);
+        assert_eq!(repo.purge_chefs(later).await.unwrap(), 3);
+        assert_eq!(repo.purge_bakeries(later).await.unwrap(), 2);
+        assert_eq!(repo.query_chefs(&all_chefs).await.unwrap().total, 2);
+        assert!(repo
+            .query_bakeries(&deleted)
+            .await
+            .unwrap()
+            .items
+            .is_empty());
+
+        // Deleted names stay taken until purged.
+        repo.delete_bakery(fournil).await.unwrap();
+        assert!(repo.create_bakery(new_bakery("Le Fournil")).await.is_err());
+        repo.purge_bakeries(later).await.unwrap();
+
+        let versions = repo.bakery_history(sad.id).await.unwrap();
+        assert_eq!(operations(&versions), [Insert, Update, Delete, Purge]);
+        assert!(versions.iter().all(|change| change.actor == "tester"));
+        let renamed = &versions[1];
+        assert_eq!(renamed.old.as_ref().unwrap().name, "Happy Bakery");
+        let at = |change: &Change<_>| change.changed_at;
+        assert_eq!(
+            repo.bakery_at(sad.id, at(renamed)).await.unwrap(),
+            renamed.new
+        );
+        let before = at(&versions[0]) - chrono::Duration::microseconds(1);
+        assert_eq!(repo.bakery_at(sad.id, before).await.unwrap(), None);
+        assert_eq!(
+            repo.bakery_at(sad.id, at(&versions[3])).await.unwrap(),
+            None
+        );
+        // Jolie went with Le Fournil each time it was deleted.
+        let versions = repo.chef_history(chefs[0].id).await.unwrap();
+        assert_eq!(
+            operations(&versions),
+            [Insert, Update, Delete, Restore, Delete, Purge]
+        );
+        assert_eq!(
+            versions[2].changed_at,
+            versions[2].new.as_ref().unwrap().deleted_at.unwrap()
+        );
+        assert!(matches!(
+            repo.chef_at(999, later).await,
+            Err(DbErr::RecordNotFound(_))
+        ));
+
+        validate(repo).await;
+        list(repo).await;
+        transactions(repo).await;
+    }
+
+    async fn transactions<R: Transactional>(repo: &R) {
+        let txn = repo.begin().await.unwrap();
+        txn.create_bakery(new_bakery("Dropped Bakery"))
+            .await
+            .unwrap();
+        drop(txn);
+        assert_eq!(
+            repo.find_bakery_by_name("Dropped Bakery").await.unwrap(),
+            None
+        );
+
+        let txn = repo.begin().await.unwrap();
+        let kept = txn.create_bakery(new_bakery("Kept Bakery")).await.unwrap();
+        txn.commit().await.unwrap();
+        assert_eq!(repo.bakery(kept.id).await.unwrap(), kept);
+        let versions = repo.bakery_history(kept.id).await.unwrap();
+        assert_eq!(operations(&versions), [Insert]);
+        assert_eq!(versions[0].actor, "tester");
+    }
+
+    fn invalid<T: std::fmt::Debug>(result: Result<T, DbErr>) -> ValidationError {
+        ValidationError::from_db_err(&result.unwrap_err())
+            .unwrap()
+            .clone()
+    }
+
+    async fn validate<R: BakeryRepository + ChefRepository>(repo: &R) {
+        let bakery = repo
+            .create_bakery(new_bakery("  Trimmed Bakery "))
+            .await
+            .unwrap();
+        assert_eq!(bakery.name, "Trimmed Bakery");
+        assert_eq!(
+            invalid(repo.create_bakery(new_bakery("Trimmed Bakery")).await),
+            ValidationError::DuplicateBakeryName {
+                name: "Trimmed Bakery".to_owned()
+            }
+        );
+        assert_eq!(
+            invalid(repo.create_bakery(new_bakery(" ")).await),
+            ValidationError::BlankName { entity: "bakery" }
+        );
+        let changes = BakeryChanges {
+            profit_margin: Some(-100.0),
+            ..BakeryChanges::default()
+        };
+        assert!(matches!(
+            invalid(repo.update_bakery(bakery.id, changes).await),
+            ValidationError::ProfitMarginOutOfRange { value, .. } if value == -100.0
+        ));
+        // Renaming a bakery to its own name is no duplicate.
+        let changes = BakeryChanges {
+            name: Some("Trimmed Bakery".to_owned()),
+            ..BakeryChanges::default()
+        };
+        assert_eq!(
+            repo.update_bakery(bakery.id, changes).await.unwrap(),
+            bakery
+        );
+
+        let mut chef = new_chef("Lucie", bakery.id);
+        chef.contact_details = Some(ContactDetails {
+            emails: vec!["lucie".to_owned()],
+            ..ContactDetails::default()
+        });
+        assert!(matches!(
+            invalid(repo.hire_chef(chef).await),
+            ValidationError::ContactDetails { .. }
+        ));
+        assert_eq!(
+            invalid(repo.hire_chef(new_chef("", bakery.id)).await),
+            ValidationError::BlankName { entity: "chef" }
+        );
+        assert!(repo.list_chefs_of(bakery.id).await.unwrap().is_empty());
+
+        let mut chef = new_chef("Lucie", bakery.id);
+        chef.contact_details = Some(ContactDetails {
+            emails: vec![
+                "lucie@example.com".to_owned(),
+                " Lucie@Bakery.FR ".to_owned(),
+            ],
+            phones: vec!["+33 1 23 45 67 89".to_owned()],
+            address: None,
+            preferred: Some(Channel::Email),
+        });
+        let lucie = repo.hire_chef(chef).await.unwrap();
+        let details = lucie.contact_details.as_ref().unwrap();
+        assert_eq!(details.emails[1], "lucie@bakery.fr");
+        assert_eq!(repo.chef(lucie.id).await.unwrap(), lucie);
+        repo.hire_chef(new_chef("Anonymous", bakery.id))
+            .await
+            .unwrap();
+        assert_eq!(
+            repo.find_chefs_by_email("LUCIE@bakery.fr").await.unwrap(),
+            std::slice::from_ref(&lucie)
+        );
+        assert_eq!(
+            repo.find_chefs_by_phone("+33 1 23 45 67 89").await.unwrap(),
+            std::slice::from_ref(&lucie)
+        );
+        assert!(repo.find_chefs_by_email("lucie@").await.unwrap().is_empty());
+        let changes = ChefChanges {
+            name: Some(" Lucie B. ".to_owned()),
+            contact_details: Some(None),
+        };
+        let updated = repo.update_chef(lucie.id, changes).await.unwrap();
+        assert_eq!(
+            (updated.name.as_str(), &updated.contact_details),
+            ("Lucie B.", &None)
+        );
+        assert!(repo
+            .find_chefs_by_phone("+33 1 23 45 67 89")
+            .await
+            .unwrap()
+            .is_empty());
+        repo.delete_bakery(bakery.id).await.unwrap();
+    }
+
+    async fn list<R: BakeryRepository + ChefRepository>(repo: &R) {
+        let margins = [
+            ("Happy Bakery", 12.5),
+            ("Sad Bakery", 3.0),
+            ("Le 100% Pain", 12.5),
+            ("La Boulangerie", 17.89),
+            ("Le Fournil", 40.0),
+        ];
+        let bakeries = margins
+            .iter()
+            .map(|&(name, profit_margin)| NewBakery {
+                name: name.to_owned(),
+                profit_margin,
+            })
+            .collect();
+        let bakeries = repo.create_bakeries(bakeries).await.unwrap();
+
+        let query = BakeryQuery {
+            filter: BakeryFilter {
+                name_contains: Some("BAKERY".to_owned()),
+                ..BakeryFilter::default()
+            },
+            ..BakeryQuery::default()
+        };
+        let listing = repo.query_bakeries(&query).await.unwrap();
+        assert_eq!(
+            names(&listing.items, |bakery| &bakery.name),
+            ["Happy Bakery", "Sad Bakery"]
+        );
+        assert_eq!((listing.total, listing.next), (2, None));
+
+        let query = BakeryQuery {
+            filter: BakeryFilter {
+                name_contains: Some("0%".to_owned()),
+                ..BakeryFilter::default()
+            },
+            ..BakeryQuery::default()
+        };
+        let listing = repo.query_bakeries(&query).await.unwrap();
+        assert_eq!(
+            names(&listing.items, |bakery| &bakery.name),
+            ["Le 100% Pain"]
+        );
+
+        let by_margin = |page| BakeryQuery {
+            filter: BakeryFilter {
+                min_profit_margin: Some(10.0),
+                max_profit_margin: Some(20.0),
+                ..BakeryFilter::default()
+            },
+            sort: vec!["-profit_margin".parse().unwrap(), "name".parse().unwrap()],
+            page,
+        };
+        let first = repo
+            .query_bakeries(&by_margin(Page::Offset { number: 1, size: 2 }))
+            .await
+            .unwrap();
+        assert_eq!(
+            names(&first.items, |bakery| &bakery.name),
+            ["La Boulangerie", "Happy Bakery"]
+        );
+        assert_eq!(first.total, 3);
+        assert_eq!(first.next, Some(Page::Offset { number: 2, size: 2 }));
+        let second = repo
+            .query_bakeries(&by_margin(first.next.unwrap()))
+            .await
+            .unwrap();
+        assert_eq!(
+            names(&second.items, |bakery| &bakery.name),
+            ["Le 100% Pain"]
+        );
+        assert_eq!((second.total, second.next), (3, None));
+        let past_the_end = Page::Offset {
+            number: u64::MAX,
+            size: 2,
+        };
+        assert!(repo.query_bakeries(&by_margin(past_the_end)).await.is_err());
+        // SQL counts rows with signed numbers.
+        for too_far in [
+            Page::Offset {
+                number: 1 << 62 | 1,
+                size: 2,
+            },
+            Page::Offset {
+                number: 1,
+                size: u64::MAX,
+            },
+            Page::After {
+                cursor: None,
+                size: u64::MAX,
+            },
+            Page::After {
+                cursor: None,
+                size: i64::MAX as u64,
+            },
+        ] {
+            assert!(repo.query_bakeries(&by_margin(too_far)).await.is_err());
+        }
+
+        // Following cursors visits every bakery once, in the same order.
+        let mut page = Page::After {
+            cursor: None,
+            size: 2,
+        };
+        let mut visited = vec![];
+        loop {
+            let listing = repo.query_bakeries(&by_margin(page)).await.unwrap();
+            assert_eq!(listing.total, 3);
+            visited.extend(listing.items);
+            match listing.next {
+                Some(next) => page = next,
+                None => break,
+            }
+        }
+        assert_eq!(
+            names(&visited, |bakery| &bakery.name),
+            ["La Boulangerie", "Happy Bakery", "Le 100% Pain"]
+        );
+
+        let cursor = Page::After {
+            cursor: Some("[1]".parse().unwrap()),
+            size: 2,
+        };
+        assert!(repo.query_bakeries(&by_margin(cursor)).await.is_err());
+
+        let (happy, sad) = (bakeries[0].id, bakeries[1].id);
+        let chefs = ["Jolie", "Charles", "Joseph", "Élise"]
+            .into_iter()
+            .map(|name| new_chef(name, happy))
+            .chain([new_chef("Jonas", sad)])
+            .collect();
+        repo.hire_chefs(chefs).await.unwrap();
+        let query = ChefQuery {
+            filter: ChefFilter {
+                name_contains: Some("jo".to_owned()),
+                bakery_id: Some(happy),
+                ..ChefFilter::default()
+            },
+            sort: vec!["-name".parse().unwrap()],
+            ..ChefQuery::default()
+        };
+        let listing = repo.query_chefs(&query).await.unwrap();
+        assert_eq!(
+            names(&listing.items, |chef| &chef.name),
+            ["Joseph", "Jolie"]
+        );
+        // Only ASCII letters match in any case, as with SQLite.
+        for (part, found) in [("LISE", 1), ("É", 1), ("é", 0)] {
+            let query = ChefQuery {
+                filter: ChefFilter {
+                    name_contains: Some(part.to_owned()),
+                    ..ChefFilter::default()
+                },
+                ..ChefQuery::default()
+            };
+            let listing = repo.query_chefs(&query).await.unwrap();
+            assert_eq!(listing.total, found, "{}", part);
+        }
+
+        let ids: Vec<i32> = bakeries.iter().map(|bakery| bakery.id).collect();
+        repo.delete_bakeries(&ids).await.unwrap();
+    }
+
+    #[test]
+    fn test_memory() {
+        block_on(exercise(&MemoryRepository::default().acting_as("tester")));
+    }
+
+    #[test]
+    fn test_sea_orm() {
+        block_on(async {
+            let db = DatabaseConfig::default().connect().await.unwrap();
+            Migrator::up(&db, None).await.unwrap();
+            exercise(&SeaOrmRepository::new(&db).acting_as("tester")).await;
+        });
+    }
+
+    #[test]
+    fn test_delete_hooks() {
+        use crate::entities::{bakery, chef};
+        use sea_orm::{ActiveModelTrait, EntityTrait, ModelTrait};
+
+        block_on(async {
+            let db = DatabaseConfig::default().connect().await.unwrap();
+            Migrator::up(&db, None).await.unwrap();
+            let repo = SeaOrmRepository::new(&db);
+            let happy = repo
+                .create_bakery(new_bakery("Happy Bakery"))
+                .await
+                .unwrap();
+            let jolie = repo.hire_chef(new_chef("Jolie", happy.id)).await.unwrap();
+
+            // Past the repository, rows are only deleted for good once soft-deleted.
+            let err = jolie.clone().delete(&db).await.unwrap_err();
+            assert_eq!(
+                ValidationError::from_db_err(&err),
+                Some(&ValidationError::NotSoftDeleted {
+                    entity: "chef",
+                    id: jolie.id
+                })
+            );
+            let bakery = bakery::ActiveModel {
+                id: sea_orm::Set(happy.id),
+                ..Default::default()
+            };
+            let err = bakery.delete(&db).await.unwrap_err();
+            assert!(matches!(
+                ValidationError::from_db_err(&err),
+                Some(ValidationError::NotSoftDeleted {
+                    entity: "bakery",
+                    ..
+                })
+            ));
+
+            let deleted = repo.delete_bakery(happy.id).await.unwrap();
+            deleted.delete(&db).await.unwrap();
+            assert_eq!(
+                chef::Entity::find_by_id(jolie.id).one(&db).await.unwrap(),
+                None
+            );
+        });
+    }
+
+    #[test]
+    fn test_schema_checks() {
+        use sea_orm::sea_query::{Query, SqliteQueryBuilder};
+        use sea_orm::{ConnectionTrait, DbBackend, Statement};
+
+        block_on(async {
+            let db = DatabaseConfig::default().connect().await.unwrap();
+            Migrator::up(&db, None).await.unwrap();
+            // Past the hooks, straight to the database.
+            let insert = |name: &str, profit_margin: f64| {
+                let (sql, values) = Query::insert()
+                    .into_table(crate::entities::bakery::Entity)
+                    .columns([
+                        crate::entities::bakery::Column::Name,
+                        crate::entities::bakery::Column::ProfitMargin,
+                    ])
+                    .values_panic([name.into(), profit_margin.into()])
+                    .build(SqliteQueryBuilder);
+                Statement::from_sql_and_values(DbBackend::Sqlite, &sql, values)
+            };
+            assert!(db.execute(insert("Happy Bakery", -100.0)).await.is_err());
+            assert!(db.execute(insert(" ", 10.0)).await.is_err());
+            db.execute(insert("Happy Bakery", 10.0)).await.unwrap();
+            assert!(db.execute(insert("Happy Bakery", 20.0)).await.is_err());
+        });
+    }
+}
@@ -0,0 +1,523 @@
+use async_trait::async_trait;
+use sea_orm::prelude::DateTimeUtc;
+use sea_orm::sea_query::{Expr, SimpleExpr};
+use sea_orm::*;
+
+use std::ops::Deref;
+
+use super::{
+    bakery_not_found, chef_not_found, deleted_bakery_not_found, deleted_chef_not_found,
+    BakeryChanges, BakeryRepository, ChefChanges, ChefRepository, NewBakery, NewChef, Transaction,
+    Transactional,
+};
+use crate::contact;
+use crate::entities::{prelude::*, *};
+use crate::history::{self, Change, Operation, Rows};
+use crate::listing::{self, BakeryQuery, ChefQuery, Listing};
+use crate::soft_delete::{self, SoftDelete};
+
+/// The repositories over a database connection, with the rules of the entities'
+/// `ActiveModelBehavior`. Each change goes in one transaction with its history.
+///
+/// [`new`](SeaOrmRepository::new) borrows a connection; [`begin`](Transactional::begin)
+/// gives one over a transaction, whose changes are savepoints within it.
+pub struct SeaOrmRepository<D> {
+    db: D,
+    actor: String,
+}
+
+impl<'db> SeaOrmRepository<&'db DatabaseConnection> {
+    pub fn new(db: &'db DatabaseConnection) -> Self {
+        SeaOrmRepository {
+            db,
+            actor: history::DEFAULT_ACTOR.to_owned(),
+        }
+    }
+}
+
+impl<D> SeaOrmRepository<D> {
+    /// Records the changes as made by `actor`.
+    pub fn acting_as(self, actor: impl Into<String>) -> Self {
+        SeaOrmRepository {
+            actor: actor.into(),
+            ..self
+        }
+    }
+}
+
+async fn find_bakery<C: ConnectionTrait>(db: &C, id: i32) -> Result<bakery::Model, DbErr> {
+    Bakery::find_active()
+        .filter(bakery::Column::Id.eq(id))
+        .one(db)
+        .await?
+        .ok_or_else(|| bakery_not_found(id))
+}
+
+async fn find_chef<C: ConnectionTrait>(db: &C, id: i32) -> Result<chef::Model, DbErr> {
+    Chef::find_active()
+        .filter(chef::Column::Id.eq(id))
+        .one(db)
+        .await?
+        .ok_or_else(|| chef_not_found(id))
+}
+
+/// Each of `rows` before and after `change`, for [`history::record`].
+fn updates<M: Clone>(rows: Vec<M>, change: impl Fn(&mut M)) -> Vec<Rows<M>> {
+    rows.into_iter()
+        .map(|old| {
+            let mut new = old.clone();
+            change(&mut new);
+            (Some(old), Some(new))
+        })
+        .collect()
+}
+
+fn removals<M>(rows: Vec<M>) -> Vec<Rows<M>> {
+    rows.into_iter().map(|old| (Some(old), None)).collect()
+}
+
+/// Marks the bakeries among `ids` that are not deleted yet, and their chefs, as deleted
+/// at `deleted_at`.
+async fn delete_bakeries<C: TransactionTrait>(
+    db: &C,
+    actor: &str,
+    ids: &[i32],
+    deleted_at: DateTimeUtc,
+) -> Result<u64, DbErr> {
+    let txn = db.begin().await?;
+    let bakeries = Bakery::find_active()
+        .filter(bakery::Column::Id.is_in(ids.iter().copied()))
+        .all(&txn)
+        .await?;
+    let ids: Vec<i32> = bakeries.iter().map(|bakery| bakery.id).collect();
+    let result = Bakery::update_many()
+        .col_expr(bakery::Column::DeletedAt, Expr::value(deleted_at))
+        .filter(bakery::Column::Id.is_in(ids.iter().copied()))
+        .exec(&txn)
+        .await?;
+    let changes = updates(bakeries, |bakery| bakery.deleted_at = Some(deleted_at));
+    history::record::<BakeryHistory, _>(&txn, Operation::Delete, actor, deleted_at, changes)
+        .await?;
+    fire_chefs_where(&txn, actor, chef::Column::BakeryId.is_in(ids), deleted_at).await?;
+    txn.commit().await?;
+    Ok(result.rows_affected)
+}
+
+async fn fire_chefs<C: TransactionTrait>(
+    db: &C,
+    actor: &str,
+    ids: &[i32],
+    deleted_at: DateTimeUtc,
+) -> Result<u64, DbErr> {
+    let txn = db.begin().await?;
+    let condition = chef::Column::Id.is_in(ids.iter().copied());
+    let fired = fire_chefs_where(&txn, actor, condition, deleted_at).await?;
+    txn.commit().await?;
+    Ok(fired)
+}
+
+async fn fire_chefs_where<C: ConnectionTrait>(
+    db: &C,
+    actor: &str,
+    condition: SimpleExpr,
+    deleted_at: DateTimeUtc,
+) -> Result<u64, DbErr> {
+    let chefs = Chef::find_active().filter(condition).all(db).await?;
+    let result = Chef::update_many()
+        .col_expr(chef::Column::DeletedAt, Expr::value(deleted_at))
+        .filter(chef::Column::Id.is_in(chefs.iter().map(|chef| chef.id)))
+        .exec(db)
+        .await?;
+    let changes = updates(chefs, |chef| chef.deleted_at = Some(deleted_at));
+    history::record::<ChefHistory, _>(db, Operation::Delete, actor, deleted_at, changes).await?;
+    Ok(result.rows_affected)
+}
+
+async fn insert_chef<C: ConnectionTrait>(
+    db: &C,
+    actor: &str,
+    chef: NewChef,
+) -> Result<chef::Model, DbErr> {
+    find_bakery(db, chef.bakery_id).await?;
+    let chef = chef::ActiveModel {
+        name: Set(chef.name),
+        bakery_id: Set(chef.bakery_id),
+        contact_details: Set(chef.contact_details),
+        ..Default::default()
+    }
+    .insert(db)
+    .await?;
+    let changes = vec![(None, Some(chef.clone()))];
+    let now = soft_delete::now();
+    history::record::<ChefHistory, _>(db, Operation::Insert, actor, now, changes).await?;
+    Ok(chef)
+}
+
+#[async_trait]
+impl<D, C> BakeryRepository for SeaOrmRepository<D>
+where
+    D: Deref<Target = C> + Send + Sync,
+    C: ConnectionTrait + TransactionTrait + Send + Sync,
+{
+    async fn create_bakery(&self, bakery: NewBakery) -> Result<bakery::Model, DbErr> {
+        Ok(self.create_bakeries(vec![bakery]).await?.remove(0))
+    }
+
+    async fn create_bakeries(&self, bakeries: Vec<NewBakery>) -> Result<Vec<bakery::Model>, DbErr> {
+        let txn = self.db.begin().await?;
+        let mut created = vec![];
+        for bakery in bakeries {
+            let bakery = bakery::ActiveModel {
+                name: Set(bakery.name),
+                profit_margin: Set(bakery.profit_margin),
+                ..Default::default()
+            };
+            created.push(bakery.insert(&txn).await?);
+        }
+        let changes = created
+            .iter()
+            .map(|bakery| (None, Some(bakery.clone())))
+            .collect();
+        let now = soft_delete::now();
+        history::record::<BakeryHistory, _>(&txn, Operation::Insert, &self.actor, now, changes)
+            .await?;
+        txn.commit().await?;
+        Ok(created)
+    }
+
+    async fn bakery(&self, id: i32) -> Result<bakery::Model, DbErr> {
+        find_bakery(&*self.db, id).await
+    }
+
+    async fn find_bakery_by_name(&self, name: &str) -> Result<Option<bakery::Model>, DbErr> {
+        Bakery::find_active()
+            .filter(bakery::Column::Name.eq(name))
+            .order_by_asc(bakery::Column::Id)
+            .one(&*self.db)
+            .await
+    }
+
+    async fn list_bakeries(&self) -> Result<Vec<bakery::Model>, DbErr> {
+        Bakery::find_active()
+            .order_by_asc(bakery::Column::Id)
+            .all(&*self.db)
+            .await
+    }
+
+    async fn query_bakeries(&self, query: &BakeryQuery) -> Result<Listing<bakery::Model>, DbErr> {
+        listing::fetch(&*self.db, query).await
+    }
+
+    async fn update_bakery(&self, id: i32, changes: BakeryChanges) -> Result<bakery::Model, DbErr> {
+        let old = find_bakery(&*self.db, id).await?;
+        if changes.is_empty() {
+            return Ok(old);
+        }
+
+        let mut bakery: bakery::ActiveModel = old.clone().into();
+        if let Some(name) = changes.name {
+            bakery.name = Set(name);
+        }
+        if let Some(profit_margin) = changes.profit_margin {
+            bakery.profit_margin = Set(profit_margin);
+        }
+        let txn = self.db.begin().await?;
+        let bakery = bakery.update(&txn).await?;
+        let changes = vec![(Some(old), Some(bakery.clone()))];
+        let now = soft_delete::now();
+        history::record::<BakeryHistory, _>(&txn, Operation::Update, &self.actor, now, changes)
+            .await?;
+        txn.commit().await?;
+        Ok(bakery)
+    }
+
+    async fn delete_bakery(&self, id: i32) -> Result<bakery::Model, DbErr> {
+        let mut bakery = find_bakery(&*self.db, id).await?;
+        let deleted_at = soft_delete::now();
+        delete_bakeries(&*self.db, &self.actor, &[id], deleted_at).await?;
+        bakery.deleted_at = Some(deleted_at);
+        Ok(bakery)
+    }
+
+    async fn delete_bakeries(&self, ids: &[i32]) -> Result<u64, DbErr> {
+        delete_bakeries(&*self.db, &self.actor, ids, soft_delete::now()).await
+    }
+
+    async fn restore_bakery(&self, id: i32) -> Result<bakery::Model, DbErr> {
+        let txn = self.db.begin().await?;
+        let bakery = Bakery::only_deleted()
+            .filter(bakery::Column::Id.eq(id))
+            .one(&txn)
+            .await?
+            .ok_or_else(|| deleted_bakery_not_found(id))?;
+        let chefs = Chef::find()
+            .filter(chef::Column::BakeryId.eq(id))
+            .filter(chef::Column::DeletedAt.eq(bakery.deleted_at))
+            .all(&txn)
+            .await?;
+        Bakery::update_many()
+            .col_expr(bakery::Column::DeletedAt, Expr::value(None::<DateTimeUtc>))
+            .filter(bakery::Column::Id.eq(id))
+            .exec(&txn)
+            .await?;
+        Chef::update_many()
+            .col_expr(chef::Column::DeletedAt, Expr::value(None::<DateTimeUtc>))
+            .filter(chef::Column::Id.is_in(chefs.iter().map(|chef| chef.id)))
+            .exec(&txn)
+            .await?;
+
+        let now = soft_delete::now();
+        let restored = bakery::Model {
+            deleted_at: None,
+            ..bakery.clone()
+        };
+        let changes = vec![(Some(bakery), Some(restored.clone()))];
+        history::record::<BakeryHistory, _>(&txn, Operation::Restore, &self.actor, now, changes)
+            .await?;
+        let changes = updates(chefs, |chef| chef.deleted_at = None);
+        history::record::<ChefHistory, _>(&txn, Operation::Restore, &self.actor, now, changes)
+            .await?;
+        txn.commit().await?;
+        Ok(restored)
+    }
+
+    async fn purge_bakeries(&self, deleted_before: DateTimeUtc) -> Result<u64, DbErr> {
+        let txn = self.db.begin().await?;
+        let bakeries = Bakery::only_deleted()
+            .filter(bakery::Column::DeletedAt.lt(deleted_before))
+            .all(&txn)
+            .await?;
+        let ids: Vec<i32> = bakeries.iter().map(|bakery| bakery.id).collect();
+        let chefs = Chef::find()
+            .filter(chef::Column::BakeryId.is_in(ids.iter().copied()))
+            .all(&txn)
+            .await?;
+        Chef::delete_many()
+            .filter(chef::Column::BakeryId.is_in(ids.iter().copied()))
+            .exec(&txn)
+            .await?;
+        let result = Bakery::delete_many()
+            .filter(bakery::Column::Id.is_in(ids))
+            .exec(&txn)
+            .await?;
+
+        let now = soft_delete::now();
+        let changes = removals(chefs);
+        history::record::<ChefHistory, _>(&txn, Operation::Purge, &self.actor, now, changes)
+            .await?;
+        let changes = removals(bakeries);
+        history::record::<BakeryHistory, _>(&txn, Operation::Purge, &self.actor, now, changes)
+            .await?;
+        txn.commit().await?;
+        Ok(result.rows_affected)
+    }
+
+    async fn bakery_history(&self, id: i32) -> Result<Vec<Change<bakery::Model>>, DbErr> {
+        history::changes::<BakeryHistory, _>(&*self.db, id).await
+    }
+}
+
+#[async_trait]
+impl<D, C> ChefRepository for SeaOrmRepository<D>
+where
+    D: Deref<Target = C> + Send + Sync,
+    C: ConnectionTrait + TransactionTrait + Send + Sync,
+{
+    async fn hire_chef(&self, chef: NewChef) -> Result<chef::Model, DbErr> {
+        Ok(self.hire_chefs(vec![chef]).await?.remove(0))
+    }
+
+    async fn hire_chefs(&self, chefs: Vec<NewChef>) -> Result<Vec<chef::Model>, DbErr> {
+        let txn = self.db.begin().await?;
+        let mut hired = vec![];
+        for chef in chefs {
+            hired.push(insert_chef(&txn, &self.actor, chef).await?);
+        }
+        txn.commit().await?;
+        Ok(hired)
+    }
+
+    async fn chef(&self, id: i32) -> Result<chef::Model, DbErr> {
+        find_chef(&*self.db, id).await
+    }
+
+    async fn list_chefs(&self) -> Result<Vec<chef::Model>, DbErr> {
+        Chef::find_active()
+            .order_by_asc(chef::Column::Id)
+            .all(&*self.db)
+            .await
+    }
+
+    async fn list_chefs_of(&self, bakery_id: i32) -> Result<Vec<chef::Model>, DbErr> {
+        find_bakery(&*self.db, bakery_id).await?;
+        Chef::find_active()
+            .filter(chef::Column::BakeryId.eq(bakery_id))
+            .order_by_asc(chef::Column::Id)
+            .all(&*self.db)
+            .await
+    }
+
+    async fn query_chefs(&self, query: &ChefQuery) -> Result<Listing<chef::Model>, DbErr> {
+        listing::fetch(&*self.db, query).await
+    }
+
+    async fn find_chefs_by_email(&self, email: &str) -> Result<Vec<chef::Model>, DbErr> {
+        Chef::find_active()
+            .filter(contact::has_email(self.db.get_database_backend(), email))
+            .order_by_asc(chef::Column::Id)
+            .all(&*self.db)
+            .await
+    }
+
+    async fn find_chefs_by_phone(&self, phone: &str) -> Result<Vec<chef::Model>, DbErr> {
+        Chef::find_active()
+            .filter(contact::has_phone(self.db.get_database_backend(), phone))
+            .order_by_asc(chef::Column::Id)
+            .all(&*self.db)
+            .await
+    }
+
+    async fn update_chef(&self, id: i32, changes: ChefChanges) -> Result<chef::Model, DbErr> {
+        let old = find_chef(&*self.db, id).await?;
+        if changes.is_empty() {
+            return Ok(old);
+        }
+
+        let mut chef: chef::ActiveModel = old.clone().into();
+        if let Some(name) = changes.name {
+            chef.name = Set(name);
+        }
+        if let Some(contact_details) = changes.contact_details {
+            chef.contact_details = Set(contact_details);
+        }
+        let txn = self.db.begin().await?;
+        let chef = chef.update(&txn).await?;
+        let changes = vec![(Some(old), Some(chef.clone()))];
+        let now = soft_delete::now();
+        history::record::<ChefHistory, _>(&txn, Operation::Update, &self.actor, now, changes)
+            .await?;
+        txn.commit().await?;
+        Ok(chef)
+    }
+
+    async fn move_chef(&self, id: i32, bakery_id: i32) -> Result<chef::Model, DbErr> {
+        let old = find_chef(&*self.db, id).await?;
+        find_bakery(&*self.db, bakery_id).await?;
+
+        let mut chef: chef::ActiveModel = old.clone().into();
+        chef.bakery_id = Set(bakery_id);
+        let txn = self.db.begin().await?;
+        let chef = chef.update(&txn).await?;
+        let changes = vec![(Some(old), Some(chef.clone()))];
+        let now = soft_delete::now();
+        history::record::<ChefHistory, _>(&txn, Operation::Update, &self.actor, now, changes)
+            .await?;
+        txn.commit().await?;
+        Ok(chef)
+    }
+
+    async fn move_chefs(&self, from_bakery_id: i32, to_bakery_id: i32) -> Result<u64, DbErr> {
+        find_bakery(&*self.db, from_bakery_id).await?;
+        find_bakery(&*self.db, to_bakery_id).await?;
+
+        let txn = self.db.begin().await?;
+        let chefs = Chef::find_active()
+            .filter(chef::Column::BakeryId.eq(from_bakery_id))
+            .all(&txn)
+            .await?;
+        let result = Chef::update_many()
+            .col_expr(chef::Column::BakeryId, Expr::value(to_bakery_id))
+            .filter(chef::Column::Id.is_in(chefs.iter().map(|chef| chef.id)))
+            .exec(&txn)
+            .await?;
+        let changes = updates(chefs, |chef| chef.bakery_id = to_bakery_id);
+        let now = soft_delete::now();
+        history::record::<ChefHistory, _>(&txn, Operation::Update, &self.actor, now, changes)
+            .await?;
+        txn.commit().await?;
+        Ok(result.rows_affected)
+    }
+
+    async fn fire_chef(&self, id: i32) -> Result<chef::Model, DbErr> {
+        let mut chef = find_chef(&*self.db, id).await?;
+        let deleted_at = soft_delete::now();
+        fire_chefs(&*self.db, &self.actor, &[id], deleted_at).await?;
+        chef.deleted_at = Some(deleted_at);
+        Ok(chef)
+    }
+
+    async fn fire_chefs(&self, ids: &[i32]) -> Result<u64, DbErr> {
+        fire_chefs(&*self.db, &self.actor, ids, soft_delete::now()).await
+    }
+
+    async fn restore_chef(&self, id: i32) -> Result<chef::Model, DbErr> {
+        let txn = self.db.begin().await?;
+        let chef = Chef::only_deleted()
+            .filter(chef::Column::Id.eq(id))
+            .one(&txn)
+            .await?
+            .ok_or_else(|| deleted_chef_not_found(id))?;
+        find_bakery(&txn, chef.bakery_id).await?;
+        Chef::update_many()
+            .col_expr(chef::Column::DeletedAt, Expr::value(None::<DateTimeUtc>))
+            .filter(chef::Column::Id.eq(id))
+            .exec(&txn)
+            .await?;
+
+        let restored = chef::Model {
+            deleted_at: None,
+            ..chef.clone()
+        };
+        let changes = vec![(Some(chef), Some(restored.clone()))];
+        let now = soft_delete::now();
+        history::record::<ChefHistory, _>(&txn, Operation::Restore, &self.actor, now, changes)
+            .await?;
+        txn.commit().await?;
+        Ok(restored)
+    }
+
+    async fn purge_chefs(&self, deleted_before: DateTimeUtc) -> Result<u64, DbErr> {
+        let txn = self.db.begin().await?;
+        let chefs = Chef::find()
+            .filter(chef::Column::DeletedAt.lt(deleted_before))
+            .all(&txn)
+            .await?;
+        let result = Chef::delete_many()
+            .filter(chef::Column::Id.is_in(chefs.iter().map(|chef| chef.id)))
+            .exec(&txn)
+            .await?;
+        let changes = removals(chefs);
+        let now = soft_delete::now();
+        history::record::<ChefHistory, _>(&txn, Operation::Purge, &self.actor, now, changes)
+            .await?;
+        txn.commit().await?;
+        Ok(result.rows_affected)
+    }
+
+    async fn chef_history(&self, id: i32) -> Result<Vec<Change<chef::Model>>, DbErr> {
+        history::changes::<ChefHistory, _>(&*self.db, id).await
+    }
+}
+
+#[async_trait]
+impl<D, C> Transactional for SeaOrmRepository<D>
+where
+    D: Deref<Target = C> + Send + Sync,
+    C: ConnectionTrait + TransactionTrait + Send + Sync,
+{
+    type Transaction = SeaOrmRepository<Box<DatabaseTransaction>>;
+
+    async fn begin(&self) -> Result<Self::Transaction, DbErr> {
+        Ok(SeaOrmRepository {
+            db: Box::new(self.db.begin().await?),
+            actor: self.actor.clone(),
+        })
+    }
+}
+
+#[async_trait]
+impl Transaction for SeaOrmRepository<Box<DatabaseTransaction>> {
+    async fn commit(self) -> Result<(), DbErr> {
+        self.db.commit().await
+    }
+}
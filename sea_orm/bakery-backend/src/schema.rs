@@ -0,0 +1,142 @@
+//! Checks that the entities in `entities/` describe the schema the migrations build.
+//!
+//! One in-memory SQLite database is migrated, another gets its tables straight from the
+//! entities, and the two must agree on tables, columns, foreign keys and unique constraints.
+
+use futures::executor::block_on;
+use migration::{Migrator, MigratorTrait};
+use sea_orm::*;
+
+use crate::config::DatabaseConfig;
+use crate::entities::{bakery, bakery_history, chef, chef_history};
+
+#[derive(Debug, PartialEq, Eq, FromQueryResult)]
+struct ColumnInfo {
+    name: String,
+    column_type: String,
+    not_null: bool,
+    default_value: Option<String>,
+    primary_key: i32,
+}
+
+#[derive(Debug, PartialEq, Eq, FromQueryResult)]
+struct ForeignKeyInfo {
+    from_column: String,
+    to_table: String,
+    to_column: String,
+    on_update: String,
+    on_delete: String,
+}
+
+/// A unique constraint or index, whatever its name.
+#[derive(Debug, PartialEq, Eq, FromQueryResult)]
+struct IndexInfo {
+    columns: String,
+    is_unique: bool,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct TableInfo {
+    name: String,
+    columns: Vec<ColumnInfo>,
+    foreign_keys: Vec<ForeignKeyInfo>,
+    indexes: Vec<IndexInfo>,
+}
+
+async fn tables(db: &DatabaseConnection) -> Result<Vec<TableInfo>, DbErr> {
+    let names = db
+        .query_all(Statement::from_string(
+            DbBackend::Sqlite,
+            "SELECT name FROM sqlite_master WHERE type = 'table' \
+             AND name NOT LIKE 'sqlite_%' AND name != 'seaql_migrations' ORDER BY name"
+                .to_owned(),
+        ))
+        .await?
+        .iter()
+        .map(|row| row.try_get::<String>("", "name"))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut tables = vec![];
+    for name in names {
+        let columns = ColumnInfo::find_by_statement(Statement::from_sql_and_values(
+            DbBackend::Sqlite,
+            r#"SELECT name, type AS column_type, "notnull" AS not_null, dflt_value AS default_value,
+                pk AS primary_key FROM pragma_table_info(?) ORDER BY name"#,
+            [name.clone().into()],
+        ))
+        .all(db)
+        .await?;
+        let foreign_keys = ForeignKeyInfo::find_by_statement(Statement::from_sql_and_values(
+            DbBackend::Sqlite,
+            r#"SELECT "from" AS from_column, "table" AS to_table, "to" AS to_column, on_update,
+                on_delete FROM pragma_foreign_key_list(?) ORDER BY "from""#,
+            [name.clone().into()],
+        ))
+        .all(db)
+        .await?;
+        let indexes = IndexInfo::find_by_statement(Statement::from_sql_and_values(
+            DbBackend::Sqlite,
+            r#"SELECT group_concat(info.name) AS columns, list."unique" AS is_unique
+                FROM pragma_index_list(?) AS list JOIN pragma_index_info(list.name) AS info
+                GROUP BY list.name ORDER BY columns"#,
+            [name.clone().into()],
+        ))
+        .all(db)
+        .await?;
+        tables.push(TableInfo {
+            name,
+            columns,
+            foreign_keys,
+            indexes,
+        });
+    }
+    Ok(tables)
+}
+
+#[test]
+fn test_entities_match_migrations() {
+    block_on(async {
+        let migrated = DatabaseConfig::default().connect().await.unwrap();
+        Migrator::up(&migrated, None).await.unwrap();
+
+        let from_entities = DatabaseConfig::default().connect().await.unwrap();
+        let schema = Schema::new(DbBackend::Sqlite);
+        for stmt in [
+            schema.create_table_from_entity(bakery::Entity),
+            schema.create_table_from_entity(chef::Entity),
+            schema.create_table_from_entity(bakery_history::Entity),
+            schema.create_table_from_entity(chef_history::Entity),
+        ] {
+            from_entities
+                .execute(DbBackend::Sqlite.build(&stmt))
+                .await
+                .unwrap();
+        }
+        // Entities cannot declare unique keys over several columns.
+        for stmt in [
+            sea_query::Index::create()
+                .name("idx_bakery_history_version")
+                .table(bakery_history::Entity)
+                .col(bakery_history::Column::BakeryId)
+                .col(bakery_history::Column::Version)
+                .unique()
+                .to_owned(),
+            sea_query::Index::create()
+                .name("idx_chef_history_version")
+                .table(chef_history::Entity)
+                .col(chef_history::Column::ChefId)
+                .col(chef_history::Column::Version)
+                .unique()
+                .to_owned(),
+        ] {
+            from_entities
+                .execute(DbBackend::Sqlite.build(&stmt))
+                .await
+                .unwrap();
+        }
+
+        let expected = tables(&migrated).await.unwrap();
+        assert_eq!(tables(&from_entities).await.unwrap(), expected);
+        assert!(!expected.is_empty());
+    });
+}
@@ -0,0 +1,87 @@
+//! Bakeries and chefs are not deleted but marked with a `deleted_at` time, and left out
+//! of every query unless asked for. Deleting a bakery marks its chefs with the same time,
+//! so restoring it brings back those chefs and no others. Rows are only really deleted
+//! once purged.
+
+use chrono::{DurationRound, Utc};
+use sea_orm::entity::prelude::*;
+use sea_orm::sea_query::SimpleExpr;
+
+use crate::entities::{bakery, chef};
+
+/// Which rows a query sees.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Scope {
+    #[default]
+    Active,
+    WithDeleted,
+    OnlyDeleted,
+}
+
+impl Scope {
+    /// The condition on `deleted_at`, unless every row is in scope.
+    pub fn condition(self, deleted_at: impl ColumnTrait) -> Option<SimpleExpr> {
+        match self {
+            Scope::Active => Some(deleted_at.is_null()),
+            Scope::WithDeleted => None,
+            Scope::OnlyDeleted => Some(deleted_at.is_not_null()),
+        }
+    }
+
+    pub fn includes(self, deleted_at: Option<DateTimeUtc>) -> bool {
+        match self {
+            Scope::Active => deleted_at.is_none(),
+            Scope::WithDeleted => true,
+            Scope::OnlyDeleted => deleted_at.is_some(),
+        }
+    }
+}
+
+pub trait SoftDelete: EntityTrait {
+    fn deleted_at() -> Self::Column;
+
+    fn find_in(scope: Scope) -> Select<Self> {
+        match scope.condition(Self::deleted_at()) {
+            Some(condition) => Self::find().filter(condition),
+            None => Self::find(),
+        }
+    }
+
+    /// The rows not deleted, which is where queries should start.
+    fn find_active() -> Select<Self> {
+        Self::find_in(Scope::Active)
+    }
+
+    fn with_deleted() -> Select<Self> {
+        Self::find_in(Scope::WithDeleted)
+    }
+
+    fn only_deleted() -> Select<Self> {
+        Self::find_in(Scope::OnlyDeleted)
+    }
+}
+
+impl SoftDelete for bakery::Entity {
+    fn deleted_at() -> bakery::Column {
+        bakery::Column::DeletedAt
+    }
+}
+
+impl SoftDelete for chef::Entity {
+    fn deleted_at() -> chef::Column {
+        chef::Column::DeletedAt
+    }
+}
+
+/// The time to mark deleted rows with, to the microsecond that every backend keeps, so
+/// that rows deleted together still compare equal once read back.
+pub fn now() -> DateTimeUtc {
+    Utc::now()
+        .duration_trunc(chrono::Duration::microseconds(1))
+        .expect("a microsecond divides any time")
+}
+
+/// Deleted more than `days` ago.
+pub fn cutoff(days: u32) -> DateTimeUtc {
+    now() - chrono::Duration::days(days.into())
+}
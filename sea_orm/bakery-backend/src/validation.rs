@@ -0,0 +1,262 @@
+//! Rules every bakery and chef must follow, checked by their `ActiveModelBehavior` hooks.
+//!
+//! Saves are checked field by field. The only rule on deletes is that a row is soft-deleted
+//! before it is deleted for good, as purges do; what goes with a deleted bakery is up to
+//! [`soft_delete`](crate::soft_delete), which takes its chefs along.
+//!
+//! The hooks can only fail with a [`DbErr`], so a [`ValidationError`] travels as the source
+//! of a `DbErr::TryIntoErr`; [`ValidationError::from_db_err`] gets it back.
+
+use sea_orm::DbErr;
+
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::sync::RwLock;
+
+use crate::contact::ContactDetails;
+
+/// The widest profit margins allowed, also enforced by the schema.
+pub const PROFIT_MARGIN_BOUNDS: RangeInclusive<f64> = 0.0..=100.0;
+
+static PROFIT_MARGIN_RANGE: RwLock<RangeInclusive<f64>> = RwLock::new(PROFIT_MARGIN_BOUNDS);
+
+/// Narrows the profit margins bakeries may have, within [`PROFIT_MARGIN_BOUNDS`].
+pub fn set_profit_margin_range(range: RangeInclusive<f64>) -> Result<(), ValidationError> {
+    let within =
+        PROFIT_MARGIN_BOUNDS.contains(range.start()) && PROFIT_MARGIN_BOUNDS.contains(range.end());
+    if !within || range.is_empty() {
+        return Err(ValidationError::ProfitMarginRange {
+            min: *range.start(),
+            max: *range.end(),
+        });
+    }
+    *PROFIT_MARGIN_RANGE.write().unwrap() = range;
+    Ok(())
+}
+
+pub fn profit_margin_range() -> RangeInclusive<f64> {
+    PROFIT_MARGIN_RANGE.read().unwrap().clone()
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ValidationError {
+    /// A bakery or chef name is empty once trimmed.
+    BlankName {
+        entity: &'static str,
+    },
+    ProfitMarginOutOfRange {
+        value: f64,
+        min: f64,
+        max: f64,
+    },
+    /// The configured profit margin range is not within [`PROFIT_MARGIN_BOUNDS`].
+    ProfitMarginRange {
+        min: f64,
+        max: f64,
+    },
+    DuplicateBakeryName {
+        name: String,
+    },
+    /// An email, phone or address is malformed, or the preferred channel has no contact.
+    ContactDetails {
+        reason: String,
+    },
+    /// A bakery or chef is deleted for good without being soft-deleted first.
+    NotSoftDeleted {
+        entity: &'static str,
+        id: i32,
+    },
+}
+
+impl ValidationError {
+    /// The validation error a hook failed with, if that is why `err` happened.
+    pub fn from_db_err(err: &DbErr) -> Option<&ValidationError> {
+        match err {
+            DbErr::TryIntoErr { source, .. } => source.downcast_ref(),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationError::BlankName { entity } => write!(f, "{} name must not be blank", entity),
+            ValidationError::ProfitMarginOutOfRange { value, min, max } => {
+                write!(
+                    f,
+                    "profit margin {} is not between {} and {}",
+                    value, min, max
+                )
+            }
+            ValidationError::ProfitMarginRange { min, max } => write!(
+                f,
+                "profit margin range {}..={} is not within {}..={}",
+                min,
+                max,
+                PROFIT_MARGIN_BOUNDS.start(),
+                PROFIT_MARGIN_BOUNDS.end()
+            ),
+            ValidationError::DuplicateBakeryName { name } => {
+                write!(f, "a bakery named {:?} already exists", name)
+            }
+            ValidationError::ContactDetails { reason } => {
+                write!(f, "invalid contact details: {}", reason)
+            }
+            ValidationError::NotSoftDeleted { entity, id } => {
+                write!(f, "{} {} must be deleted before it is purged", entity, id)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+impl From<ValidationError> for DbErr {
+    fn from(err: ValidationError) -> Self {
+        DbErr::TryIntoErr {
+            from: "ActiveModel",
+            into: "valid Model",
+            source: Box::new(err),
+        }
+    }
+}
+
+/// `name` trimmed, unless nothing is left.
+pub fn name(entity: &'static str, name: &str) -> Result<String, ValidationError> {
+    match name.trim() {
+        "" => Err(ValidationError::BlankName { entity }),
+        name => Ok(name.to_owned()),
+    }
+}
+
+pub fn profit_margin(value: f64) -> Result<(), ValidationError> {
+    let range = profit_margin_range();
+    match range.contains(&value) {
+        true => Ok(()),
+        false => Err(ValidationError::ProfitMarginOutOfRange {
+            value,
+            min: *range.start(),
+            max: *range.end(),
+        }),
+    }
+}
+
+/// `details` with every contact trimmed and emails in lowercase, unless one is malformed.
+pub fn contact_details(details: ContactDetails) -> Result<ContactDetails, ValidationError> {
+    let invalid = |reason: String| Err(ValidationError::ContactDetails { reason });
+
+    let emails: Vec<String> = details
+        .emails
+        .iter()
+        .map(|email| email.trim().to_lowercase())
+        .collect();
+    for email in &emails {
+        let well_formed = match email.split_once('@') {
+            Some((user, domain)) => {
+                !user.is_empty()
+                    && !domain.contains('@')
+                    && domain.contains('.')
+                    && !domain.starts_with('.')
+                    && !domain.ends_with('.')
+                    && !email.contains(char::is_whitespace)
+            }
+            None => false,
+        };
+        if !well_formed {
+            return invalid(format!("{:?} is not an email address", email));
+        }
+    }
+
+    let phones: Vec<String> = details
+        .phones
+        .iter()
+        .map(|phone| phone.trim().to_owned())
+        .collect();
+    for phone in &phones {
+        let digits = phone.chars().filter(char::is_ascii_digit).count();
+        let number = phone.strip_prefix('+').unwrap_or(phone);
+        if digits < 3
+            || !number
+                .chars()
+                .all(|c| c.is_ascii_digit() || " -.()".contains(c))
+        {
+            return invalid(format!("{:?} is not a phone number", phone));
+        }
+    }
+
+    let address = match details.address.as_deref().map(str::trim) {
+        Some("") => return invalid("the address is blank".to_owned()),
+        address => address.map(str::to_owned),
+    };
+
+    let details = ContactDetails {
+        emails,
+        phones,
+        address,
+        preferred: details.preferred,
+    };
+    if let Some(channel) = details.preferred {
+        if !details.reachable_by(channel) {
+            return invalid(format!(
+                "{} is preferred but there is no way to use it",
+                channel
+            ));
+        }
+    }
+    Ok(details)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contact::Channel;
+
+    #[test]
+    fn test_rules() {
+        assert_eq!(name("bakery", "  Happy Bakery ").unwrap(), "Happy Bakery");
+        assert_eq!(
+            name("chef", " \t").unwrap_err(),
+            ValidationError::BlankName { entity: "chef" }
+        );
+
+        assert!(profit_margin(17.89).is_ok());
+        assert!(profit_margin(-100.0).is_err());
+        assert!(set_profit_margin_range(-5.0..=10.0).is_err());
+
+        let details = |emails: &[&str], phones: &[&str], preferred| ContactDetails {
+            emails: emails.iter().map(|email| email.to_string()).collect(),
+            phones: phones.iter().map(|phone| phone.to_string()).collect(),
+            address: None,
+            preferred,
+        };
+        assert_eq!(
+            contact_details(details(
+                &[" Jolie@Example.com"],
+                &["+33 1 23 45 67 89 "],
+                None
+            ))
+            .unwrap(),
+            details(&["jolie@example.com"], &["+33 1 23 45 67 89"], None)
+        );
+        assert!(contact_details(details(&["jolie"], &[], None)).is_err());
+        assert!(contact_details(details(&["jolie@example"], &[], None)).is_err());
+        assert!(contact_details(details(&[], &["call me"], None)).is_err());
+        assert!(contact_details(details(&["a@b.fr"], &[], Some(Channel::Phone))).is_err());
+        let blank_address = ContactDetails {
+            address: Some(" ".to_owned()),
+            ..ContactDetails::default()
+        };
+        assert!(contact_details(blank_address).is_err());
+
+        let err = DbErr::from(ValidationError::BlankName { entity: "bakery" });
+        assert_eq!(
+            ValidationError::from_db_err(&err),
+            Some(&ValidationError::BlankName { entity: "bakery" })
+        );
+        assert_eq!(
+            ValidationError::from_db_err(&DbErr::Custom("other".to_owned())),
+            None
+        );
+    }
+}